@@ -0,0 +1,52 @@
+use std::{
+    env, fs,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    if env::var_os("CARGO_FEATURE_EMBED_TEMPLATES").is_none() {
+        return;
+    }
+    println!("cargo:rerun-if-changed=templates");
+
+    let root = Path::new(&env::var_os("CARGO_MANIFEST_DIR").unwrap()).join("templates");
+    let mut games = fs::read_dir(&root)
+        .expect("Failed to read templates directory")
+        .map(|e| e.unwrap().path())
+        .filter(|p| p.join("root.json").is_file())
+        .collect::<Vec<_>>();
+    games.sort();
+
+    let out_path = Path::new(&env::var_os("OUT_DIR").unwrap()).join("templates.rs");
+    let mut out = BufWriter::new(fs::File::create(out_path).unwrap());
+    writeln!(out, "pub static TEMPLATES: &[(&str, &[(&str, &str)])] = &[").unwrap();
+    for game in games {
+        let mut files = vec![];
+        collect_files(&game, &mut files);
+        files.sort();
+        let name = game.file_name().unwrap().to_str().unwrap();
+        writeln!(out, "    ({:?}, &[", name).unwrap();
+        for file in files {
+            let relative = file.strip_prefix(&game).unwrap().to_str().unwrap().replace('\\', "/");
+            writeln!(out, "        ({:?}, include_str!({:?})),", relative, file).unwrap();
+        }
+        writeln!(out, "    ]),").unwrap();
+    }
+    writeln!(out, "];").unwrap();
+}
+
+/// Template JSON files under `dir`, skipping the schemas.
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) {
+    for entry in fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            collect_files(&path, files);
+        } else if path.extension().map_or(false, |e| e == "json")
+            && !path.to_string_lossy().ends_with(".schema.json")
+        {
+            files.push(path);
+        }
+    }
+}
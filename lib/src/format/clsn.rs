@@ -0,0 +1,502 @@
+use std::{
+    io::{Cursor, Seek, Write},
+    marker::PhantomData,
+};
+
+use anyhow::{anyhow, bail, ensure, Result};
+use binrw::{binrw, BinReaderExt, BinWriterExt, Endian};
+use zerocopy::{ByteOrder, U32};
+
+use crate::format::{
+    chunk::ChunkDescriptor, rfrm::FormDescriptor, slice_chunks, CAABox, COBBox, CTransform4f,
+    CVector3f, FourCC, MaybeParsed, TaggedVec,
+};
+
+// CAABoxCollisionTree
+pub const K_FORM_CLSN: FourCC = FourCC(*b"CLSN");
+// COBBoxCollisionTree
+pub const K_FORM_DCLN: FourCC = FourCC(*b"DCLN");
+
+// COBBCollisionTree header (only used in DCLN)
+pub const K_CHUNK_INFO: FourCC = FourCC(*b"INFO");
+// Vertex data
+pub const K_CHUNK_VERT: FourCC = FourCC(*b"VERT");
+// Material data
+pub const K_CHUNK_MTRL: FourCC = FourCC(*b"MTRL");
+// Triangle data
+pub const K_CHUNK_TRIS: FourCC = FourCC(*b"TRIS");
+// Bounding volume tree
+pub const K_CHUNK_TREE: FourCC = FourCC(*b"TREE");
+
+pub const K_CLSN_READER_VERSION: u32 = 11;
+pub const K_CLSN_WRITER_VERSION: u32 = 22;
+
+pub const K_DCLN_READER_VERSION: u32 = 9;
+pub const K_DCLN_WRITER_VERSION: u32 = 18;
+
+/// Maximum number of triangles in a leaf node when building a tree.
+const MAX_LEAF_TRIANGLES: usize = 8;
+
+#[binrw]
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct CCollisionMaterial {
+    pub orientation: u32,
+    pub material_type: u32,
+    pub world_type: u32,
+    pub behavior_list: u32,
+    pub filter_list: u32,
+}
+
+#[binrw]
+#[derive(Clone, Debug, Default)]
+pub struct CIndexedTriangle {
+    pub idx1: u32,
+    pub idx2: u32,
+    pub idx3: u32,
+    pub material: u16,
+    pub unk: u16,
+}
+
+#[binrw]
+#[derive(Clone, Debug, Default)]
+pub struct AABoxTreeNode {
+    pub bounds: CAABox,
+    pub start: u32,
+    pub end: u32,
+    pub unk1: u8,
+    pub unk2: u8,
+    pub unk3: u8,
+    pub unk4: u8,
+}
+
+#[binrw]
+#[derive(Clone, Debug, Default)]
+pub struct OBBoxTreeNode {
+    pub bounds: COBBox,
+    pub start: u32,
+    pub end: u32,
+    pub unk1: u8,
+    pub unk2: u8,
+    pub unk3: u8,
+    pub unk4: u8,
+}
+
+#[derive(Clone, Debug)]
+pub enum CollisionTree {
+    /// CLSN
+    AABox(Vec<AABoxTreeNode>),
+    /// DCLN
+    OBBox(Vec<OBBoxTreeNode>),
+}
+
+impl CollisionTree {
+    pub fn len(&self) -> usize {
+        match self {
+            CollisionTree::AABox(nodes) => nodes.len(),
+            CollisionTree::OBBox(nodes) => nodes.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool { self.len() == 0 }
+
+    /// Triangle range (start, end) of each node.
+    pub fn ranges(&self) -> Vec<(u32, u32)> {
+        match self {
+            CollisionTree::AABox(nodes) => nodes.iter().map(|n| (n.start, n.end)).collect(),
+            CollisionTree::OBBox(nodes) => nodes.iter().map(|n| (n.start, n.end)).collect(),
+        }
+    }
+
+    /// Builds a tree over the given triangles, reordering them so that each node covers the
+    /// contiguous range `start..end`. Nodes are emitted depth-first, parents before children.
+    ///
+    /// Experimental: this order is the same guess [`Self::depths`] makes, and `unk1..unk4` are
+    /// left at 0. Neither has been checked against a retail tree.
+    pub fn build(
+        oriented: bool,
+        vertices: &[CVector3f],
+        triangles: &mut Vec<CIndexedTriangle>,
+    ) -> Self {
+        let centroids: Vec<[f32; 3]> = triangles
+            .iter()
+            .map(|t| {
+                let [a, b, c] = triangle_points(vertices, t);
+                [0, 1, 2].map(|i| (a[i] + b[i] + c[i]) / 3.0)
+            })
+            .collect();
+        let mut order: Vec<usize> = (0..triangles.len()).collect();
+        let mut ranges = vec![];
+        split_node(&centroids, &mut order, 0, &mut ranges);
+        *triangles = order.iter().map(|&i| triangles[i].clone()).collect();
+
+        let points = |start: u32, end: u32| {
+            triangles[start as usize..end as usize]
+                .iter()
+                .flat_map(|t| triangle_points(vertices, t))
+                .collect::<Vec<[f32; 3]>>()
+        };
+        if oriented {
+            CollisionTree::OBBox(
+                ranges
+                    .into_iter()
+                    .map(|(start, end)| OBBoxTreeNode {
+                        bounds: oriented_bounds(&points(start, end)),
+                        start,
+                        end,
+                        ..Default::default()
+                    })
+                    .collect(),
+            )
+        } else {
+            CollisionTree::AABox(
+                ranges
+                    .into_iter()
+                    .map(|(start, end)| AABoxTreeNode {
+                        bounds: aligned_bounds(&points(start, end)),
+                        start,
+                        end,
+                        ..Default::default()
+                    })
+                    .collect(),
+            )
+        }
+    }
+
+    /// Depth of each node, guessed from triangle range containment.
+    ///
+    /// This is a heuristic: the node layout has no child links we know of, so it assumes nodes
+    /// are in depth-first order with children covering a sub-range of their parent's triangles.
+    /// Only meant for visualisation.
+    pub fn depths(&self) -> Vec<u32> {
+        let mut stack: Vec<(u32, u32)> = vec![];
+        self.ranges()
+            .into_iter()
+            .map(|(start, end)| {
+                while let Some(&(p_start, p_end)) = stack.last() {
+                    if start >= p_start && end <= p_end {
+                        break;
+                    }
+                    stack.pop();
+                }
+                let depth = stack.len() as u32;
+                stack.push((start, end));
+                depth
+            })
+            .collect()
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct CollisionData<O: ByteOrder> {
+    pub kind: FourCC,
+    pub bounds: Option<MaybeParsed<CAABox>>,
+    pub vertices: Vec<CVector3f>,
+    pub materials: Vec<CCollisionMaterial>,
+    pub triangles: Vec<CIndexedTriangle>,
+    pub tree: Option<MaybeParsed<CollisionTree>>,
+    _marker: PhantomData<O>,
+}
+
+impl<O> CollisionData<O>
+where O: ByteOrder + 'static
+{
+    pub fn slice(data: &[u8]) -> Result<Self> {
+        let (form_desc, form_data, _) = FormDescriptor::<O>::slice(data)?;
+        match form_desc.id {
+            K_FORM_CLSN => {
+                ensure!(form_desc.reader_version.get() == K_CLSN_READER_VERSION);
+                ensure!(form_desc.writer_version.get() == K_CLSN_WRITER_VERSION);
+            }
+            K_FORM_DCLN => {
+                ensure!(form_desc.reader_version.get() == K_DCLN_READER_VERSION);
+                ensure!(form_desc.writer_version.get() == K_DCLN_WRITER_VERSION);
+            }
+            id => bail!("Unknown collision form: {id:?}"),
+        }
+
+        let mut bounds: Option<MaybeParsed<CAABox>> = None;
+        let mut vertices: Option<Vec<CVector3f>> = None;
+        let mut materials: Option<Vec<CCollisionMaterial>> = None;
+        let mut triangles: Option<Vec<CIndexedTriangle>> = None;
+        let mut tree: Option<MaybeParsed<CollisionTree>> = None;
+        slice_chunks::<O, _, _>(
+            form_data,
+            |chunk, data| {
+                let mut reader = Cursor::new(data);
+                match chunk.id {
+                    K_CHUNK_INFO => {
+                        bounds = Some(MaybeParsed::read(chunk.id, data, Endian::Little))
+                    }
+                    K_CHUNK_VERT => {
+                        let v: TaggedVec<u32, _> = reader.read_type(Endian::Little)?;
+                        vertices = Some(v.data);
+                    }
+                    K_CHUNK_MTRL => {
+                        let v: TaggedVec<u32, _> = reader.read_type(Endian::Little)?;
+                        materials = Some(v.data);
+                    }
+                    K_CHUNK_TRIS => {
+                        let v: TaggedVec<u32, _> = reader.read_type(Endian::Little)?;
+                        triangles = Some(v.data);
+                    }
+                    K_CHUNK_TREE => {
+                        tree = Some(if form_desc.id == K_FORM_CLSN {
+                            MaybeParsed::<TaggedVec<u32, _>>::read(chunk.id, data, Endian::Little)
+                                .map(|v| CollisionTree::AABox(v.data))
+                        } else {
+                            MaybeParsed::<TaggedVec<u32, _>>::read(chunk.id, data, Endian::Little)
+                                .map(|v| CollisionTree::OBBox(v.data))
+                        })
+                    }
+                    id => bail!("Unknown {:?} chunk: {id:?}", form_desc.id),
+                }
+                Ok(())
+            },
+            |form, _data| bail!("Unknown {:?} form: {:?}", form_desc.id, form.id),
+        )?;
+
+        let vertices = vertices.ok_or_else(|| anyhow!("Missing VERT chunk"))?;
+        let materials = materials.ok_or_else(|| anyhow!("Missing MTRL chunk"))?;
+        let triangles = triangles.ok_or_else(|| anyhow!("Missing TRIS chunk"))?;
+        for (idx, tri) in triangles.iter().enumerate() {
+            ensure!(
+                [tri.idx1, tri.idx2, tri.idx3].iter().all(|&i| (i as usize) < vertices.len()),
+                "Triangle {idx} vertex index out of range"
+            );
+            ensure!(
+                (tri.material as usize) < materials.len(),
+                "Triangle {idx} material index out of range"
+            );
+        }
+        Ok(Self {
+            kind: form_desc.id,
+            bounds,
+            vertices,
+            materials,
+            triangles,
+            tree,
+            _marker: PhantomData,
+        })
+    }
+
+    /// Builds collision data from a triangle soup, generating the tree for the given form.
+    pub fn new(
+        kind: FourCC,
+        vertices: Vec<CVector3f>,
+        materials: Vec<CCollisionMaterial>,
+        mut triangles: Vec<CIndexedTriangle>,
+    ) -> Result<Self> {
+        ensure!(kind == K_FORM_CLSN || kind == K_FORM_DCLN, "Unknown collision form: {kind:?}");
+        ensure!(materials.len() <= u16::MAX as usize + 1, "Too many materials");
+        for (idx, tri) in triangles.iter().enumerate() {
+            ensure!(
+                [tri.idx1, tri.idx2, tri.idx3].iter().all(|&i| (i as usize) < vertices.len()),
+                "Triangle {idx} vertex index out of range"
+            );
+            ensure!(
+                (tri.material as usize) < materials.len(),
+                "Triangle {idx} material index out of range"
+            );
+        }
+        let tree = CollisionTree::build(kind == K_FORM_DCLN, &vertices, &mut triangles);
+        let bounds = (kind == K_FORM_DCLN).then(|| {
+            MaybeParsed::Parsed(aligned_bounds(
+                &vertices.iter().map(|v| v.to_array()).collect::<Vec<_>>(),
+            ))
+        });
+        let tree = Some(MaybeParsed::Parsed(tree));
+        Ok(Self { kind, bounds, vertices, materials, triangles, tree, _marker: PhantomData })
+    }
+
+    pub fn write<W: Write + Seek>(&self, w: &mut W) -> Result<()> {
+        let (reader_version, writer_version) = match self.kind {
+            K_FORM_CLSN => (K_CLSN_READER_VERSION, K_CLSN_WRITER_VERSION),
+            K_FORM_DCLN => (K_DCLN_READER_VERSION, K_DCLN_WRITER_VERSION),
+            id => bail!("Unknown collision form: {id:?}"),
+        };
+        FormDescriptor::<O> {
+            id: self.kind,
+            reader_version: U32::new(reader_version),
+            writer_version: U32::new(writer_version),
+            ..Default::default()
+        }
+        .write(w, |w| {
+            if let Some(bounds) = &self.bounds {
+                ChunkDescriptor::<O> { id: K_CHUNK_INFO, ..Default::default() }
+                    .write(w, |w| Ok(w.write_type(bounds, Endian::Little)?))?;
+            }
+            ChunkDescriptor::<O> { id: K_CHUNK_VERT, ..Default::default() }.write(w, |w| {
+                let v = TaggedVec::<u32, _>::new(self.vertices.clone());
+                Ok(w.write_type(&v, Endian::Little)?)
+            })?;
+            ChunkDescriptor::<O> { id: K_CHUNK_MTRL, ..Default::default() }.write(w, |w| {
+                let v = TaggedVec::<u32, _>::new(self.materials.clone());
+                Ok(w.write_type(&v, Endian::Little)?)
+            })?;
+            ChunkDescriptor::<O> { id: K_CHUNK_TRIS, ..Default::default() }.write(w, |w| {
+                let v = TaggedVec::<u32, _>::new(self.triangles.clone());
+                Ok(w.write_type(&v, Endian::Little)?)
+            })?;
+            if let Some(tree) = &self.tree {
+                ChunkDescriptor::<O> { id: K_CHUNK_TREE, ..Default::default() }.write(w, |w| {
+                    match tree {
+                        MaybeParsed::Parsed(CollisionTree::AABox(nodes)) => {
+                            w.write_type(&TaggedVec::<u32, _>::new(nodes.clone()), Endian::Little)?
+                        }
+                        MaybeParsed::Parsed(CollisionTree::OBBox(nodes)) => {
+                            w.write_type(&TaggedVec::<u32, _>::new(nodes.clone()), Endian::Little)?
+                        }
+                        MaybeParsed::Raw(data) => w.write_all(data)?,
+                    }
+                    Ok(())
+                })?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Bounding box from the INFO chunk, if present and parsed.
+    pub fn bounds(&self) -> Option<&CAABox> { self.bounds.as_ref().and_then(|b| b.parsed()) }
+
+    /// Node tree from the TREE chunk, if present and parsed.
+    pub fn tree(&self) -> Option<&CollisionTree> { self.tree.as_ref().and_then(|t| t.parsed()) }
+
+    /// Vertex positions of a triangle.
+    pub fn triangle_vertices(&self, tri: &CIndexedTriangle) -> [CVector3f; 3] {
+        [
+            self.vertices[tri.idx1 as usize],
+            self.vertices[tri.idx2 as usize],
+            self.vertices[tri.idx3 as usize],
+        ]
+    }
+}
+
+fn triangle_points(vertices: &[CVector3f], tri: &CIndexedTriangle) -> [[f32; 3]; 3] {
+    [tri.idx1, tri.idx2, tri.idx3].map(|i| vertices[i as usize].to_array())
+}
+
+/// Median split along the longest axis of the triangle centroids.
+fn split_node(
+    centroids: &[[f32; 3]],
+    order: &mut [usize],
+    offset: u32,
+    ranges: &mut Vec<(u32, u32)>,
+) {
+    let end = offset + order.len() as u32;
+    ranges.push((offset, end));
+    if order.len() <= MAX_LEAF_TRIANGLES {
+        return;
+    }
+    let mut min = [f32::MAX; 3];
+    let mut max = [f32::MIN; 3];
+    for &idx in order.iter() {
+        for i in 0..3 {
+            min[i] = min[i].min(centroids[idx][i]);
+            max[i] = max[i].max(centroids[idx][i]);
+        }
+    }
+    let axis = (0..3).max_by(|&a, &b| (max[a] - min[a]).total_cmp(&(max[b] - min[b]))).unwrap();
+    order.sort_by(|&a, &b| centroids[a][axis].total_cmp(&centroids[b][axis]));
+    let mid = order.len() / 2;
+    let (left, right) = order.split_at_mut(mid);
+    split_node(centroids, left, offset, ranges);
+    split_node(centroids, right, offset + mid as u32, ranges);
+}
+
+fn aligned_bounds(points: &[[f32; 3]]) -> CAABox {
+    let mut bounds = CAABox::default();
+    for p in points {
+        let (min, max) = (bounds.min.to_array(), bounds.max.to_array());
+        bounds.min = [0, 1, 2].map(|i| min[i].min(p[i])).into();
+        bounds.max = [0, 1, 2].map(|i| max[i].max(p[i])).into();
+    }
+    bounds
+}
+
+/// Oriented box along the principal axes of the points.
+fn oriented_bounds(points: &[[f32; 3]]) -> COBBox {
+    let n = points.len().max(1) as f32;
+    let mut mean = [0.0f32; 3];
+    for p in points {
+        for i in 0..3 {
+            mean[i] += p[i] / n;
+        }
+    }
+    let mut cov = [[0.0f32; 3]; 3];
+    for p in points {
+        let d = [p[0] - mean[0], p[1] - mean[1], p[2] - mean[2]];
+        for i in 0..3 {
+            for j in 0..3 {
+                cov[i][j] += d[i] * d[j] / n;
+            }
+        }
+    }
+    let mut axes = eigenvectors(cov);
+    // Keep the basis right-handed
+    axes[2] = cross(axes[0], axes[1]);
+
+    let mut min = [f32::MAX; 3];
+    let mut max = [f32::MIN; 3];
+    for p in points {
+        for i in 0..3 {
+            let d = dot(axes[i], *p);
+            min[i] = min[i].min(d);
+            max[i] = max[i].max(d);
+        }
+    }
+    let mut center = [0.0f32; 3];
+    for i in 0..3 {
+        let mid = (min[i] + max[i]) / 2.0;
+        for j in 0..3 {
+            center[j] += axes[i][j] * mid;
+        }
+    }
+    let row = |j: usize| mint::Vector4::from([axes[0][j], axes[1][j], axes[2][j], center[j]]);
+    COBBox {
+        xf: CTransform4f::from(mint::RowMatrix3x4 { x: row(0), y: row(1), z: row(2) }),
+        extents: CVector3f::new(
+            (max[0] - min[0]) / 2.0,
+            (max[1] - min[1]) / 2.0,
+            (max[2] - min[2]) / 2.0,
+        ),
+    }
+}
+
+/// Eigenvectors of a symmetric 3x3 matrix (cyclic Jacobi).
+fn eigenvectors(mut a: [[f32; 3]; 3]) -> [[f32; 3]; 3] {
+    let mut v = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+    for _ in 0..32 {
+        let (p, q) = [(0, 1), (0, 2), (1, 2)]
+            .into_iter()
+            .max_by(|&(a1, b1), &(a2, b2)| a[a1][b1].abs().total_cmp(&a[a2][b2].abs()))
+            .unwrap();
+        if a[p][q].abs() < 1e-9 {
+            break;
+        }
+        let theta = (a[q][q] - a[p][p]) / (2.0 * a[p][q]);
+        let t = theta.signum() / (theta.abs() + (theta * theta + 1.0).sqrt());
+        let c = 1.0 / (t * t + 1.0).sqrt();
+        let s = t * c;
+        let mut j = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+        j[p][p] = c;
+        j[q][q] = c;
+        j[p][q] = s;
+        j[q][p] = -s;
+        a = mul(transpose(j), mul(a, j));
+        v = mul(v, j);
+    }
+    // Columns of v are the eigenvectors
+    [0, 1, 2].map(|i| [v[0][i], v[1][i], v[2][i]])
+}
+
+fn mul(a: [[f32; 3]; 3], b: [[f32; 3]; 3]) -> [[f32; 3]; 3] {
+    [0, 1, 2].map(|i| [0, 1, 2].map(|j| (0..3).map(|k| a[i][k] * b[k][j]).sum()))
+}
+
+fn transpose(a: [[f32; 3]; 3]) -> [[f32; 3]; 3] { [0, 1, 2].map(|i| [0, 1, 2].map(|j| a[j][i])) }
+
+fn dot(a: [f32; 3], b: [f32; 3]) -> f32 { a[0] * b[0] + a[1] * b[1] + a[2] * b[2] }
+
+fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[1] * b[2] - a[2] * b[1], a[2] * b[0] - a[0] * b[2], a[0] * b[1] - a[1] * b[0]]
+}
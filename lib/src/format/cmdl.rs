@@ -1,16 +1,20 @@
-use std::{io::Cursor, marker::PhantomData};
+use std::{
+    io::{Cursor, Seek, Write},
+    marker::PhantomData,
+};
 
 use anyhow::{bail, ensure, Result};
-use binrw::{binrw, BinReaderExt, Endian};
+use binrw::{binrw, BinReaderExt, BinWriterExt, Endian};
+use half::f16;
 use uuid::Uuid;
-use zerocopy::ByteOrder;
+use zerocopy::{ByteOrder, U32};
 
 use crate::{
     format::{
-        rfrm::FormDescriptor, slice_chunks, CAABox, CColor4f, CMatrix4f, CStringFixed, CVector4i,
-        FourCC,
+        chunk::ChunkDescriptor, rfrm::FormDescriptor, slice_chunks, CAABox, CColor4f, CMatrix4f,
+        CStringFixed, CVector4i, FourCC,
     },
-    util::compression::decompress_buffer,
+    util::compression::{compress_buffer, decompress_buffer},
 };
 
 // Cooked model
@@ -216,6 +220,138 @@ impl EVertexDataFormat {
         }
     }
 
+    pub fn component_count(self) -> usize {
+        match self {
+            EVertexDataFormat::Unknown => 0,
+            EVertexDataFormat::R8Unorm
+            | EVertexDataFormat::R8Uint
+            | EVertexDataFormat::R8Snorm
+            | EVertexDataFormat::R8Sint
+            | EVertexDataFormat::R16Unorm
+            | EVertexDataFormat::R16Uint
+            | EVertexDataFormat::R16Snorm
+            | EVertexDataFormat::R16Sint
+            | EVertexDataFormat::R16Float
+            | EVertexDataFormat::R32Uint
+            | EVertexDataFormat::R32Sint
+            | EVertexDataFormat::R32Float => 1,
+            EVertexDataFormat::Rg8Unorm
+            | EVertexDataFormat::Rg8Uint
+            | EVertexDataFormat::Rg8Snorm
+            | EVertexDataFormat::Rg8Sint
+            | EVertexDataFormat::Rg16Unorm
+            | EVertexDataFormat::Rg16Uint
+            | EVertexDataFormat::Rg16Snorm
+            | EVertexDataFormat::Rg16Sint
+            | EVertexDataFormat::Rg16Float
+            | EVertexDataFormat::Rg32Uint
+            | EVertexDataFormat::Rg32Sint
+            | EVertexDataFormat::Rg32Float => 2,
+            EVertexDataFormat::Rgb32Uint
+            | EVertexDataFormat::Rgb32Sint
+            | EVertexDataFormat::Rgb32Float => 3,
+            EVertexDataFormat::Rgba8Unorm
+            | EVertexDataFormat::Rgba8Uint
+            | EVertexDataFormat::Rgba8Snorm
+            | EVertexDataFormat::Rgba8Sint
+            | EVertexDataFormat::Rgb10a2Unorm
+            | EVertexDataFormat::Rgb10a2Uint
+            | EVertexDataFormat::Rgba16Unorm
+            | EVertexDataFormat::Rgba16Uint
+            | EVertexDataFormat::Rgba16Snorm
+            | EVertexDataFormat::Rgba16Sint
+            | EVertexDataFormat::Rgba16Float
+            | EVertexDataFormat::Rgba32Uint
+            | EVertexDataFormat::Rgba32Sint
+            | EVertexDataFormat::Rgba32Float => 4,
+        }
+    }
+
+    /// Encodes a single vertex attribute. Missing components are written as zero.
+    pub fn encode(self, values: &[f32], out: &mut Vec<u8>) -> Result<()> {
+        let count = self.component_count();
+        let value = |i: usize| values.get(i).copied().unwrap_or_default();
+        let unorm = |v: f32, max: f32| (v.clamp(0.0, 1.0) * max).round();
+        let snorm = |v: f32, max: f32| (v.clamp(-1.0, 1.0) * max).round();
+        match self {
+            EVertexDataFormat::Unknown => bail!("Can't encode unknown vertex format"),
+            EVertexDataFormat::R8Unorm
+            | EVertexDataFormat::Rg8Unorm
+            | EVertexDataFormat::Rgba8Unorm => {
+                out.extend((0..count).map(|i| unorm(value(i), 255.0) as u8))
+            }
+            EVertexDataFormat::R8Uint
+            | EVertexDataFormat::Rg8Uint
+            | EVertexDataFormat::Rgba8Uint => out.extend((0..count).map(|i| value(i) as u8)),
+            EVertexDataFormat::R8Snorm
+            | EVertexDataFormat::Rg8Snorm
+            | EVertexDataFormat::Rgba8Snorm => {
+                out.extend((0..count).map(|i| snorm(value(i), 127.0) as i8 as u8))
+            }
+            EVertexDataFormat::R8Sint
+            | EVertexDataFormat::Rg8Sint
+            | EVertexDataFormat::Rgba8Sint => out.extend((0..count).map(|i| value(i) as i8 as u8)),
+            EVertexDataFormat::R16Unorm
+            | EVertexDataFormat::Rg16Unorm
+            | EVertexDataFormat::Rgba16Unorm => {
+                out.extend((0..count).flat_map(|i| (unorm(value(i), 65535.0) as u16).to_le_bytes()))
+            }
+            EVertexDataFormat::R16Uint
+            | EVertexDataFormat::Rg16Uint
+            | EVertexDataFormat::Rgba16Uint => {
+                out.extend((0..count).flat_map(|i| (value(i) as u16).to_le_bytes()))
+            }
+            EVertexDataFormat::R16Snorm
+            | EVertexDataFormat::Rg16Snorm
+            | EVertexDataFormat::Rgba16Snorm => {
+                out.extend((0..count).flat_map(|i| (snorm(value(i), 32767.0) as i16).to_le_bytes()))
+            }
+            EVertexDataFormat::R16Sint
+            | EVertexDataFormat::Rg16Sint
+            | EVertexDataFormat::Rgba16Sint => {
+                out.extend((0..count).flat_map(|i| (value(i) as i16).to_le_bytes()))
+            }
+            EVertexDataFormat::R16Float
+            | EVertexDataFormat::Rg16Float
+            | EVertexDataFormat::Rgba16Float => {
+                out.extend((0..count).flat_map(|i| f16::from_f32(value(i)).to_le_bytes()))
+            }
+            EVertexDataFormat::R32Uint
+            | EVertexDataFormat::Rg32Uint
+            | EVertexDataFormat::Rgb32Uint
+            | EVertexDataFormat::Rgba32Uint => {
+                out.extend((0..count).flat_map(|i| (value(i) as u32).to_le_bytes()))
+            }
+            EVertexDataFormat::R32Sint
+            | EVertexDataFormat::Rg32Sint
+            | EVertexDataFormat::Rgb32Sint
+            | EVertexDataFormat::Rgba32Sint => {
+                out.extend((0..count).flat_map(|i| (value(i) as i32).to_le_bytes()))
+            }
+            EVertexDataFormat::R32Float
+            | EVertexDataFormat::Rg32Float
+            | EVertexDataFormat::Rgb32Float
+            | EVertexDataFormat::Rgba32Float => {
+                out.extend((0..count).flat_map(|i| value(i).to_le_bytes()))
+            }
+            EVertexDataFormat::Rgb10a2Unorm => {
+                let packed = unorm(value(0), 1023.0) as u32
+                    | (unorm(value(1), 1023.0) as u32) << 10
+                    | (unorm(value(2), 1023.0) as u32) << 20
+                    | (unorm(value(3), 3.0) as u32) << 30;
+                out.extend(packed.to_le_bytes())
+            }
+            EVertexDataFormat::Rgb10a2Uint => {
+                let packed = (value(0) as u32 & 0x3FF)
+                    | (value(1) as u32 & 0x3FF) << 10
+                    | (value(2) as u32 & 0x3FF) << 20
+                    | (value(3) as u32 & 0x3) << 30;
+                out.extend(packed.to_le_bytes())
+            }
+        }
+        Ok(())
+    }
+
     pub fn normalized(self) -> bool {
         matches!(
             self,
@@ -342,6 +478,10 @@ pub struct SModelHeader {
     pub bounds: CAABox,
     // pub data_source_count: u32,
     // pub data_sources: Vec<DataSourceLoader>,
+    // TODO: SKHD/WDHD data. The SKHD skinning layout (joints, per-mesh bone palettes) hasn't
+    // been verified against retail data, so it's kept as raw bytes.
+    #[br(parse_with = binrw::until_eof)]
+    pub unparsed: Vec<u8>,
 }
 
 #[binrw]
@@ -812,8 +952,54 @@ fn decompress_gpu_buffers(
     Ok(out)
 }
 
+/// Reader and writer versions for a model form.
+fn form_versions(kind: FourCC) -> Result<(u32, u32)> {
+    Ok(match kind {
+        K_FORM_CMDL => (114, 125),
+        K_FORM_SMDL => (127, 133),
+        K_FORM_WMDL => (118, 124),
+        id => bail!("Unknown FourCC {:?}", id),
+    })
+}
+
+/// Header chunk ID for a model form.
+fn head_chunk_id(kind: FourCC) -> FourCC {
+    match kind {
+        K_FORM_SMDL => K_CHUNK_SKHD,
+        K_FORM_WMDL => K_CHUNK_WDHD,
+        _ => K_CHUNK_HEAD,
+    }
+}
+
+/// Compresses GPU buffers into a single read buffer, returning the buffer infos.
+fn compress_gpu_buffers(
+    buffers: &[Vec<u8>],
+    read_index: u32,
+    out: &mut Vec<u8>,
+) -> Result<Vec<SModelBufferInfo>> {
+    let mut infos = Vec::with_capacity(buffers.len());
+    for buf in buffers {
+        // Use the widest mode the buffer size is aligned for
+        let mode = match buf.len() % 4 {
+            0 => 3,
+            2 => 2,
+            _ => 1,
+        };
+        let compressed = compress_buffer(buf, mode)?;
+        infos.push(SModelBufferInfo {
+            read_index,
+            offset: out.len() as u32,
+            size: compressed.len() as u32,
+            dest_size: buf.len() as u32,
+        });
+        out.extend_from_slice(&compressed);
+    }
+    Ok(infos)
+}
+
 #[derive(Debug, Clone)]
 pub struct ModelData<O: ByteOrder> {
+    pub kind: FourCC,
     pub head: SModelHeader,
     pub mtrl: SMaterialChunk,
     pub mesh: SMeshLoadInformation,
@@ -829,21 +1015,9 @@ where O: ByteOrder + 'static
 {
     pub fn slice(data: &[u8], meta: &[u8]) -> Result<Self> {
         let (cmdl_desc, cmdl_data, _) = FormDescriptor::<O>::slice(data)?;
-        match cmdl_desc.id {
-            K_FORM_CMDL => {
-                ensure!(cmdl_desc.reader_version.get() == 114);
-                ensure!(cmdl_desc.writer_version.get() == 125);
-            }
-            K_FORM_SMDL => {
-                ensure!(cmdl_desc.reader_version.get() == 127);
-                ensure!(cmdl_desc.writer_version.get() == 133);
-            }
-            K_FORM_WMDL => {
-                ensure!(cmdl_desc.reader_version.get() == 118);
-                ensure!(cmdl_desc.writer_version.get() == 124);
-            }
-            id => bail!("Unknown FourCC {:?}", id),
-        }
+        let (reader_version, writer_version) = form_versions(cmdl_desc.id)?;
+        ensure!(cmdl_desc.reader_version.get() == reader_version);
+        ensure!(cmdl_desc.writer_version.get() == writer_version);
 
         let meta: SModelMetaData = Cursor::new(meta).read_type(Endian::Little)?;
         let vtx_buffers = decompress_gpu_buffers(data, &meta.read_info, &meta.vtx_buffer_info)?;
@@ -885,6 +1059,78 @@ where O: ByteOrder + 'static
         // log::debug!("VBUF: {vbuf:#?}");
         // log::debug!("IBUF: {ibuf:#?}");
 
-        Ok(Self { head, mtrl, mesh, vbuf, ibuf, vtx_buffers, idx_buffers, _marker: PhantomData })
+        Ok(Self {
+            kind: cmdl_desc.id,
+            head,
+            mtrl,
+            mesh,
+            vbuf,
+            ibuf,
+            vtx_buffers,
+            idx_buffers,
+            _marker: PhantomData,
+        })
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        kind: FourCC,
+        head: SModelHeader,
+        mtrl: SMaterialChunk,
+        mesh: SMeshLoadInformation,
+        vbuf: SVertexBufferInfoSection,
+        ibuf: SIndexBufferInfoSection,
+        vtx_buffers: Vec<Vec<u8>>,
+        idx_buffers: Vec<Vec<u8>>,
+    ) -> Self {
+        Self { kind, head, mtrl, mesh, vbuf, ibuf, vtx_buffers, idx_buffers, _marker: PhantomData }
+    }
+
+    /// Writes the model form, compressing the vertex and index buffers into the GPU chunk.
+    /// GPU offsets in the returned metadata are relative to the start of the form, which is
+    /// expected to be the start of the file.
+    pub fn write<W: Write + Seek>(&self, w: &mut W) -> Result<SModelMetaData> {
+        let (reader_version, writer_version) = form_versions(self.kind)?;
+        let mut compressed = Vec::new();
+        let vtx_buffer_info = compress_gpu_buffers(&self.vtx_buffers, 0, &mut compressed)?;
+        let idx_buffer_info = compress_gpu_buffers(&self.idx_buffers, 0, &mut compressed)?;
+
+        let start = w.stream_position()?;
+        let mut gpu_offset = 0;
+        FormDescriptor::<O> {
+            id: self.kind,
+            reader_version: U32::new(reader_version),
+            writer_version: U32::new(writer_version),
+            ..Default::default()
+        }
+        .write(w, |w| {
+            ChunkDescriptor::<O> { id: head_chunk_id(self.kind), ..Default::default() }
+                .write(w, |w| Ok(w.write_type(&self.head, Endian::Little)?))?;
+            ChunkDescriptor::<O> { id: K_CHUNK_MTRL, ..Default::default() }
+                .write(w, |w| Ok(w.write_type(&self.mtrl, Endian::Little)?))?;
+            ChunkDescriptor::<O> { id: K_CHUNK_MESH, ..Default::default() }
+                .write(w, |w| Ok(w.write_type(&self.mesh, Endian::Little)?))?;
+            ChunkDescriptor::<O> { id: K_CHUNK_VBUF, ..Default::default() }
+                .write(w, |w| Ok(w.write_type(&self.vbuf, Endian::Little)?))?;
+            ChunkDescriptor::<O> { id: K_CHUNK_IBUF, ..Default::default() }
+                .write(w, |w| Ok(w.write_type(&self.ibuf, Endian::Little)?))?;
+            ChunkDescriptor::<O> { id: K_CHUNK_GPU, ..Default::default() }.write(w, |w| {
+                gpu_offset = (w.stream_position()? - start) as u32;
+                w.write_all(&compressed)?;
+                Ok(())
+            })?;
+            Ok(())
+        })?;
+
+        Ok(SModelMetaData {
+            unk: 0,
+            gpu_offset,
+            read_info: vec![SModelReadBufferInfo {
+                size: compressed.len() as u32,
+                offset: gpu_offset,
+            }],
+            vtx_buffer_info,
+            idx_buffer_info,
+        })
     }
 }
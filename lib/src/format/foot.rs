@@ -1,7 +1,7 @@
-use std::io::Cursor;
+use std::io::{Cursor, Seek, Write};
 
 use anyhow::{anyhow, ensure, Result};
-use binrw::{BinReaderExt, Endian};
+use binrw::{BinReaderExt, BinWrite, BinWriterExt, Endian};
 use uuid::Uuid;
 use zerocopy::ByteOrder;
 
@@ -58,3 +58,60 @@ pub fn locate_asset_id<O: ByteOrder>(file_data: &[u8]) -> Result<Uuid> {
     }
     Err(anyhow!("Failed to locate AINF chunk"))
 }
+
+/// Footer form of an extracted file, for re-emitting after writing a new asset.
+///
+/// The footer is copied out of the file data, since importers may overwrite the file it was
+/// read from.
+#[derive(Debug, Clone)]
+pub struct Footer<O: ByteOrder> {
+    desc: FormDescriptor<O>,
+    data: Vec<u8>,
+}
+
+impl<O> Footer<O>
+where O: ByteOrder + 'static
+{
+    /// Copies the footer following the asset form in `file_data`.
+    pub fn slice(file_data: &[u8]) -> Result<Self> {
+        let (_, _, remain) = FormDescriptor::<O>::slice(file_data)?;
+        let (desc, data, _) = FormDescriptor::<O>::slice(remain)?;
+        ensure!(desc.id == K_FORM_FOOT);
+        Ok(Self { desc: desc.clone(), data: data.to_vec() })
+    }
+
+    /// Writes the footer unchanged.
+    pub fn write<W: Write + Seek>(&self, w: &mut W) -> Result<()> {
+        self.desc.write(w, |w| {
+            w.write_all(&self.data)?;
+            Ok(())
+        })
+    }
+
+    /// Writes the footer with its META chunk replaced by `meta`.
+    pub fn write_with_meta<W, T>(&self, w: &mut W, meta: &T) -> Result<()>
+    where
+        W: Write + Seek,
+        T: for<'a> BinWrite<Args<'a> = ()>,
+    {
+        self.desc.write(w, |w| {
+            let mut data = self.data.as_slice();
+            while !data.is_empty() {
+                let (desc, _, remain) = ChunkDescriptor::<O>::slice(data)?;
+                if desc.id == K_CHUNK_META {
+                    ChunkDescriptor::<O> { id: K_CHUNK_META, ..Default::default() }.write(
+                        w,
+                        |w| {
+                            w.write_le(meta)?;
+                            Ok(())
+                        },
+                    )?;
+                } else {
+                    w.write_all(&data[..data.len() - remain.len()])?;
+                }
+                data = remain;
+            }
+            Ok(())
+        })
+    }
+}
@@ -1,8 +1,11 @@
-use std::{io::Cursor, marker::PhantomData};
+use std::{
+    io::{Cursor, Seek, Write},
+    marker::PhantomData,
+};
 
-use anyhow::{bail, ensure, Result};
-use binrw::{binrw, BinReaderExt, Endian};
-use zerocopy::ByteOrder;
+use anyhow::{anyhow, bail, ensure, Result};
+use binrw::{binrw, BinReaderExt, BinWriterExt, Endian};
+use zerocopy::{ByteOrder, U32};
 
 use crate::format::{
     chunk::ChunkDescriptor,
@@ -19,6 +22,9 @@ pub const K_CHUNK_PHDR: FourCC = FourCC(*b"PHDR");
 // Probe texture
 pub const K_CHUNK_PTEX: FourCC = FourCC(*b"PTEX");
 
+const K_LTPB_READER_VERSION: u32 = 66;
+const K_LTPB_WRITER_VERSION: u32 = 73;
+
 #[binrw]
 #[derive(Clone, Debug)]
 pub struct CBakedLightingUniformProbeGridIndex {
@@ -38,7 +44,7 @@ pub struct LightProbeBundleHeader {
 }
 
 #[binrw]
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Default)]
 pub struct LightProbeExtra {
     pub vec: CVector3i,
     pub unk: u32,
@@ -46,13 +52,15 @@ pub struct LightProbeExtra {
 
 #[binrw]
 #[derive(Clone, Debug)]
-struct SLightProbeMetaData {
-    unk1: u32,
-    unk2: u32,
+pub struct SLightProbeMetaData {
+    pub unk1: u32,
+    pub unk2: u32,
     #[br(map = |v: TaggedVec<u32, _>| v.data)]
-    meta_offsets: Vec<u64>,
+    #[bw(map = |v| TaggedVec::<u32, _>::new(v.clone()))]
+    pub meta_offsets: Vec<u64>,
     #[br(map = |v: TaggedVec<u32, _>| v.data)]
-    txtr_offsets: Vec<u64>,
+    #[bw(map = |v| TaggedVec::<u32, _>::new(v.clone()))]
+    pub txtr_offsets: Vec<u64>,
 }
 
 #[derive(Debug, Clone)]
@@ -60,6 +68,8 @@ pub struct LightProbeData<O: ByteOrder> {
     pub head: LightProbeBundleHeader,
     pub textures: Vec<TextureData<O>>,
     pub extra: Vec<LightProbeExtra>,
+    /// Original texture metadata, used to fill in unknown fields when writing
+    pub texture_meta: Vec<STextureMetaData>,
     _marker: PhantomData<O>,
 }
 
@@ -67,8 +77,8 @@ impl<O: ByteOrder> LightProbeData<O> {
     pub fn slice(data: &[u8], meta: &[u8]) -> Result<Self> {
         let (ltpb_desc, mut ltpb_data, _) = FormDescriptor::<O>::slice(data)?;
         ensure!(ltpb_desc.id == K_FORM_LTPB);
-        ensure!(ltpb_desc.reader_version.get() == 66);
-        ensure!(ltpb_desc.writer_version.get() == 73);
+        ensure!(ltpb_desc.reader_version.get() == K_LTPB_READER_VERSION);
+        ensure!(ltpb_desc.writer_version.get() == K_LTPB_WRITER_VERSION);
 
         let meta: SLightProbeMetaData = Cursor::new(meta).read_type(Endian::Little)?;
         ensure!(meta.meta_offsets.len() == meta.txtr_offsets.len());
@@ -89,16 +99,87 @@ impl<O: ByteOrder> LightProbeData<O> {
 
         let mut textures = Vec::with_capacity(texture_count);
         let mut extra: Vec<LightProbeExtra> = Vec::with_capacity(texture_count);
+        let mut texture_meta = Vec::with_capacity(texture_count);
         for (meta_offset, txtr_offset) in meta.meta_offsets.into_iter().zip(meta.txtr_offsets) {
             let meta = &data[meta_offset as usize..];
 
-            // Skip metadata to read extra fields
+            // Extra fields follow the texture metadata
             let mut reader = Cursor::new(meta);
-            reader.read_type::<STextureMetaData>(Endian::Little)?;
+            texture_meta.push(reader.read_type::<STextureMetaData>(Endian::Little)?);
             extra.push(reader.read_type(Endian::Little)?);
 
             textures.push(TextureData::<O>::slice(&data[txtr_offset as usize..], meta)?);
         }
-        Ok(Self { head, textures, extra, _marker: PhantomData })
+        Ok(Self { head, textures, extra, texture_meta, _marker: PhantomData })
+    }
+
+    pub fn new(
+        head: LightProbeBundleHeader,
+        textures: Vec<TextureData<O>>,
+        extra: Vec<LightProbeExtra>,
+        texture_meta: Vec<STextureMetaData>,
+    ) -> Self {
+        Self { head, textures, extra, texture_meta, _marker: PhantomData }
+    }
+
+    /// Writes the LTPB form, with each probe's TXTR form and metadata in PTEX.
+    /// Offsets in the returned metadata are relative to the start of the form, which is
+    /// expected to be the start of the file. Unknown metadata fields are left zeroed.
+    pub fn write<W: Write + Seek>(&self, w: &mut W) -> Result<SLightProbeMetaData> {
+        ensure!(self.textures.len() == self.extra.len(), "Mismatched probe texture/extra counts");
+        let start = w.stream_position()?;
+        let mut meta_offsets = Vec::with_capacity(self.textures.len());
+        let mut txtr_offsets = Vec::with_capacity(self.textures.len());
+        FormDescriptor::<O> {
+            id: K_FORM_LTPB,
+            reader_version: U32::new(K_LTPB_READER_VERSION),
+            writer_version: U32::new(K_LTPB_WRITER_VERSION),
+            ..Default::default()
+        }
+        .write(w, |w| {
+            ChunkDescriptor::<O> { id: K_CHUNK_PHDR, ..Default::default() }
+                .write(w, |w| Ok(w.write_type(&self.head, Endian::Little)?))?;
+            ChunkDescriptor::<O> { id: K_CHUNK_PTEX, ..Default::default() }.write(w, |w| {
+                for (idx, (texture, extra)) in self.textures.iter().zip(&self.extra).enumerate() {
+                    txtr_offsets.push(w.stream_position()? - start);
+                    let meta = texture.write(w)?;
+                    let meta = match self.texture_meta.get(idx) {
+                        Some(orig) => STextureMetaData {
+                            unk1: orig.unk1,
+                            unk2: orig.unk2,
+                            alloc_category: orig.alloc_category,
+                            align: orig.align,
+                            ..meta
+                        },
+                        None => meta,
+                    };
+                    meta_offsets.push(w.stream_position()? - start);
+                    w.write_type(&meta, Endian::Little)?;
+                    w.write_type(extra, Endian::Little)?;
+                }
+                Ok(())
+            })?;
+            Ok(())
+        })?;
+        Ok(SLightProbeMetaData { unk1: 0, unk2: 0, meta_offsets, txtr_offsets })
+    }
+
+    /// Uniform grid index of a probe, assuming its extra vector holds one. Unverified.
+    ///
+    /// There's no world position counterpart yet, since the header fields that would place the
+    /// grid aren't understood.
+    pub fn grid_index(&self, probe: usize) -> Result<CBakedLightingUniformProbeGridIndex> {
+        let Some(extra) = self.extra.get(probe) else {
+            bail!("Probe {probe} out of range");
+        };
+        let vec = &extra.vec;
+        let component = |v: i32| {
+            u16::try_from(v).map_err(|_| anyhow!("Probe {probe}: grid index {v} out of range"))
+        };
+        Ok(CBakedLightingUniformProbeGridIndex {
+            x: component(vec.x)?,
+            y: component(vec.y)?,
+            z: component(vec.z)?,
+        })
     }
 }
@@ -1,14 +1,16 @@
-use std::{io::Cursor, marker::PhantomData};
+use std::{
+    io::{Cursor, Seek, Write},
+    marker::PhantomData,
+};
 
 use anyhow::{bail, ensure, Result};
-use binrw::{binrw, BinReaderExt, Endian};
-use binrw_derive::binread;
+use binrw::{binrw, BinReaderExt, BinWriterExt, Endian};
 use uuid::Uuid;
-use zerocopy::ByteOrder;
+use zerocopy::{ByteOrder, U32};
 
 use crate::format::{
     chunk::ChunkDescriptor, peek_four_cc, rfrm::FormDescriptor, CColor4f, CTransform4f, FourCC,
-    TaggedVec,
+    MaybeParsed, TaggedVec,
 };
 
 // Texture
@@ -18,14 +20,19 @@ const K_CHUNK_MCVD: FourCC = FourCC(*b"MCVD");
 const K_CHUNK_MCHD: FourCC = FourCC(*b"MCHD");
 const K_CHUNK_MCCD: FourCC = FourCC(*b"MCCD");
 
+const K_CHUNK_PEEK: FourCC = FourCC(*b"PEEK");
+
+const K_MCON_READER_VERSION: u32 = 41;
+const K_MCON_WRITER_VERSION: u32 = 44;
+
 #[binrw]
 #[derive(Clone, Debug)]
-struct SModConHeader {
-    unk: u32,
+pub struct SModConHeader {
+    pub unk: u32,
 }
 
 #[binrw]
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Default)]
 pub struct ObjectTransform {
     #[br(map = Uuid::from_bytes_le)]
     #[bw(map = Uuid::to_bytes_le)]
@@ -33,35 +40,127 @@ pub struct ObjectTransform {
     pub xf: CTransform4f,
 }
 
-#[binread]
+fn uuids_to_tagged(ids: &[Uuid]) -> TaggedVec<u32, uuid::Bytes> {
+    TaggedVec::new(ids.iter().map(Uuid::to_bytes_le).collect())
+}
+
+#[binrw]
 #[derive(Clone, Debug)]
 pub struct SModConVisualData {
     #[br(map = |v: TaggedVec<u32, uuid::Bytes>| v.data.into_iter().map(Uuid::from_bytes_le).collect())]
+    #[bw(map = |v: &Vec<Uuid>| uuids_to_tagged(v))]
     pub models: Vec<Uuid>,
     #[br(map = |v: TaggedVec<u32, uuid::Bytes>| v.data.into_iter().map(Uuid::from_bytes_le).collect())]
+    #[bw(map = |v: &Vec<Uuid>| uuids_to_tagged(v))]
     pub ids_2: Vec<Uuid>,
     #[br(map = |v: TaggedVec<u32, _>| v.data)]
+    #[bw(map = |v| TaggedVec::<u32, _>::new(v.clone()))]
     pub colors: Vec<CColor4f>,
     #[br(map = |v: TaggedVec<u32, _>| v.data)]
+    #[bw(map = |v| TaggedVec::<u32, _>::new(v.clone()))]
     pub transforms: Vec<CTransform4f>,
     #[br(map = |v: TaggedVec<u32, _>| v.data)]
+    #[bw(map = |v| TaggedVec::<u32, _>::new(v.clone()))]
     pub object_transforms: Vec<ObjectTransform>,
     #[br(map = |v: TaggedVec<u32, _>| v.data)]
+    #[bw(map = |v| TaggedVec::<u32, _>::new(v.clone()))]
     pub bytes_1: Vec<u8>,
     #[br(map = |v: TaggedVec<u32, _>| v.data)]
+    #[bw(map = |v| TaggedVec::<u32, _>::new(v.clone()))]
     pub shorts_1: Vec<u16>,
     #[br(map = |v: TaggedVec<u32, _>| v.data)]
+    #[bw(map = |v| TaggedVec::<u32, _>::new(v.clone()))]
     pub shorts_2: Vec<u16>,
     #[br(map = |v: TaggedVec<u32, _>| v.data)]
+    #[bw(map = |v| TaggedVec::<u32, _>::new(v.clone()))]
     pub bytes_2: Vec<u8>,
     #[br(map = |v: TaggedVec<u32, _>| v.data)]
+    #[bw(map = |v| TaggedVec::<u32, _>::new(v.clone()))]
     pub bytes_3: Vec<u8>,
-    // TODO
+    /// Remaining chunk data after `bytes_3`, not decoded yet. Kept as-is for writing.
+    #[br(parse_with = binrw::until_eof)]
+    pub unparsed: Vec<u8>,
+}
+
+impl SModConVisualData {
+    /// Model index for each entry in `transforms`.
+    pub fn transform_models(&self) -> impl Iterator<Item = (usize, &CTransform4f)> + '_ {
+        self.shorts_1.iter().map(|&idx| idx as usize).zip(&self.transforms)
+    }
+
+    /// Adds an instance of `model_idx`. Returns the new instance index.
+    pub fn add_instance(&mut self, model_idx: usize, xf: CTransform4f) -> Result<usize> {
+        ensure!(model_idx < self.models.len(), "Model index {model_idx} out of range");
+        let count = self.check_instances()?;
+        self.shorts_1.push(model_idx as u16);
+        self.transforms.push(xf);
+        Ok(count)
+    }
+
+    /// Removes an instance.
+    pub fn remove_instance(&mut self, idx: usize) -> Result<()> {
+        let count = self.check_instances()?;
+        ensure!(idx < count, "Instance {idx} out of range");
+        self.shorts_1.remove(idx);
+        self.transforms.remove(idx);
+        Ok(())
+    }
+
+    /// Sets the transform of an instance. `object_transforms` isn't touched, since how it
+    /// relates to `transforms` is unknown.
+    pub fn set_transform(&mut self, idx: usize, xf: CTransform4f) -> Result<()> {
+        ensure!(idx < self.transforms.len(), "Instance {idx} out of range");
+        self.transforms[idx] = xf;
+        Ok(())
+    }
+
+    /// Checks that instances can be added or removed, returning the instance count.
+    ///
+    /// Only `transforms` and `shorts_1` are known to hold one entry per instance. Any data in
+    /// the undecoded arrays or the chunk tail may depend on the instance count as well, so
+    /// editing is refused while there is some.
+    pub fn check_instances(&self) -> Result<usize> {
+        let count = self.transforms.len();
+        ensure!(
+            self.shorts_1.len() == count,
+            "MCVD: {} model indices for {count} transforms",
+            self.shorts_1.len()
+        );
+        let undecoded = [
+            ("colors", self.colors.len()),
+            ("object transforms", self.object_transforms.len()),
+            ("bytes_1", self.bytes_1.len()),
+            ("shorts_2", self.shorts_2.len()),
+            ("bytes_2", self.bytes_2.len()),
+            ("bytes_3", self.bytes_3.len()),
+            ("unparsed tail", self.unparsed.len()),
+        ];
+        let non_empty =
+            undecoded.iter().filter(|(_, len)| *len > 0).map(|(name, _)| *name).collect::<Vec<_>>();
+        ensure!(
+            non_empty.is_empty(),
+            "MCVD: can't add or remove instances while undecoded data is present ({})",
+            non_empty.join(", ")
+        );
+        Ok(count)
+    }
 }
 
+/// Model connector: instanced placements of models.
+///
+/// Only MCHD and the start of MCVD are decoded. MCCD, the end of MCVD and the `PEEK` section
+/// have unknown layouts and are kept as raw bytes.
 #[derive(Debug, Clone)]
 pub struct ModConData<O: ByteOrder> {
+    pub header: Option<MaybeParsed<SModConHeader>>,
     pub visual_data: Option<SModConVisualData>,
+    /// MCCD chunk, not decoded.
+    pub connection_data: Option<Vec<u8>>,
+    /// Everything from the `PEEK` marker to the end of the form, not decoded. It doesn't start
+    /// with a chunk descriptor.
+    pub peek: Vec<u8>,
+    /// Chunk descriptors in file order
+    chunks: Vec<ChunkDescriptor<O>>,
     _marker: PhantomData<O>,
 }
 
@@ -69,25 +168,83 @@ impl<O: ByteOrder> ModConData<O> {
     pub fn slice(data: &[u8]) -> Result<Self> {
         let (mcon_desc, mut mcon_data, _) = FormDescriptor::<O>::slice(data)?;
         ensure!(mcon_desc.id == K_FORM_MCON);
-        ensure!(mcon_desc.reader_version.get() == 41);
-        ensure!(mcon_desc.writer_version.get() == 44);
+        ensure!(mcon_desc.reader_version.get() == K_MCON_READER_VERSION);
+        ensure!(mcon_desc.writer_version.get() == K_MCON_WRITER_VERSION);
 
-        let mut data = Self { visual_data: None, _marker: PhantomData };
+        let mut data = Self {
+            header: None,
+            visual_data: None,
+            connection_data: None,
+            peek: vec![],
+            chunks: vec![],
+            _marker: PhantomData,
+        };
         while !mcon_data.is_empty() {
-            if peek_four_cc(mcon_data) == *b"PEEK" {
+            // Not a chunk descriptor
+            if peek_four_cc(mcon_data) == K_CHUNK_PEEK {
+                data.peek = mcon_data.to_vec();
                 break;
             }
             let (chunk_desc, chunk_data, remain) = ChunkDescriptor::<O>::slice(mcon_data)?;
+            ensure!(
+                !data.chunks.iter().any(|c| c.id == chunk_desc.id),
+                "Duplicate MCON chunk {:?}",
+                chunk_desc.id
+            );
             match chunk_desc.id {
+                K_CHUNK_MCHD => {
+                    data.header = Some(MaybeParsed::read(K_CHUNK_MCHD, chunk_data, Endian::Little));
+                }
                 K_CHUNK_MCVD => {
-                    data.visual_data = Some(Cursor::new(chunk_data).read_type(Endian::Little)?)
+                    data.visual_data = Some(Cursor::new(chunk_data).read_type(Endian::Little)?);
                 }
-                K_CHUNK_MCHD => { /* TODO */ }
-                K_CHUNK_MCCD => { /* TODO */ }
+                K_CHUNK_MCCD => data.connection_data = Some(chunk_data.to_vec()),
                 id => bail!("Unknown MCON chunk ID {id:?}"),
             }
+            data.chunks.push(chunk_desc.clone());
             mcon_data = remain;
         }
         Ok(data)
     }
+
+    pub fn write<W: Write + Seek>(&self, w: &mut W) -> Result<()> {
+        let mut chunks = self.chunks.clone();
+        for (id, present) in [
+            (K_CHUNK_MCHD, self.header.is_some()),
+            (K_CHUNK_MCVD, self.visual_data.is_some()),
+            (K_CHUNK_MCCD, self.connection_data.is_some()),
+        ] {
+            if present && !chunks.iter().any(|c| c.id == id) {
+                chunks.push(ChunkDescriptor { id, ..Default::default() });
+            }
+        }
+        FormDescriptor::<O> {
+            id: K_FORM_MCON,
+            reader_version: U32::new(K_MCON_READER_VERSION),
+            writer_version: U32::new(K_MCON_WRITER_VERSION),
+            ..Default::default()
+        }
+        .write(w, |w| {
+            for desc in &chunks {
+                match desc.id {
+                    K_CHUNK_MCHD => {
+                        let Some(header) = &self.header else { continue };
+                        desc.write(w, |w| Ok(w.write_type(header, Endian::Little)?))?;
+                    }
+                    K_CHUNK_MCVD => {
+                        let Some(visual_data) = &self.visual_data else { continue };
+                        desc.write(w, |w| Ok(w.write_type(visual_data, Endian::Little)?))?;
+                    }
+                    K_CHUNK_MCCD => {
+                        let Some(data) = &self.connection_data else { continue };
+                        desc.write(w, |w| Ok(w.write_all(data)?))?;
+                    }
+                    id => bail!("Unknown MCON chunk ID {id:?}"),
+                }
+            }
+            w.write_all(&self.peek)?;
+            Ok(())
+        })?;
+        Ok(())
+    }
 }
@@ -1,6 +1,7 @@
 #![allow(clippy::useless_conversion)] // for TaggedVec / VecIndex
 
 pub mod chunk;
+pub mod clsn;
 pub mod cmdl;
 pub mod foot;
 pub mod ltpb;
@@ -12,6 +13,7 @@ pub mod room;
 pub mod txtr;
 
 use std::{
+    collections::HashMap,
     fmt::{Debug, Display, Formatter, Write as FmtWrite},
     io::{Read, Seek, Write},
     marker::PhantomData,
@@ -114,7 +116,7 @@ impl mint::IntoMint for CVector3f {
 }
 
 #[binrw]
-#[derive(Copy, Clone, Debug, Default)]
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
 pub struct CVector4f {
     pub x: f32,
     pub y: f32,
@@ -308,7 +310,7 @@ impl Default for CAABox {
 }
 
 #[binrw]
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub struct CTransform4f {
     m0: CVector4f,
     m1: CVector4f,
@@ -367,10 +369,10 @@ impl From<CTransform4f> for mint::ColumnMatrix4<f32> {
 }
 
 #[binrw]
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Default)]
 pub struct COBBox {
-    xf: CTransform4f,
-    extents: CVector3f,
+    pub xf: CTransform4f,
+    pub extents: CVector3f,
 }
 
 #[binrw]
@@ -440,6 +442,74 @@ where
     }
 }
 
+/// Chunk data read with an unverified layout, kept as raw bytes if it doesn't fit.
+#[derive(Clone, Debug)]
+pub enum MaybeParsed<T> {
+    Parsed(T),
+    Raw(Vec<u8>),
+}
+
+impl<T> MaybeParsed<T>
+where T: for<'a> BinRead<Args<'a> = ()>
+{
+    /// Reads `T` from `data`, falling back to the raw bytes if it fails or leaves data unread.
+    pub fn read(id: FourCC, data: &[u8], e: Endian) -> Self {
+        let mut reader = std::io::Cursor::new(data);
+        match reader.read_type::<T>(e) {
+            Ok(value) if reader.position() as usize == data.len() => Self::Parsed(value),
+            Ok(_) => {
+                log::warn!(
+                    "{id:?}: {:#X} unparsed bytes, keeping raw data",
+                    data.len() - reader.position() as usize
+                );
+                Self::Raw(data.to_vec())
+            }
+            Err(err) => {
+                log::warn!("{id:?}: {err}, keeping raw data");
+                Self::Raw(data.to_vec())
+            }
+        }
+    }
+}
+
+impl<T: Default> Default for MaybeParsed<T> {
+    fn default() -> Self { Self::Parsed(T::default()) }
+}
+
+impl<T> MaybeParsed<T> {
+    pub fn parsed(&self) -> Option<&T> {
+        match self {
+            MaybeParsed::Parsed(value) => Some(value),
+            MaybeParsed::Raw(_) => None,
+        }
+    }
+
+    pub fn map<U>(self, f: impl FnOnce(T) -> U) -> MaybeParsed<U> {
+        match self {
+            MaybeParsed::Parsed(value) => MaybeParsed::Parsed(f(value)),
+            MaybeParsed::Raw(data) => MaybeParsed::Raw(data),
+        }
+    }
+}
+
+impl<T> BinWrite for MaybeParsed<T>
+where T: for<'a> BinWrite<Args<'a> = ()>
+{
+    type Args<'a> = ();
+
+    fn write_options<W: Write + Seek>(
+        &self,
+        writer: &mut W,
+        endian: Endian,
+        args: Self::Args<'_>,
+    ) -> BinResult<()> {
+        match self {
+            MaybeParsed::Parsed(value) => value.write_options(writer, endian, args),
+            MaybeParsed::Raw(data) => data.write_options(writer, endian, args),
+        }
+    }
+}
+
 //noinspection RsNeedlessLifetimes
 pub fn slice_chunks<'a, O, ChunkCallback, FormCallback>(
     mut data: &'a [u8],
@@ -465,6 +535,139 @@ where
     Ok(())
 }
 
+/// Descriptors and child sequences of a form tree, recorded while reading.
+///
+/// Every occurrence of a form or chunk is recorded in document order, along with the full
+/// child sequence of each form (repeats included). Writers replay it through a [`LayoutCursor`]
+/// to reproduce the original descriptor fields and the order of interleaved children.
+#[derive(Debug, Clone)]
+pub struct FormLayout<O: ByteOrder> {
+    forms: HashMap<FourCC, Vec<FormDescriptor<O>>>,
+    chunks: HashMap<FourCC, Vec<ChunkDescriptor<O>>>,
+    children: HashMap<FourCC, Vec<Vec<FourCC>>>,
+}
+
+impl<O: ByteOrder> Default for FormLayout<O> {
+    fn default() -> Self {
+        Self { forms: HashMap::new(), chunks: HashMap::new(), children: HashMap::new() }
+    }
+}
+
+impl<O> FormLayout<O>
+where O: ByteOrder + 'static
+{
+    /// Records the layout of the form at the start of `data`.
+    /// Forms with an ID in `opaque` are not descended into.
+    pub fn record(data: &[u8], opaque: &[FourCC]) -> Result<Self> {
+        let mut layout = Self::default();
+        let (desc, form_data, _) = FormDescriptor::<O>::slice(data)?;
+        layout.forms.entry(desc.id).or_default().push(desc.clone());
+        layout.record_children(desc.id, form_data, opaque)?;
+        Ok(layout)
+    }
+
+    fn record_children(
+        &mut self,
+        parent: FourCC,
+        mut data: &[u8],
+        opaque: &[FourCC],
+    ) -> Result<()> {
+        let mut sequence = vec![];
+        while !data.is_empty() {
+            if peek_four_cc(data) == K_CHUNK_RFRM {
+                let (desc, form_data, remain) = FormDescriptor::<O>::slice(data)?;
+                self.forms.entry(desc.id).or_default().push(desc.clone());
+                if !opaque.contains(&desc.id) {
+                    self.record_children(desc.id, form_data, opaque)?;
+                }
+                sequence.push(desc.id);
+                data = remain;
+            } else {
+                let (desc, _, remain) = ChunkDescriptor::<O>::slice(data)?;
+                self.chunks.entry(desc.id).or_default().push(desc.clone());
+                sequence.push(desc.id);
+                data = remain;
+            }
+        }
+        self.children.entry(parent).or_default().push(sequence);
+        Ok(())
+    }
+
+    /// Starts replaying the recorded layout from the root form.
+    pub fn cursor(&self) -> LayoutCursor<'_, O> {
+        LayoutCursor { layout: self, forms: HashMap::new(), chunks: HashMap::new() }
+    }
+}
+
+/// Replays a [`FormLayout`] in write order.
+///
+/// The n-th form or chunk written with a given ID receives the n-th recorded descriptor for
+/// that ID. Occurrences beyond what was recorded reuse the last recorded descriptor, or a
+/// blank one if the ID was never seen.
+pub struct LayoutCursor<'a, O: ByteOrder> {
+    layout: &'a FormLayout<O>,
+    forms: HashMap<FourCC, usize>,
+    chunks: HashMap<FourCC, usize>,
+}
+
+impl<O> LayoutCursor<'_, O>
+where O: ByteOrder + 'static
+{
+    /// Descriptor for the next form with `id`.
+    pub fn form(&mut self, id: FourCC) -> FormDescriptor<O> {
+        let idx = self.forms.entry(id).or_default();
+        let desc = self.layout.forms.get(&id).and_then(|v| v.get(*idx).or(v.last()));
+        *idx += 1;
+        desc.cloned().unwrap_or_else(|| FormDescriptor { id, ..Default::default() })
+    }
+
+    /// Descriptor for the next chunk with `id`.
+    pub fn chunk(&mut self, id: FourCC) -> ChunkDescriptor<O> {
+        let idx = self.chunks.entry(id).or_default();
+        let desc = self.layout.chunks.get(&id).and_then(|v| v.get(*idx).or(v.last()));
+        *idx += 1;
+        desc.cloned().unwrap_or_else(|| ChunkDescriptor { id, ..Default::default() })
+    }
+
+    /// Recorded child sequence of the form with ID `parent` most recently handed out by
+    /// [`form`](Self::form), followed by any IDs from `default` it doesn't contain.
+    pub fn children(&self, parent: FourCC, default: &[FourCC]) -> ChildSequence {
+        let idx = self.forms.get(&parent).map_or(0, |n| n.saturating_sub(1));
+        let recorded = self.layout.children.get(&parent).and_then(|v| v.get(idx).or(v.last()));
+        let mut ids = recorded.cloned().unwrap_or_default();
+        for id in default {
+            if !ids.contains(id) {
+                ids.push(*id);
+            }
+        }
+        ChildSequence(ids)
+    }
+}
+
+/// Child IDs of a form in write order. See [`LayoutCursor::children`].
+#[derive(Debug, Clone)]
+pub struct ChildSequence(Vec<FourCC>);
+
+impl ChildSequence {
+    /// Iterates over the IDs, along with whether it's the last occurrence of that ID.
+    ///
+    /// Writers of repeated children emit one element per occurrence, and any elements left
+    /// over (e.g. added since reading) at the last one.
+    pub fn iter(&self) -> impl Iterator<Item = (FourCC, bool)> + '_ {
+        self.0.iter().enumerate().map(|(i, id)| (*id, !self.0[i + 1..].contains(id)))
+    }
+}
+
+/// Takes the elements to write for one occurrence of a repeated child: the next one, or all
+/// remaining ones if this is the `last` occurrence.
+pub fn take_repeated<'a, T>(iter: &mut std::slice::Iter<'a, T>, last: bool) -> Vec<&'a T> {
+    if last {
+        iter.by_ref().collect()
+    } else {
+        iter.next().into_iter().collect()
+    }
+}
+
 pub trait SumBy {
     type Item;
 
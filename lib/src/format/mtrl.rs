@@ -6,9 +6,10 @@ use std::{
 use anyhow::{ensure, Result};
 use binrw::{binrw, BinReaderExt, Endian};
 use flate2::bufread::ZlibDecoder;
+use uuid::Uuid;
 use zerocopy::ByteOrder;
 
-use crate::format::{rfrm::FormDescriptor, FourCC};
+use crate::format::{cmdl::CMaterialCache, rfrm::FormDescriptor, FourCC};
 
 // Texture
 pub const K_FORM_MTRL: FourCC = FourCC(*b"MTRL");
@@ -23,8 +24,10 @@ struct SMaterialMetaData {
     file_offset: u32,
 }
 
+/// Shader material referenced by [`CMaterialCache::shader_id`].
 #[derive(Debug, Clone)]
 pub struct MaterialData<O: ByteOrder> {
+    /// Inflated payload, not decoded yet
     pub decompressed: Vec<u8>,
     _marker: PhantomData<O>,
 }
@@ -45,4 +48,12 @@ impl<O: ByteOrder> MaterialData<O> {
 
         Ok(Self { decompressed, _marker: PhantomData })
     }
+
+    /// Materials in a model that reference this shader, by index.
+    pub fn users<'a>(
+        shader_id: Uuid,
+        materials: &'a [CMaterialCache],
+    ) -> impl Iterator<Item = (usize, &'a CMaterialCache)> + 'a {
+        materials.iter().enumerate().filter(move |(_, mat)| mat.shader_id == shader_id)
+    }
 }
@@ -17,7 +17,10 @@ use crate::{
         rfrm::FormDescriptor,
         ByteOrderExt, ByteOrderUuid, FourCC,
     },
-    util::{compression::decompress_buffer, read::read_u32},
+    util::{
+        compression::{compress_buffer, decompress_buffer},
+        read::read_u32,
+    },
 };
 
 // Package file
@@ -465,11 +468,26 @@ where O: ByteOrderExt + 'static
         let mut asset_directory = AssetDirectory::default();
         let mut metadata = MetadataTable::default();
         let mut string_table = StringTable::default();
+        let mut compressed_data = Vec::with_capacity(self.assets.len());
         let mut last_uuid = Uuid::nil();
         for asset in &self.assets {
             ensure!(asset.id >= last_uuid, "Assets must be ordered by ID ascending");
             last_uuid = asset.id;
 
+            let data = match asset.info.compression_mode {
+                0 => Cow::Borrowed(asset.data.as_ref()),
+                mode => {
+                    let compressed = compress_buffer(&asset.data, mode).with_context(|| {
+                        format!("Failed to compress asset {} {}", asset.kind, asset.id)
+                    })?;
+                    if compressed.len() == asset.data.len() {
+                        // Equal sizes are read back as uncompressed, store as-is
+                        Cow::Borrowed(asset.data.as_ref())
+                    } else {
+                        Cow::Owned(compressed)
+                    }
+                }
+            };
             asset_directory.push(AssetDirectoryEntry {
                 asset_type: asset.kind,
                 asset_id: ByteOrderUuid::new(asset.id),
@@ -477,8 +495,9 @@ where O: ByteOrderExt + 'static
                 other_version: U32::new(asset.other_version),
                 offset: U64::new(0),
                 decompressed_size: U64::new(asset.data.len() as u64),
-                size: U64::new(asset.data.len() as u64),
+                size: U64::new(data.len() as u64),
             });
+            compressed_data.push(data);
             if asset.meta.is_some() {
                 metadata.entries.push(MetadataTableEntry { asset_id: asset.id, offset: 0 });
             }
@@ -540,12 +559,17 @@ where O: ByteOrderExt + 'static
                 })?;
                 Ok(())
             })?;
-            let mut entries: Vec<(&Asset, &mut AssetDirectoryEntry<O>)> =
-                self.assets.iter().zip(&mut asset_directory).collect();
-            entries.sort_by_key(|(a, _)| a.info.orig_offset);
-            for (asset, entry) in entries {
+            let mut entries: Vec<(&Asset, &Cow<[u8]>, &mut AssetDirectoryEntry<O>)> = self
+                .assets
+                .iter()
+                .zip(&compressed_data)
+                .zip(&mut asset_directory)
+                .map(|((a, d), e)| (a, d, e))
+                .collect();
+            entries.sort_by_key(|(a, _, _)| a.info.orig_offset);
+            for (_, data, entry) in entries {
                 entry.offset.set(w.stream_position()?);
-                w.write_all(&asset.data)?;
+                w.write_all(data)?;
             }
             Ok(())
         })?;
@@ -1,22 +1,24 @@
 use std::{
     fmt::Debug,
-    io::{Cursor, Read, Seek},
-    marker::PhantomData,
-    path::Path,
+    io::{Cursor, Read, Seek, Write},
 };
 
-use anyhow::{anyhow, bail, ensure, Result};
-use binrw::{binrw, BinReaderExt, Endian};
+use anyhow::{anyhow, bail, ensure, Context, Result};
+use binrw::{binrw, BinReaderExt, BinWrite, BinWriterExt, Endian};
+use serde::{ser::SerializeMap, Serialize, Serializer};
+use serde_json::{json, Value};
+use uuid::Uuid;
 use zerocopy::ByteOrder;
 
 use crate::{
     format::{
-        rfrm::FormDescriptor, slice_chunks, CColor4f, CObjectId, CStringFixed, CVector3f,
-        CVector4f, FourCC, TaggedVec,
+        rfrm::FormDescriptor, slice_chunks, take_repeated, CColor4f, CObjectId, CStringFixed,
+        CVector3f, CVector4f, FormLayout, FourCC, LayoutCursor, MaybeParsed, TaggedVec,
     },
     util::templates::{
-        load_templates, EnumTemplate, HexU32, PropertyListTemplate, PropertyTemplateType,
-        StructTemplate, TemplateDatabase, TypeTemplate, TypeTemplateType, TypedefProperty,
+        EnumTemplate, HexU32, PropertyListTemplate, PropertyTemplateType,
+        PropertyTemplateTypeDiscriminants, StructTemplate, TemplateDatabase, TypeTemplate,
+        TypeTemplateType, TypedefProperty,
     },
 };
 
@@ -84,6 +86,8 @@ pub struct SGameAreaHeader {
     pub id_e: CObjectId,
     pub path_find_area_id: CObjectId,
     // TODO ProductionWorkStages
+    #[br(parse_with = binrw::until_eof)]
+    pub unparsed: Vec<u8>,
 }
 
 #[binrw]
@@ -119,6 +123,141 @@ pub struct BakedLighting {
     pub light_probe: Option<BakedLightingLightProbe>,
 }
 
+#[binrw]
+#[derive(Clone, Debug, Default)]
+pub struct SPerformanceGroup {
+    pub id: CObjectId,
+    pub flags: u32,
+}
+
+/// PGRP chunk. Layout unverified.
+#[binrw]
+#[derive(Clone, Debug, Default)]
+pub struct SPerformanceGroups {
+    #[br(map = |v: TaggedVec<u32, _>| v.data)]
+    #[bw(map = |v| TaggedVec::<u32, _>::new(v.clone()))]
+    pub groups: Vec<SPerformanceGroup>,
+    #[br(parse_with = binrw::until_eof)]
+    pub unparsed: Vec<u8>,
+}
+
+/// Object spawned at runtime by a generator instance.
+#[binrw]
+#[derive(Clone, Debug, Default)]
+pub struct SGeneratedObject {
+    pub generator_id: CObjectId,
+    pub object_id: CObjectId,
+}
+
+/// LGEN chunk. Layout unverified.
+#[binrw]
+#[derive(Clone, Debug, Default)]
+pub struct SGeneratedObjectMap {
+    #[br(map = |v: TaggedVec<u32, _>| v.data)]
+    #[bw(map = |v| TaggedVec::<u32, _>::new(v.clone()))]
+    pub objects: Vec<SGeneratedObject>,
+    #[br(parse_with = binrw::until_eof)]
+    pub unparsed: Vec<u8>,
+}
+
+/// Dock on the other side of a connection.
+#[binrw]
+#[derive(Clone, Debug, Default)]
+pub struct SDockConnection {
+    pub room_id: CObjectId,
+    pub dock_id: CObjectId,
+}
+
+#[binrw]
+#[derive(Clone, Debug, Default)]
+pub struct SDock {
+    pub id: CObjectId,
+    #[br(map = |v: TaggedVec<u32, _>| v.data)]
+    #[bw(map = |v| TaggedVec::<u32, _>::new(v.clone()))]
+    pub connections: Vec<SDockConnection>,
+    /// Dock plane outline
+    #[br(map = |v: TaggedVec<u32, _>| v.data)]
+    #[bw(map = |v| TaggedVec::<u32, _>::new(v.clone()))]
+    pub vertices: Vec<CVector3f>,
+}
+
+/// DOCK chunk. Layout unverified.
+#[binrw]
+#[derive(Clone, Debug, Default)]
+pub struct SDockData {
+    #[br(map = |v: TaggedVec<u32, _>| v.data)]
+    #[bw(map = |v| TaggedVec::<u32, _>::new(v.clone()))]
+    pub docks: Vec<SDock>,
+    #[br(parse_with = binrw::until_eof)]
+    pub unparsed: Vec<u8>,
+}
+
+/// LUNS chunk. Layout unverified.
+#[binrw]
+#[derive(Clone, Debug, Default)]
+pub struct SLoadUnitCount {
+    pub count: u32,
+    #[br(parse_with = binrw::until_eof)]
+    pub unparsed: Vec<u8>,
+}
+
+/// LUHD chunk. Layout unverified.
+#[binrw]
+#[derive(Clone, Debug, Default)]
+pub struct SLoadUnitHeader {
+    #[br(try_map = CStringFixed::into_string)]
+    #[bw(map = CStringFixed::from_string)]
+    pub name: String,
+    pub id: CObjectId,
+    #[br(parse_with = binrw::until_eof)]
+    pub unparsed: Vec<u8>,
+}
+
+#[binrw]
+#[derive(Clone, Debug, Default)]
+pub struct SLoadUnitResource {
+    pub id: CObjectId,
+    pub kind: FourCC,
+}
+
+/// LRES chunk. Layout unverified.
+#[binrw]
+#[derive(Clone, Debug, Default)]
+pub struct SLoadUnitResources {
+    #[br(map = |v: TaggedVec<u32, _>| v.data)]
+    #[bw(map = |v| TaggedVec::<u32, _>::new(v.clone()))]
+    pub resources: Vec<SLoadUnitResource>,
+    #[br(parse_with = binrw::until_eof)]
+    pub unparsed: Vec<u8>,
+}
+
+/// LLYR chunk: layers streamed in with the load unit. Layout unverified.
+#[binrw]
+#[derive(Clone, Debug, Default)]
+pub struct SLoadUnitLayers {
+    #[br(map = |v: TaggedVec<u32, _>| v.data)]
+    #[bw(map = |v| TaggedVec::<u32, _>::new(v.clone()))]
+    pub layer_ids: Vec<CObjectId>,
+    #[br(parse_with = binrw::until_eof)]
+    pub unparsed: Vec<u8>,
+}
+
+/// LUNT form. Chunks that don't match their guessed layout are kept raw.
+#[derive(Clone, Debug, Default)]
+pub struct LoadUnit {
+    pub header: MaybeParsed<SLoadUnitHeader>,
+    pub resources: MaybeParsed<SLoadUnitResources>,
+    pub layers: MaybeParsed<SLoadUnitLayers>,
+}
+
+impl LoadUnit {
+    pub fn header(&self) -> Option<&SLoadUnitHeader> { self.header.parsed() }
+
+    pub fn resources(&self) -> Option<&SLoadUnitResources> { self.resources.parsed() }
+
+    pub fn layers(&self) -> Option<&SLoadUnitLayers> { self.layers.parsed() }
+}
+
 #[binrw]
 #[derive(Clone, Debug)]
 // name?
@@ -157,6 +296,10 @@ pub struct PooledString {
 }
 
 impl PooledString {
+    fn inline(value: &str) -> Self {
+        Self { a: u32::MAX, b: value.len() as u32, bytes: value.as_bytes().to_vec() }
+    }
+
     pub fn get(&self, pool: Option<&StringPool>) -> Option<String> {
         if self.a == u32::MAX {
             String::from_utf8(self.bytes.clone()).ok()
@@ -170,35 +313,43 @@ impl PooledString {
     }
 }
 
+/// Serialized object (event criteria or action payload), decoded with the object templates.
 #[binrw]
 #[derive(Clone, Debug)]
 pub struct SizeofAllocations {
-    a: u32,
-    #[br(if(a != 0), map = |v: TaggedVec<u16, _>| v.data)]
-    skip1: Vec<u8>,
-    #[br(if(a != 0), map = |v: TaggedVec<u32, _>| v.data)]
-    skip2: Vec<u8>,
+    /// Object type ID, or 0 if empty
+    pub type_id: u32,
+    #[br(if(type_id != 0), map = |v: TaggedVec<u16, _>| v.data)]
+    #[bw(if(*type_id != 0), map = |v| TaggedVec::<u16, _>::new(v.clone()))]
+    pub unk: Vec<u8>,
+    #[br(if(type_id != 0), map = |v: TaggedVec<u32, _>| v.data)]
+    #[bw(if(*type_id != 0), map = |v| TaggedVec::<u32, _>::new(v.clone()))]
+    pub data: Vec<u8>,
 }
 
+/// Outgoing connection from the instance that owns it.
 #[binrw]
 #[derive(Clone, Debug)]
 pub struct SConnection {
-    #[br(count = 0x1a)]
-    pub skip0: Vec<u8>,
-    pub event_criteria_sldr: SizeofAllocations,
-    pub action_payload_sldr: SizeofAllocations,
-    #[br(count = 0x13)]
-    pub skip1: Vec<u8>,
+    pub target_id: CObjectId,
+    pub event: u32,
+    pub action: u32,
+    pub flags: u16,
+    pub event_criteria: SizeofAllocations,
+    pub action_payload: SizeofAllocations,
+    pub unk_id: CObjectId,
+    pub unk1: u16,
+    pub unk2: u8,
 }
 
 #[binrw]
 #[derive(Clone, Debug)]
 pub struct SScriptLink {
-    #[br(count = 0x14)]
-    pub skip0: Vec<u8>,
-    pub c: SizeofAllocations,
-    #[br(count = 0x12)]
-    pub skip1: Vec<u8>,
+    pub target_id: CObjectId,
+    pub unk1: u32,
+    pub data: SizeofAllocations,
+    pub unk_id: CObjectId,
+    pub unk2: u16,
 }
 
 #[binrw]
@@ -207,8 +358,10 @@ pub struct SGOComponentInstanceData {
     pub id: CObjectId,
     pub string: PooledString,
     #[br(map = |v: TaggedVec<u16, _>| v.data)]
+    #[bw(map = |v| TaggedVec::<u16, _>::new(v.clone()))]
     pub connections: Vec<SConnection>,
     #[br(map = |v: TaggedVec<u16, _>| v.data)]
+    #[bw(map = |v| TaggedVec::<u16, _>::new(v.clone()))]
     pub links: Vec<SScriptLink>,
 }
 
@@ -220,10 +373,14 @@ pub struct LayerHeader {
     pub name: String,
     pub id: CObjectId,
     pub unk: u32,
+    /// Unknown. It isn't known what these reference or whether they depend on the layer's
+    /// components.
     #[br(map = |v: TaggedVec<u16, _>| v.data)]
     #[bw(map = |v| TaggedVec::<u16, _>::new(v.clone()))]
     pub ids: Vec<CObjectId>,
     pub empty_id: CObjectId,
+    /// Unknown. The layer's default-active state hasn't been identified, here or elsewhere,
+    /// so toggling it isn't supported.
     pub unk2: u8,
 }
 
@@ -248,6 +405,7 @@ pub struct StringPool {
     pub unk1: u32,
     pub num_strings: u32,
     #[br(if(num_strings > 0))]
+    #[bw(if(*num_strings > 0))]
     pub pool_len: u32,
     #[br(if(num_strings > 0), count = pool_len)]
     pub pool_data: Vec<u8>,
@@ -257,6 +415,32 @@ pub struct StringPool {
     pub unk_pool_len: u32,
     #[br(count = unk_pool_len)]
     pub unk_pool_data: Vec<u8>,
+    /// Pool entries (offset, length) referenced by the room's properties
+    #[brw(ignore)]
+    pub entries: Vec<(u32, u32)>,
+}
+
+impl StringPool {
+    /// Returns a reference to the entry holding exactly `value`, appending a new entry if none
+    /// does. Only entries in [`entries`](Self::entries) are reused.
+    pub fn insert(&mut self, value: &str) -> PooledString {
+        let bytes = value.as_bytes();
+        let existing = self.entries.iter().find(|&&(offset, len)| {
+            self.pool_data.get(offset as usize..(offset + len) as usize) == Some(bytes)
+        });
+        let (offset, len) = match existing {
+            Some(&entry) => entry,
+            None => {
+                let entry = (self.pool_data.len() as u32, bytes.len() as u32);
+                self.pool_data.extend_from_slice(bytes);
+                self.pool_len = self.pool_data.len() as u32;
+                self.num_strings += 1;
+                self.entries.push(entry);
+                entry
+            }
+        };
+        PooledString { a: offset, b: len, bytes: vec![] }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -264,13 +448,53 @@ pub struct RoomData<O: ByteOrder> {
     pub string_pool: Option<StringPool>,
     pub room_header: SGameAreaHeader,
     pub baked_lighting: BakedLighting,
+    pub performance_groups: Option<MaybeParsed<SPerformanceGroups>>,
+    pub generated_objects: Option<MaybeParsed<SGeneratedObjectMap>>,
+    pub docks: Option<MaybeParsed<SDockData>>,
+    pub load_unit_count: Option<MaybeParsed<SLoadUnitCount>>,
+    pub load_units: Vec<LoadUnit>,
+    pub script_header: ScriptDataHeader,
     pub component_properties: Vec<ComponentProperties>,
     pub constructed_properties: Vec<ConstructedProperty>,
     pub instance_data: Vec<SGOComponentInstanceData>,
+    /// Decoded view of every connection in `instance_data`, in order.
+    pub constructed_connections: Vec<ConstructedConnection>,
     pub layers: Vec<Layer>,
-    _marker: PhantomData<O>,
+    layout: FormLayout<O>,
+}
+
+/// Connection between two instances, with criteria and payload decoded.
+#[derive(Debug, Clone)]
+pub struct ConstructedConnection {
+    pub source_id: CObjectId,
+    pub target_id: CObjectId,
+    pub event: u32,
+    pub action: u32,
+    pub event_criteria: Option<ConstructedProperty>,
+    pub action_payload: Option<ConstructedProperty>,
+}
+
+/// Script instance placement. Rotation is XYZ Euler angles in degrees.
+#[derive(Debug, Clone, Copy)]
+pub struct InstanceTransform {
+    pub position: CVector3f,
+    pub rotation: CVector3f,
+    pub scale: CVector3f,
+}
+
+/// Box volume of a script instance, relative to its placement (ignoring scale).
+#[derive(Debug, Clone, Copy)]
+pub struct InstanceVolume {
+    pub size: CVector3f,
+    pub offset: CVector3f,
 }
 
+/// Template property names (size, offset) that describe an instance volume.
+const VOLUME_PROPERTIES: &[(&str, &str)] = &[("CollisionSize", "CollisionOffset")];
+
+/// Template property name of the model in a render component's properties.
+const MODEL_PROPERTY: &str = "Model";
+
 /// Property with an ID.
 #[derive(Debug, Clone)]
 pub struct ConstructedProperty {
@@ -301,7 +525,143 @@ pub enum ConstructedPropertyValue {
     U64(u64),
     F32(f32),
     F64(f64),
-    String(String),
+    String(ConstructedString),
+}
+
+/// String property value.
+#[derive(Debug, Clone)]
+pub struct ConstructedString {
+    pub value: String,
+    pub storage: StringStorage,
+}
+
+/// Where a [`ConstructedString`] is stored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StringStorage {
+    /// In the property data itself
+    Inline,
+    /// In the room's string pool, with the entry (offset, length) it was read from, if any
+    Pooled(Option<(u32, u32)>),
+}
+
+impl ConstructedPropertyValue {
+    /// Vector value, from a `vector` property or a struct with `x`, `y` and `z` floats.
+    pub fn as_vector(&self) -> Option<CVector3f> {
+        match self {
+            ConstructedPropertyValue::Vector(v) => Some(*v),
+            ConstructedPropertyValue::PropertyList(_) | ConstructedPropertyValue::Struct(_) => {
+                let component = |name| match self.find(name) {
+                    Some(ConstructedPropertyValue::F32(v)) => Some(*v),
+                    _ => None,
+                };
+                Some(CVector3f::new(component("x")?, component("y")?, component("z")?))
+            }
+            _ => None,
+        }
+    }
+
+    /// Finds the first named property or struct element, searching depth-first.
+    pub fn find(&self, name: &str) -> Option<&ConstructedPropertyValue> {
+        match self {
+            ConstructedPropertyValue::PropertyList(list) => list.properties.iter().find_map(|p| {
+                if p.name.as_deref() == Some(name) {
+                    Some(&p.value)
+                } else {
+                    p.value.find(name)
+                }
+            }),
+            ConstructedPropertyValue::Struct(s) => s.elements.iter().find_map(|e| {
+                if e.name.as_deref() == Some(name) {
+                    Some(&e.value)
+                } else {
+                    e.value.find(name)
+                }
+            }),
+            ConstructedPropertyValue::Typedef(t) => {
+                if t.name.as_deref() == Some(name) {
+                    Some(&t.value)
+                } else {
+                    t.value.find(name)
+                }
+            }
+            ConstructedPropertyValue::List(values) => values.iter().find_map(|v| v.find(name)),
+            _ => None,
+        }
+    }
+}
+
+/// Key for a template-named entry, or the hex type ID without a name.
+fn json_key(name: Option<&str>, id: u32) -> String {
+    name.map_or_else(|| HexU32(id).to_string(), str::to_string)
+}
+
+/// Key for a struct element, or its index without a name.
+fn element_key(name: Option<&str>, idx: usize) -> String {
+    name.map_or_else(|| idx.to_string(), str::to_string)
+}
+
+/// Unparsed data is written as `{"unknown": "<hex>"}`.
+const K_JSON_UNKNOWN: &str = "unknown";
+const K_JSON_INLINE: &str = "inline";
+
+/// Human-editable form, keyed by template names. Decoded by [`type_from_json`].
+impl Serialize for ConstructedPropertyValue {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where S: Serializer {
+        match self {
+            ConstructedPropertyValue::Unknown(data) => {
+                let mut map = serializer.serialize_map(Some(1))?;
+                map.serialize_entry(K_JSON_UNKNOWN, &hex_string(data))?;
+                map.end()
+            }
+            ConstructedPropertyValue::Enum(v) => match &v.enum_value {
+                Some(name) => serializer.serialize_str(name),
+                None => serializer.serialize_u32(v.value),
+            },
+            ConstructedPropertyValue::PropertyList(list) => {
+                let mut map = serializer.serialize_map(Some(list.properties.len()))?;
+                for prop in &list.properties {
+                    map.serialize_entry(&json_key(prop.name.as_deref(), prop.id), &prop.value)?;
+                }
+                map.end()
+            }
+            ConstructedPropertyValue::Struct(s) => {
+                let mut map = serializer.serialize_map(Some(s.elements.len()))?;
+                for (idx, elem) in s.elements.iter().enumerate() {
+                    map.serialize_entry(&element_key(elem.name.as_deref(), idx), &elem.value)?;
+                }
+                map.end()
+            }
+            ConstructedPropertyValue::Typedef(t) => {
+                let mut map = serializer.serialize_map(Some(1))?;
+                map.serialize_entry(&json_key(t.name.as_deref(), t.id), &t.value)?;
+                map.end()
+            }
+            ConstructedPropertyValue::List(values) => values.serialize(serializer),
+            ConstructedPropertyValue::Id(id) => serializer.serialize_str(&id.to_string()),
+            ConstructedPropertyValue::Color(color) => color.to_array().serialize(serializer),
+            ConstructedPropertyValue::Vector(vec) => vec.to_array().serialize(serializer),
+            ConstructedPropertyValue::Bool(v) => serializer.serialize_bool(*v),
+            ConstructedPropertyValue::I8(v) => serializer.serialize_i8(*v),
+            ConstructedPropertyValue::I16(v) => serializer.serialize_i16(*v),
+            ConstructedPropertyValue::I32(v) => serializer.serialize_i32(*v),
+            ConstructedPropertyValue::I64(v) => serializer.serialize_i64(*v),
+            ConstructedPropertyValue::U8(v) => serializer.serialize_u8(*v),
+            ConstructedPropertyValue::U16(v) => serializer.serialize_u16(*v),
+            ConstructedPropertyValue::U32(v) => serializer.serialize_u32(*v),
+            ConstructedPropertyValue::U64(v) => serializer.serialize_u64(*v),
+            ConstructedPropertyValue::F32(v) => serializer.serialize_f32(*v),
+            ConstructedPropertyValue::F64(v) => serializer.serialize_f64(*v),
+            ConstructedPropertyValue::String(v) => match v.storage {
+                StringStorage::Inline => {
+                    let mut map = serializer.serialize_map(Some(1))?;
+                    map.serialize_entry(K_JSON_INLINE, &v.value)?;
+                    map.end()
+                }
+                StringStorage::Pooled(_) => serializer.serialize_str(&v.value),
+            },
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -341,12 +701,34 @@ pub struct ConstructedElement {
 pub struct Layer {
     pub header: LayerHeader,
     pub components: Vec<GameObjectComponent>,
+    // TODO: GSRP is written back as-is
+    pub gsrp: Option<Vec<u8>>,
+}
+
+impl Layer {
+    /// Empty layer.
+    fn new(id: CObjectId, name: String, unk: u32) -> Self {
+        Self {
+            header: LayerHeader {
+                name,
+                id,
+                unk,
+                ids: vec![],
+                empty_id: CObjectId::default(),
+                unk2: 0,
+            },
+            components: vec![],
+            gsrp: None,
+        }
+    }
 }
 
 impl<O> RoomData<O>
 where O: ByteOrder + 'static
 {
-    pub fn slice(data: &[u8]) -> Result<Self> {
+    /// Reads a ROOM, constructing script properties with `db`.
+    /// Without templates, all property data is kept unparsed.
+    pub fn slice(data: &[u8], db: Option<&TemplateDatabase>) -> Result<Self> {
         let (room_desc, room_data, _) = FormDescriptor::<O>::slice(data)?;
         ensure!(room_desc.id == K_FORM_ROOM);
         ensure!(room_desc.reader_version.get() == 147);
@@ -355,6 +737,12 @@ where O: ByteOrder + 'static
         let mut string_pool: Option<StringPool> = None;
         let mut room_header: Option<SGameAreaHeader> = None;
         let mut baked_lighting: Option<BakedLighting> = None;
+        let mut performance_groups: Option<MaybeParsed<SPerformanceGroups>> = None;
+        let mut generated_objects: Option<MaybeParsed<SGeneratedObjectMap>> = None;
+        let mut docks: Option<MaybeParsed<SDockData>> = None;
+        let mut load_unit_count: Option<MaybeParsed<SLoadUnitCount>> = None;
+        let mut load_units: Vec<LoadUnit> = vec![];
+        let mut script_header: Option<ScriptDataHeader> = None;
         let mut component_properties: Vec<ComponentProperties> = vec![];
         let mut instance_data: Vec<SGOComponentInstanceData> = vec![];
         let mut layers: Vec<Layer> = vec![];
@@ -382,18 +770,30 @@ where O: ByteOrder + 'static
                                     K_CHUNK_BLIT => {
                                         baked_lighting = Some(reader.read_type(Endian::Little)?)
                                     }
-                                    K_CHUNK_PGRP | K_CHUNK_LGEN | K_CHUNK_DOCK | K_CHUNK_LUNS => {
-                                        // TODO
+                                    // Guessed layouts, kept raw if they don't match
+                                    K_CHUNK_PGRP => {
+                                        performance_groups =
+                                            Some(MaybeParsed::read(chunk.id, data, Endian::Little))
+                                    }
+                                    K_CHUNK_LGEN => {
+                                        generated_objects =
+                                            Some(MaybeParsed::read(chunk.id, data, Endian::Little))
+                                    }
+                                    K_CHUNK_DOCK => {
+                                        docks =
+                                            Some(MaybeParsed::read(chunk.id, data, Endian::Little))
+                                    }
+                                    K_CHUNK_LUNS => {
+                                        load_unit_count =
+                                            Some(MaybeParsed::read(chunk.id, data, Endian::Little))
                                     }
                                     id => bail!("Unknown HEAD chunk: {id:?}"),
                                 }
                                 Ok(())
                             },
-                            |form, _data| {
+                            |form, data| {
                                 match form.id {
-                                    K_FORM_LUNT => {
-                                        // TODO
-                                    }
+                                    K_FORM_LUNT => load_units.push(slice_load_unit::<O>(data)?),
                                     id => bail!("Unknown HEAD form: {id:?}"),
                                 }
                                 Ok(())
@@ -401,8 +801,11 @@ where O: ByteOrder + 'static
                         )?;
                     }
                     K_FORM_SDTA => {
-                        (component_properties, instance_data) =
-                            slice_script_data::<O>(data, Endian::Little)?
+                        let (header, properties, instances) =
+                            slice_script_data::<O>(data, Endian::Little)?;
+                        script_header = Some(header);
+                        component_properties = properties;
+                        instance_data = instances;
                     }
                     K_FORM_LYRS => layers = slice_layers::<O>(data, Endian::Little)?,
                     id => bail!("Unknown ROOM form: {id:?}"),
@@ -411,58 +814,794 @@ where O: ByteOrder + 'static
             },
         )?;
 
-        let db = match load_templates(Path::new("lib/templates/mp1r")) {
-            Ok(db) => Some(db),
-            Err(e) => {
-                log::error!("Failed to load templates: {:?}", e);
-                None
-            }
-        };
-
-        let mut constructed_properties = Vec::with_capacity(component_properties.len());
-        for props in &component_properties {
-            let (name, type_tmpl) = db
-                .as_ref()
-                .map(|db| db.find_object(props.component_type))
-                .map_or((None, None), |v| v);
-            let mut reader = Cursor::new(&*props.data);
-            let value = if let Some(type_tmpl) = type_tmpl {
-                match parse_type(
-                    &mut reader,
-                    Endian::Little,
-                    type_tmpl,
-                    db.as_ref().unwrap(),
-                    string_pool.as_ref(),
-                ) {
-                    Ok(v) => v,
-                    Err(e) => {
-                        log::error!("Failed to parse type {}: {:?}", type_tmpl.name, e);
-                        ConstructedPropertyValue::Unknown(props.data.clone())
-                    }
-                }
-            } else {
-                ConstructedPropertyValue::Unknown(props.data.clone())
-            };
-            constructed_properties.push(ConstructedProperty {
-                id: props.component_type,
-                name: name.cloned(),
-                value,
-            });
-        }
-
         let room_header = room_header.ok_or_else(|| anyhow!("Missing RMHD chunk"))?;
         let baked_lighting = baked_lighting.ok_or_else(|| anyhow!("Missing BLIT chunk"))?;
-        Ok(Self {
+        let script_header = script_header.ok_or_else(|| anyhow!("Missing SDTA form"))?;
+        let layout = FormLayout::record(data, &[K_FORM_GSRP])?;
+        let mut room = Self {
             string_pool,
             room_header,
             baked_lighting,
+            performance_groups,
+            generated_objects,
+            docks,
+            load_unit_count,
+            load_units,
+            script_header,
             component_properties,
-            constructed_properties,
+            constructed_properties: vec![],
             instance_data,
+            constructed_connections: vec![],
             layers,
-            _marker: PhantomData,
+            layout,
+        };
+        room.construct_properties(db);
+        Ok(room)
+    }
+
+    /// PGRP chunk, if present and parsed.
+    pub fn performance_groups(&self) -> Option<&SPerformanceGroups> {
+        self.performance_groups.as_ref().and_then(|v| v.parsed())
+    }
+
+    /// LGEN chunk, if present and parsed.
+    pub fn generated_objects(&self) -> Option<&SGeneratedObjectMap> {
+        self.generated_objects.as_ref().and_then(|v| v.parsed())
+    }
+
+    /// DOCK chunk, if present and parsed. The dock layout is a guess, so connections read from
+    /// it may be wrong even when it parses.
+    pub fn docks(&self) -> Option<&SDockData> { self.docks.as_ref().and_then(|v| v.parsed()) }
+
+    /// Rebuilds `constructed_properties` and `constructed_connections` from the raw property
+    /// data, e.g. after the templates changed.
+    pub fn construct_properties(&mut self, db: Option<&TemplateDatabase>) {
+        let string_pool = self.string_pool.as_ref();
+        self.constructed_properties = self
+            .component_properties
+            .iter()
+            .map(|props| construct_object(db, string_pool, props.component_type, &props.data))
+            .collect();
+        let construct_allocation = |alloc: &SizeofAllocations| {
+            (alloc.type_id != 0)
+                .then(|| construct_object(db, string_pool, alloc.type_id, &alloc.data))
+        };
+        self.constructed_connections = self
+            .instance_data
+            .iter()
+            .flat_map(|instance| {
+                instance.connections.iter().map(|connection| ConstructedConnection {
+                    source_id: instance.id,
+                    target_id: connection.target_id,
+                    event: connection.event,
+                    action: connection.action,
+                    event_criteria: construct_allocation(&connection.event_criteria),
+                    action_payload: construct_allocation(&connection.action_payload),
+                })
+            })
+            .collect();
+        if let Some(string_pool) = &mut self.string_pool {
+            let mut entries = vec![];
+            for value in self.constructed_properties.iter().map(|p| &p.value).chain(
+                self.constructed_connections
+                    .iter()
+                    .flat_map(|c| [&c.event_criteria, &c.action_payload])
+                    .flatten()
+                    .map(|p| &p.value),
+            ) {
+                collect_pool_entries(value, &mut entries);
+            }
+            entries.sort_unstable();
+            entries.dedup();
+            string_pool.entries = entries;
+        }
+    }
+
+    /// Connections whose source is the instance `id`.
+    pub fn outgoing_connections(
+        &self,
+        id: CObjectId,
+    ) -> impl Iterator<Item = &ConstructedConnection> + '_ {
+        self.constructed_connections.iter().filter(move |c| c.source_id == id)
+    }
+
+    /// Connections whose target is the instance `id`.
+    pub fn incoming_connections(
+        &self,
+        id: CObjectId,
+    ) -> impl Iterator<Item = &ConstructedConnection> + '_ {
+        self.constructed_connections.iter().filter(move |c| c.target_id == id)
+    }
+
+    /// Constructed properties of every component attached to an instance.
+    pub fn instance_components(
+        &self,
+        instance_idx: usize,
+    ) -> impl Iterator<Item = &ConstructedProperty> + '_ {
+        self.layers
+            .iter()
+            .flat_map(|l| &l.components)
+            .filter(move |c| c.instance_index as usize == instance_idx)
+            .filter_map(|c| self.constructed_properties.get(c.property_index as usize))
+    }
+
+    /// Placement of an instance, from the first component with a `Position` property.
+    pub fn instance_transform(&self, instance_idx: usize) -> Option<InstanceTransform> {
+        self.instance_components(instance_idx).find_map(|c| {
+            let vector = |name| match c.value.find(name) {
+                Some(ConstructedPropertyValue::Vector(v)) => Some(*v),
+                _ => None,
+            };
+            Some(InstanceTransform {
+                position: vector("Position")?,
+                rotation: vector("Rotation").unwrap_or_default(),
+                scale: vector("Scale").unwrap_or(CVector3f::splat(1.0)),
+            })
         })
     }
+
+    /// Box volume of an instance, from the first component with named volume properties.
+    /// Only templates that name their extent properties provide one.
+    pub fn instance_volume(&self, instance_idx: usize) -> Option<InstanceVolume> {
+        self.instance_components(instance_idx).find_map(|c| {
+            let vector = |name| c.value.find(name).and_then(|v| v.as_vector());
+            VOLUME_PROPERTIES.iter().find_map(|&(size, offset)| {
+                Some(InstanceVolume {
+                    size: vector(size)?,
+                    offset: vector(offset).unwrap_or_default(),
+                })
+            })
+        })
+    }
+
+    /// Model IDs of an instance, from the `RenderProperties` of its render components.
+    pub fn instance_models(&self, instance_idx: usize) -> Vec<CObjectId> {
+        self.instance_components(instance_idx)
+            .filter_map(|c| match c.value.find("RenderProperties") {
+                Some(ConstructedPropertyValue::Typedef(t)) => Some(&t.value),
+                _ => None,
+            })
+            .filter_map(|value| match value {
+                // Only the render type's own properties, not nested structs
+                ConstructedPropertyValue::PropertyList(list) => {
+                    list.properties.iter().find(|p| p.name.as_deref() == Some(MODEL_PROPERTY))
+                }
+                _ => None,
+            })
+            .filter_map(|p| match &p.value {
+                ConstructedPropertyValue::Id(id) if !id.is_nil() => Some(*id),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Appends an empty layer. `id` should be freshly generated.
+    /// Returns the new layer index.
+    pub fn add_layer(&mut self, id: CObjectId, name: String) -> usize {
+        let unk = self.layers.first().map_or(0, |l| l.header.unk);
+        self.layers.push(Layer::new(id, name, unk));
+        self.layers.len() - 1
+    }
+
+    /// Moves a component to the end of another layer.
+    /// Refused while either layer has [`LayerHeader::ids`], since it isn't known whether they
+    /// would need updating.
+    pub fn move_component(
+        &mut self,
+        from_layer: usize,
+        component_idx: usize,
+        to_layer: usize,
+    ) -> Result<()> {
+        ensure!(from_layer < self.layers.len(), "Invalid layer index {from_layer}");
+        ensure!(to_layer < self.layers.len(), "Invalid layer index {to_layer}");
+        for idx in [from_layer, to_layer] {
+            let header = &self.layers[idx].header;
+            ensure!(
+                header.ids.is_empty(),
+                "Layer '{}' has {} undecoded IDs, refusing to move components",
+                header.name,
+                header.ids.len()
+            );
+        }
+        let components = &mut self.layers[from_layer].components;
+        ensure!(component_idx < components.len(), "Invalid component index {component_idx}");
+        let component = components.remove(component_idx);
+        self.layers[to_layer].components.push(component);
+        Ok(())
+    }
+
+    /// Re-encodes `constructed_properties` into `component_properties`.
+    /// Pooled strings are added to the string pool if not already present.
+    pub fn encode_properties(&mut self, db: &TemplateDatabase) -> Result<()> {
+        ensure!(self.constructed_properties.len() == self.component_properties.len());
+        for (props, constructed) in
+            self.component_properties.iter_mut().zip(&self.constructed_properties)
+        {
+            props.component_type = constructed.id;
+            props.data = encode_object(constructed, db, self.string_pool.as_mut())?;
+        }
+        Ok(())
+    }
+
+    /// Instances, layers and their components' properties as human-editable JSON.
+    /// Components are keyed by instance ID and object template name. Instances carry their
+    /// connections and links; link data is kept as hex.
+    pub fn export_script(&self) -> Result<Value> {
+        let mut layers = Vec::with_capacity(self.layers.len());
+        for layer in &self.layers {
+            let mut components = Vec::with_capacity(layer.components.len());
+            for component in &layer.components {
+                let instance = self
+                    .instance_data
+                    .get(component.instance_index as usize)
+                    .ok_or_else(|| anyhow!("Invalid instance {}", component.instance_index))?;
+                let property =
+                    self.constructed_properties
+                        .get(component.property_index as usize)
+                        .ok_or_else(|| anyhow!("Invalid property {}", component.property_index))?;
+                components.push(json!({
+                    "instance": instance.id.to_string(),
+                    "type": json_key(property.name.as_deref(), property.id),
+                    "properties": property.value,
+                }));
+            }
+            layers.push(json!({
+                "name": layer.header.name,
+                "id": layer.header.id.to_string(),
+                "unk2": layer.header.unk2,
+                "components": components,
+            }));
+        }
+
+        ensure!(
+            self.constructed_connections.len()
+                == self.instance_data.iter().map(|i| i.connections.len()).sum::<usize>(),
+            "Connections not constructed"
+        );
+        let mut constructed_connections = self.constructed_connections.iter();
+        let mut instances = Vec::with_capacity(self.instance_data.len());
+        for instance in &self.instance_data {
+            let mut connections = Vec::with_capacity(instance.connections.len());
+            for (connection, constructed) in
+                instance.connections.iter().zip(constructed_connections.by_ref())
+            {
+                connections.push(json!({
+                    "target": connection.target_id.to_string(),
+                    "event": connection.event,
+                    "action": connection.action,
+                    "flags": connection.flags,
+                    "event_criteria": allocation_json(
+                        &connection.event_criteria,
+                        constructed.event_criteria.as_ref(),
+                    ),
+                    "action_payload": allocation_json(
+                        &connection.action_payload,
+                        constructed.action_payload.as_ref(),
+                    ),
+                    "unk_id": connection.unk_id.to_string(),
+                    "unk1": connection.unk1,
+                    "unk2": connection.unk2,
+                }));
+            }
+            let links = instance
+                .links
+                .iter()
+                .map(|link| {
+                    json!({
+                        "target": link.target_id.to_string(),
+                        "unk1": link.unk1,
+                        "data": allocation_json(&link.data, None),
+                        "unk_id": link.unk_id.to_string(),
+                        "unk2": link.unk2,
+                    })
+                })
+                .collect::<Vec<_>>();
+            instances.push(json!({
+                "id": instance.id.to_string(),
+                "name": instance.string.get(self.string_pool.as_ref()),
+                "connections": connections,
+                "links": links,
+            }));
+        }
+        Ok(json!({ "instances": instances, "layers": layers }))
+    }
+
+    /// Replaces connections, links, layers and component properties with JSON from
+    /// [`Self::export_script`]. Instances in the `instances` array have their connections and
+    /// links replaced; the others are kept as they are, and unknown instance IDs are an error.
+    /// Layers missing from the JSON are removed, along with the
+    /// properties of components that are no longer in any layer.
+    pub fn import_script(&mut self, value: &Value, db: &TemplateDatabase) -> Result<()> {
+        if let Some(instances) = value.get("instances") {
+            self.import_instances(instances, db)?;
+        }
+        let layers = value
+            .get("layers")
+            .and_then(Value::as_array)
+            .ok_or_else(|| anyhow!("Missing layers array"))?;
+        let mut old_layers = std::mem::take(&mut self.layers);
+        let old_components =
+            old_layers.iter().flat_map(|l| l.components.clone()).collect::<Vec<_>>();
+        let unk = old_layers.first().map_or(0, |l| l.header.unk);
+        let mut claimed = vec![false; self.constructed_properties.len()];
+        for layer_value in layers {
+            let name = json_str(&layer_value["name"])?.to_string();
+            let id = json_str(&layer_value["id"])?;
+            let id = Uuid::parse_str(id).with_context(|| format!("Invalid ID {id}"))?;
+            let id = CObjectId::from(id);
+            let unk2 = match &layer_value["unk2"] {
+                Value::Null => None,
+                v => Some(
+                    v.as_u64()
+                        .and_then(|v| u8::try_from(v).ok())
+                        .ok_or_else(|| anyhow!("Invalid unk2 in layer {}", id))?,
+                ),
+            };
+            let mut layer = match old_layers.iter().position(|l| l.header.id == id) {
+                Some(idx) => old_layers.remove(idx),
+                None => Layer::new(id, String::new(), unk),
+            };
+            layer.header.name = name;
+            // Missing values keep the existing layer's, or the default of a new one
+            if let Some(unk2) = unk2 {
+                layer.header.unk2 = unk2;
+            }
+            layer.components.clear();
+
+            let components = layer_value["components"]
+                .as_array()
+                .ok_or_else(|| anyhow!("Missing components array in layer {}", id))?;
+            for component_value in components {
+                let instance_id = json_str(&component_value["instance"])?;
+                let instance_index = self
+                    .instance_data
+                    .iter()
+                    .position(|i| i.id.to_string() == instance_id)
+                    .ok_or_else(|| anyhow!("Unknown instance {}", instance_id))?;
+                let component_type = object_type_from_json(db, &component_value["type"])?;
+                let property = object_from_json(db, component_type, &component_value["properties"])
+                    .with_context(|| format!("While reading instance {instance_id}"))?;
+
+                // Reuse the property slot of the component being replaced
+                let existing = old_components.iter().find(|c| {
+                    c.instance_index as usize == instance_index
+                        && c.component_type == component_type
+                        && claimed.get(c.property_index as usize) == Some(&false)
+                });
+                let property_index = match existing {
+                    Some(c) => c.property_index as usize,
+                    None => {
+                        self.component_properties
+                            .push(ComponentProperties { component_type, data: vec![] });
+                        self.constructed_properties.push(property.clone());
+                        claimed.push(false);
+                        self.constructed_properties.len() - 1
+                    }
+                };
+                claimed[property_index] = true;
+                self.constructed_properties[property_index] = property;
+                layer.components.push(GameObjectComponent {
+                    component_type,
+                    property_index: property_index as u32,
+                    instance_index: instance_index as u32,
+                });
+            }
+            self.layers.push(layer);
+        }
+        self.remove_unused_properties();
+        self.encode_properties(db)?;
+        // Decode the new connections
+        self.construct_properties(Some(db));
+        Ok(())
+    }
+
+    /// Replaces the names, connections and links of instances from the `instances` array of
+    /// [`Self::export_script`]. Instances not in the array are left untouched, and IDs not
+    /// already in the room are an error.
+    fn import_instances(&mut self, value: &Value, db: &TemplateDatabase) -> Result<()> {
+        let values = value.as_array().ok_or_else(|| anyhow!("Invalid instances array"))?;
+        for instance_value in values {
+            let id = json_id(&instance_value["id"])?;
+            // New instances would need entries in the script data header's ID table,
+            // which isn't understood well enough to extend
+            let idx =
+                self.instance_data.iter().position(|i| i.id == id).ok_or_else(|| {
+                    anyhow!("Unknown instance {id}; adding instances is unsupported")
+                })?;
+            let result = (|| -> Result<()> {
+                let connection_values = instance_value["connections"]
+                    .as_array()
+                    .ok_or_else(|| anyhow!("Missing connections array"))?;
+                let mut connections = Vec::with_capacity(connection_values.len());
+                for v in connection_values {
+                    connections.push(SConnection {
+                        target_id: json_id(&v["target"])?,
+                        event: json_int(&v["event"])?,
+                        action: json_int(&v["action"])?,
+                        flags: json_int(&v["flags"])?,
+                        event_criteria: allocation_from_json(
+                            &v["event_criteria"],
+                            db,
+                            self.string_pool.as_mut(),
+                        )?,
+                        action_payload: allocation_from_json(
+                            &v["action_payload"],
+                            db,
+                            self.string_pool.as_mut(),
+                        )?,
+                        unk_id: json_id(&v["unk_id"])?,
+                        unk1: json_int(&v["unk1"])?,
+                        unk2: json_int(&v["unk2"])?,
+                    });
+                }
+                let link_values = instance_value["links"]
+                    .as_array()
+                    .ok_or_else(|| anyhow!("Missing links array"))?;
+                let mut links = Vec::with_capacity(link_values.len());
+                for v in link_values {
+                    links.push(SScriptLink {
+                        target_id: json_id(&v["target"])?,
+                        unk1: json_int(&v["unk1"])?,
+                        data: allocation_from_json(&v["data"], db, self.string_pool.as_mut())?,
+                        unk_id: json_id(&v["unk_id"])?,
+                        unk2: json_int(&v["unk2"])?,
+                    });
+                }
+                let instance = &mut self.instance_data[idx];
+                if let Some(name) = instance_value["name"].as_str() {
+                    if instance.string.get(self.string_pool.as_ref()).as_deref() != Some(name) {
+                        instance.string = match self.string_pool.as_mut() {
+                            Some(pool) if instance.string.a != u32::MAX => pool.insert(name),
+                            _ => PooledString::inline(name),
+                        };
+                    }
+                }
+                instance.connections = connections;
+                instance.links = links;
+                Ok(())
+            })();
+            result.with_context(|| format!("While reading instance {id}"))?;
+        }
+        Ok(())
+    }
+
+    /// Drops component properties no longer referenced by any layer, remapping the rest.
+    fn remove_unused_properties(&mut self) {
+        let mut remap = vec![None; self.component_properties.len()];
+        for component in self.layers.iter().flat_map(|l| &l.components) {
+            if let Some(slot) = remap.get_mut(component.property_index as usize) {
+                *slot = Some(0);
+            }
+        }
+        let mut next = 0;
+        for slot in remap.iter_mut().flatten() {
+            *slot = next;
+            next += 1;
+        }
+        let mut idx = 0;
+        self.component_properties.retain(|_| {
+            idx += 1;
+            remap[idx - 1].is_some()
+        });
+        let mut idx = 0;
+        self.constructed_properties.retain(|_| {
+            idx += 1;
+            remap[idx - 1].is_some()
+        });
+        for component in self.layers.iter_mut().flat_map(|l| &mut l.components) {
+            if let Some(Some(new_idx)) = remap.get(component.property_index as usize) {
+                component.property_index = *new_idx;
+            }
+        }
+    }
+
+    pub fn write<W: Write + Seek>(&self, w: &mut W) -> Result<()> {
+        let mut l = self.layout.cursor();
+        let mut room_desc = l.form(K_FORM_ROOM);
+        room_desc.reader_version.set(147);
+        room_desc.writer_version.set(160);
+        room_desc.write(w, |w| {
+            let order = [K_FORM_HEAD, K_CHUNK_STRP, K_FORM_SDTA, K_FORM_LYRS];
+            for (id, _) in l.children(K_FORM_ROOM, &order).iter() {
+                match id {
+                    K_FORM_HEAD => l.form(id).write(w, |w| self.write_head(w, &mut l))?,
+                    K_CHUNK_STRP => {
+                        if let Some(string_pool) = &self.string_pool {
+                            write_chunk(w, &mut l, id, string_pool)?;
+                        }
+                    }
+                    K_FORM_SDTA => l.form(id).write(w, |w| self.write_script_data(w, &mut l))?,
+                    K_FORM_LYRS => l.form(id).write(w, |w| self.write_layers(w, &mut l))?,
+                    id => bail!("Unknown ROOM form: {id:?}"),
+                }
+            }
+            Ok(())
+        })
+    }
+
+    fn write_head<W: Write + Seek>(&self, w: &mut W, l: &mut LayoutCursor<O>) -> Result<()> {
+        let order = [K_CHUNK_RMHD, K_CHUNK_BLIT];
+        let mut load_units = self.load_units.iter();
+        for (id, last) in l.children(K_FORM_HEAD, &order).iter() {
+            match id {
+                K_CHUNK_RMHD => write_chunk(w, l, id, &self.room_header)?,
+                K_CHUNK_BLIT => write_chunk(w, l, id, &self.baked_lighting)?,
+                K_CHUNK_PGRP => write_opt_chunk(w, l, id, &self.performance_groups)?,
+                K_CHUNK_LGEN => write_opt_chunk(w, l, id, &self.generated_objects)?,
+                K_CHUNK_DOCK => write_opt_chunk(w, l, id, &self.docks)?,
+                K_CHUNK_LUNS => {
+                    if let Some(load_unit_count) = &self.load_unit_count {
+                        let mut load_unit_count = load_unit_count.clone();
+                        if let MaybeParsed::Parsed(value) = &mut load_unit_count {
+                            value.count = self.load_units.len() as u32;
+                        }
+                        write_chunk(w, l, id, &load_unit_count)?;
+                    }
+                }
+                K_FORM_LUNT => {
+                    for load_unit in take_repeated(&mut load_units, last) {
+                        l.form(id).write(w, |w| self.write_load_unit(w, l, load_unit))?;
+                    }
+                }
+                id => bail!("Unknown HEAD chunk: {id:?}"),
+            }
+        }
+        Ok(())
+    }
+
+    fn write_load_unit<W: Write + Seek>(
+        &self,
+        w: &mut W,
+        l: &mut LayoutCursor<O>,
+        load_unit: &LoadUnit,
+    ) -> Result<()> {
+        let order = [K_CHUNK_LUHD, K_CHUNK_LRES, K_CHUNK_LLYR];
+        for (id, _) in l.children(K_FORM_LUNT, &order).iter() {
+            match id {
+                K_CHUNK_LUHD => write_chunk(w, l, id, &load_unit.header)?,
+                K_CHUNK_LRES => write_chunk(w, l, id, &load_unit.resources)?,
+                K_CHUNK_LLYR => write_chunk(w, l, id, &load_unit.layers)?,
+                id => bail!("Unknown LUNT chunk: {id:?}"),
+            }
+        }
+        Ok(())
+    }
+
+    fn write_script_data<W: Write + Seek>(&self, w: &mut W, l: &mut LayoutCursor<O>) -> Result<()> {
+        let order = [K_CHUNK_SDHR, K_CHUNK_SDEN, K_CHUNK_IDTA];
+        let mut component_properties = self.component_properties.iter();
+        let mut instance_data = self.instance_data.iter();
+        for (id, last) in l.children(K_FORM_SDTA, &order).iter() {
+            match id {
+                K_CHUNK_SDHR => {
+                    let mut header = self.script_header.clone();
+                    header.properties_count = self.component_properties.len() as u32;
+                    header.instance_data_count = self.instance_data.len() as u32;
+                    write_chunk(w, l, id, &header)?;
+                }
+                K_CHUNK_SDEN => {
+                    for props in take_repeated(&mut component_properties, last) {
+                        write_chunk(w, l, id, props)?;
+                    }
+                }
+                K_CHUNK_IDTA => {
+                    for instance in take_repeated(&mut instance_data, last) {
+                        write_chunk(w, l, id, instance)?;
+                    }
+                }
+                id => bail!("Unknown SDTA chunk: {id:?}"),
+            }
+        }
+        Ok(())
+    }
+
+    fn write_layers<W: Write + Seek>(&self, w: &mut W, l: &mut LayoutCursor<O>) -> Result<()> {
+        let mut layers = self.layers.iter();
+        for (id, last) in l.children(K_FORM_LYRS, &[K_FORM_LAYR]).iter() {
+            if id != K_FORM_LAYR {
+                bail!("Unknown LYRS form: {id:?}");
+            }
+            for layer in take_repeated(&mut layers, last) {
+                l.form(id).write(w, |w| self.write_layer(w, l, layer))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn write_layer<W: Write + Seek>(
+        &self,
+        w: &mut W,
+        l: &mut LayoutCursor<O>,
+        layer: &Layer,
+    ) -> Result<()> {
+        for (id, _) in l.children(K_FORM_LAYR, &[K_CHUNK_LHED, K_FORM_SRIP]).iter() {
+            match id {
+                K_CHUNK_LHED => write_chunk(w, l, id, &layer.header)?,
+                K_FORM_GSRP => {
+                    if let Some(data) = &layer.gsrp {
+                        l.form(id).write(w, |w| Ok(w.write_all(data)?))?;
+                    }
+                }
+                K_FORM_SRIP => l.form(id).write(w, |w| {
+                    for (id, _) in l.children(K_FORM_SRIP, &[K_CHUNK_COMP]).iter() {
+                        if id != K_CHUNK_COMP {
+                            bail!("Unknown SRIP chunk: {id:?}");
+                        }
+                        write_chunk(w, l, id, &layer.components)?;
+                    }
+                    Ok(())
+                })?,
+                id => bail!("Unknown LAYR chunk: {id:?}"),
+            }
+        }
+        Ok(())
+    }
+}
+
+fn write_chunk<O, W, T>(w: &mut W, l: &mut LayoutCursor<O>, id: FourCC, value: &T) -> Result<()>
+where
+    O: ByteOrder + 'static,
+    W: Write + Seek,
+    T: for<'a> BinWrite<Args<'a> = ()>,
+{
+    l.chunk(id).write(w, |w| {
+        w.write_type(value, Endian::Little)?;
+        Ok(())
+    })
+}
+
+/// Decodes serialized object data with its type template, if known.
+fn construct_object(
+    db: Option<&TemplateDatabase>,
+    string_pool: Option<&StringPool>,
+    id: u32,
+    data: &[u8],
+) -> ConstructedProperty {
+    let (name, type_tmpl) = db.map(|db| db.find_object(id)).map_or((None, None), |v| v);
+    let value = match (db, type_tmpl) {
+        (Some(db), Some(type_tmpl)) => {
+            let mut reader = Cursor::new(data);
+            match parse_type(&mut reader, Endian::Little, type_tmpl, db, string_pool) {
+                Ok(v) => v,
+                Err(e) => {
+                    log::error!("Failed to parse type {}: {:?}", type_tmpl.name, e);
+                    ConstructedPropertyValue::Unknown(data.to_vec())
+                }
+            }
+        }
+        _ => ConstructedPropertyValue::Unknown(data.to_vec()),
+    };
+    ConstructedProperty { id, name: name.cloned(), value }
+}
+
+/// Outcome of decoding object data with its type template.
+#[derive(Debug, Clone)]
+pub enum ObjectValidation {
+    /// No template for the object type
+    MissingTemplate,
+    /// The template failed to parse the data
+    Failed(String),
+    Parsed {
+        /// Bytes left after parsing the object
+        unconsumed: usize,
+        /// Paths of nested properties left as unknown data
+        unknown_properties: Vec<String>,
+    },
+}
+
+/// Decodes object data like [`RoomData::slice`], reporting what the template didn't cover.
+pub fn validate_object(
+    db: &TemplateDatabase,
+    string_pool: Option<&StringPool>,
+    id: u32,
+    data: &[u8],
+) -> ObjectValidation {
+    let Some(tmpl) = db.find_object(id).1 else {
+        return ObjectValidation::MissingTemplate;
+    };
+    let mut reader = Cursor::new(data);
+    match parse_type(&mut reader, Endian::Little, tmpl, db, string_pool) {
+        Ok(value) => {
+            let mut unknown_properties = vec![];
+            collect_unknown(&value, &tmpl.name, &mut unknown_properties);
+            ObjectValidation::Parsed {
+                unconsumed: data.len() - reader.position() as usize,
+                unknown_properties,
+            }
+        }
+        Err(e) => ObjectValidation::Failed(format!("{e:#}")),
+    }
+}
+
+fn collect_unknown(value: &ConstructedPropertyValue, path: &str, out: &mut Vec<String>) {
+    let mut visit = |value: &ConstructedPropertyValue, path: String| match value {
+        ConstructedPropertyValue::Unknown(_) => out.push(path),
+        value => collect_unknown(value, &path, out),
+    };
+    match value {
+        ConstructedPropertyValue::PropertyList(list) => {
+            for prop in &list.properties {
+                visit(&prop.value, format!("{path}.{}", json_key(prop.name.as_deref(), prop.id)));
+            }
+        }
+        ConstructedPropertyValue::Struct(s) => {
+            for (idx, elem) in s.elements.iter().enumerate() {
+                visit(&elem.value, format!("{path}.{}", element_key(elem.name.as_deref(), idx)));
+            }
+        }
+        ConstructedPropertyValue::Typedef(t) => {
+            visit(&t.value, format!("{path}.{}", json_key(t.name.as_deref(), t.id)))
+        }
+        ConstructedPropertyValue::List(values) => {
+            for value in values {
+                visit(value, format!("{path}[]"));
+            }
+        }
+        _ => {}
+    }
+}
+
+fn collect_pool_entries(value: &ConstructedPropertyValue, out: &mut Vec<(u32, u32)>) {
+    match value {
+        ConstructedPropertyValue::PropertyList(list) => {
+            list.properties.iter().for_each(|p| collect_pool_entries(&p.value, out))
+        }
+        ConstructedPropertyValue::Struct(s) => {
+            s.elements.iter().for_each(|e| collect_pool_entries(&e.value, out))
+        }
+        ConstructedPropertyValue::Typedef(t) => collect_pool_entries(&t.value, out),
+        ConstructedPropertyValue::List(values) => {
+            values.iter().for_each(|v| collect_pool_entries(v, out))
+        }
+        ConstructedPropertyValue::String(ConstructedString {
+            storage: StringStorage::Pooled(Some(entry)),
+            ..
+        }) => out.push(*entry),
+        _ => {}
+    }
+}
+
+fn write_opt_chunk<O, W, T>(
+    w: &mut W,
+    l: &mut LayoutCursor<O>,
+    id: FourCC,
+    value: &Option<T>,
+) -> Result<()>
+where
+    O: ByteOrder + 'static,
+    W: Write + Seek,
+    T: for<'a> BinWrite<Args<'a> = ()>,
+{
+    match value {
+        Some(value) => write_chunk(w, l, id, value),
+        None => Ok(()),
+    }
+}
+
+fn slice_load_unit<O>(data: &[u8]) -> Result<LoadUnit>
+where O: ByteOrder + 'static {
+    let mut header: Option<MaybeParsed<SLoadUnitHeader>> = None;
+    let mut resources: Option<MaybeParsed<SLoadUnitResources>> = None;
+    let mut layers: Option<MaybeParsed<SLoadUnitLayers>> = None;
+    slice_chunks::<O, _, _>(
+        data,
+        |chunk, data| {
+            // Guessed layouts, kept raw if they don't match
+            match chunk.id {
+                K_CHUNK_LUHD => header = Some(MaybeParsed::read(chunk.id, data, Endian::Little)),
+                K_CHUNK_LRES => resources = Some(MaybeParsed::read(chunk.id, data, Endian::Little)),
+                K_CHUNK_LLYR => layers = Some(MaybeParsed::read(chunk.id, data, Endian::Little)),
+                id => bail!("Unknown LUNT chunk: {id:?}"),
+            }
+            Ok(())
+        },
+        |form, _| bail!("Unknown LUNT form: {:?}", form.id),
+    )?;
+    Ok(LoadUnit {
+        header: header.ok_or_else(|| anyhow!("Missing LUHD chunk"))?,
+        resources: resources.unwrap_or_default(),
+        layers: layers.unwrap_or_default(),
+    })
 }
 
 #[binrw]
@@ -577,7 +1716,22 @@ where
         PropertyTemplateType::F64 => Some(ConstructedPropertyValue::F64(reader.read_type(e)?)),
         PropertyTemplateType::PooledString => {
             let ps: PooledString = reader.read_type(e)?;
-            ps.get(string_pool).map(ConstructedPropertyValue::String)
+            let storage = if ps.a == u32::MAX {
+                StringStorage::Inline
+            } else {
+                StringStorage::Pooled(Some((ps.a, ps.b)))
+            };
+            Some(match ps.get(string_pool) {
+                Some(value) => {
+                    ConstructedPropertyValue::String(ConstructedString { value, storage })
+                }
+                // Keep unresolved strings as-is
+                None => {
+                    let mut data = Cursor::new(Vec::new());
+                    data.write_type(&ps, e)?;
+                    ConstructedPropertyValue::Unknown(data.into_inner())
+                }
+            })
         }
     })
 }
@@ -697,10 +1851,528 @@ where
     Ok(elements)
 }
 
+/// Encodes a value parsed with `tmpl` back into its binary form.
+pub fn encode_type(
+    tmpl: &TypeTemplate,
+    value: &ConstructedPropertyValue,
+    db: &TemplateDatabase,
+    string_pool: Option<&mut StringPool>,
+) -> Result<Vec<u8>> {
+    let mut writer = Cursor::new(Vec::new());
+    write_type(&mut writer, Endian::Little, tmpl, value, db, string_pool)?;
+    Ok(writer.into_inner())
+}
+
+fn write_type<W>(
+    writer: &mut W,
+    e: Endian,
+    tmpl: &TypeTemplate,
+    value: &ConstructedPropertyValue,
+    db: &TemplateDatabase,
+    string_pool: Option<&mut StringPool>,
+) -> Result<()>
+where
+    W: Write + Seek,
+{
+    match (&tmpl.template, value) {
+        (_, ConstructedPropertyValue::Unknown(data)) => writer.write_all(data)?,
+        (TypeTemplateType::PropertyList(plist_tmpl), ConstructedPropertyValue::PropertyList(v)) => {
+            write_property_list(writer, e, db, string_pool, plist_tmpl, v)?
+        }
+        (TypeTemplateType::Struct(struct_tmpl), ConstructedPropertyValue::Struct(v)) => {
+            write_struct(writer, e, db, string_pool, struct_tmpl, v)?
+        }
+        (TypeTemplateType::Enum(_), ConstructedPropertyValue::Enum(v)) => {
+            writer.write_type(&v.value, e)?
+        }
+        (_, value) => bail!("Mismatched value for type {}: {:?}", tmpl.name, value),
+    }
+    Ok(())
+}
+
+fn write_property<W>(
+    writer: &mut W,
+    e: Endian,
+    tmpl: &PropertyTemplateType,
+    value: &ConstructedPropertyValue,
+    db: &TemplateDatabase,
+    mut string_pool: Option<&mut StringPool>,
+) -> Result<()>
+where
+    W: Write + Seek,
+{
+    match (tmpl, value) {
+        // Unparsed data, or the fallback for list elements that failed to parse
+        (_, ConstructedPropertyValue::Unknown(data)) => writer.write_all(data)?,
+        (PropertyTemplateType::Enum(_), ConstructedPropertyValue::Enum(v)) => {
+            writer.write_type(&v.value, e)?
+        }
+        (
+            PropertyTemplateType::Struct(struct_prop),
+            ConstructedPropertyValue::PropertyList(_) | ConstructedPropertyValue::Struct(_),
+        ) => {
+            let tmpl = db
+                .find_struct(&struct_prop.struct_name)
+                .ok_or_else(|| anyhow!("Missing struct template {}", struct_prop.struct_name))?;
+            write_type(writer, e, tmpl, value, db, string_pool)?
+        }
+        (PropertyTemplateType::Typedef(_), ConstructedPropertyValue::Typedef(v)) => {
+            write_typedef_interface(writer, e, db, string_pool, v)?
+        }
+        (PropertyTemplateType::List(list_prop), ConstructedPropertyValue::List(v)) => {
+            write_list(writer, e, v, |writer, e, v| {
+                write_property(writer, e, &list_prop.element, v, db, string_pool.as_deref_mut())
+            })?
+        }
+        (PropertyTemplateType::Id, ConstructedPropertyValue::Id(v)) => writer.write_type(v, e)?,
+        (PropertyTemplateType::Color, ConstructedPropertyValue::Color(v)) => {
+            writer.write_type(v, e)?
+        }
+        (PropertyTemplateType::Vector, ConstructedPropertyValue::Vector(v)) => {
+            writer.write_type(v, e)?
+        }
+        (PropertyTemplateType::Bool, ConstructedPropertyValue::Bool(v)) => {
+            writer.write_type(&(*v as u8), e)?
+        }
+        // Out of range bools are parsed as U8
+        (
+            PropertyTemplateType::Bool | PropertyTemplateType::U8,
+            ConstructedPropertyValue::U8(v),
+        ) => writer.write_type(v, e)?,
+        (PropertyTemplateType::I8, ConstructedPropertyValue::I8(v)) => writer.write_type(v, e)?,
+        (PropertyTemplateType::I16, ConstructedPropertyValue::I16(v)) => writer.write_type(v, e)?,
+        (PropertyTemplateType::I32, ConstructedPropertyValue::I32(v)) => writer.write_type(v, e)?,
+        (PropertyTemplateType::I64, ConstructedPropertyValue::I64(v)) => writer.write_type(v, e)?,
+        (PropertyTemplateType::U16, ConstructedPropertyValue::U16(v)) => writer.write_type(v, e)?,
+        (PropertyTemplateType::U32, ConstructedPropertyValue::U32(v)) => writer.write_type(v, e)?,
+        (PropertyTemplateType::U64, ConstructedPropertyValue::U64(v)) => writer.write_type(v, e)?,
+        (PropertyTemplateType::F32, ConstructedPropertyValue::F32(v)) => writer.write_type(v, e)?,
+        (PropertyTemplateType::F64, ConstructedPropertyValue::F64(v)) => writer.write_type(v, e)?,
+        (PropertyTemplateType::PooledString, ConstructedPropertyValue::String(v)) => {
+            let string = match (v.storage, string_pool) {
+                (StringStorage::Pooled(_), Some(pool)) => pool.insert(&v.value),
+                _ => PooledString::inline(&v.value),
+            };
+            writer.write_type(&string, e)?
+        }
+        (tmpl, value) => bail!(
+            "Mismatched value for {:?} property: {:?}",
+            PropertyTemplateTypeDiscriminants::from(tmpl),
+            value
+        ),
+    }
+    Ok(())
+}
+
+/// Whether `parse_property` produces a value for `tmpl`, as opposed to
+/// skipping it without consuming any data.
+fn property_has_value(tmpl: &PropertyTemplateType, db: &TemplateDatabase) -> bool {
+    match tmpl {
+        PropertyTemplateType::Unknown => false,
+        PropertyTemplateType::Enum(enum_prop) => db
+            .find_enum(&enum_prop.enum_name)
+            .map_or(true, |tmpl| matches!(tmpl.template, TypeTemplateType::Enum(_))),
+        PropertyTemplateType::Struct(struct_prop) => db
+            .find_struct(&struct_prop.struct_name)
+            .map_or(false, |tmpl| !matches!(tmpl.template, TypeTemplateType::Enum(_))),
+        _ => true,
+    }
+}
+
+fn write_property_list<W: Write + Seek>(
+    writer: &mut W,
+    e: Endian,
+    db: &TemplateDatabase,
+    mut string_pool: Option<&mut StringPool>,
+    tmpl: &PropertyListTemplate,
+    value: &ConstructedPropertyList,
+) -> Result<()> {
+    let num_properties: u16 = value.properties.len().try_into()?;
+    writer.write_type(&num_properties, e)?;
+    for prop in &value.properties {
+        let mut inner = Cursor::new(Vec::new());
+        let prop_tmpl = tmpl
+            .properties
+            .get(&HexU32(prop.id))
+            .map_or(&PropertyTemplateType::Unknown, |t| &t.template);
+        write_property(&mut inner, e, prop_tmpl, &prop.value, db, string_pool.as_deref_mut())?;
+        let data = inner.into_inner();
+        let size: u16 = data.len().try_into()?;
+        writer.write_type(&prop.id, e)?;
+        writer.write_type(&size, e)?;
+        writer.write_all(&data)?;
+    }
+    Ok(())
+}
+
+fn write_typedef_interface<W: Write + Seek>(
+    writer: &mut W,
+    e: Endian,
+    db: &TemplateDatabase,
+    string_pool: Option<&mut StringPool>,
+    value: &ConstructedTypedef,
+) -> Result<()> {
+    let data = match (db.find_typedef(value.id), &value.value) {
+        (_, ConstructedPropertyValue::Unknown(data)) => data.clone(),
+        ((_, Some(tmpl)), value) => encode_type(tmpl, value, db, string_pool)?,
+        ((_, None), _) => bail!("Missing template for typedef {:#010X}", value.id),
+    };
+    let size: u16 = data.len().try_into()?;
+    writer.write_type(&value.id, e)?;
+    writer.write_type(&size, e)?;
+    writer.write_all(&data)?;
+    Ok(())
+}
+
+fn write_struct<W: Write + Seek>(
+    writer: &mut W,
+    e: Endian,
+    db: &TemplateDatabase,
+    mut string_pool: Option<&mut StringPool>,
+    tmpl: &StructTemplate,
+    value: &ConstructedStruct,
+) -> Result<()> {
+    let mut elements = value.elements.iter();
+    for prop_tmpl in &tmpl.elements {
+        if !property_has_value(&prop_tmpl.template, db) {
+            continue;
+        }
+        let elem = elements.next().ok_or_else(|| {
+            anyhow!("Missing element {:?} in struct {}", prop_tmpl.name, value.name)
+        })?;
+        let pool = string_pool.as_deref_mut();
+        write_property(writer, e, &prop_tmpl.template, &elem.value, db, pool)?;
+    }
+    ensure!(elements.next().is_none(), "Too many elements in struct {}", value.name);
+    Ok(())
+}
+
+#[inline]
+fn write_list<W, T, Cb>(writer: &mut W, e: Endian, elements: &[T], mut cb: Cb) -> Result<()>
+where
+    W: Write + Seek,
+    Cb: FnMut(&mut W, Endian, &T) -> Result<()>,
+{
+    let num_elements: u32 = elements.len().try_into()?;
+    writer.write_type(&num_elements, e)?;
+    for element in elements {
+        cb(writer, e, element)?;
+    }
+    Ok(())
+}
+
+/// Serializes a decoded object with its type template, or as-is if it was left unknown.
+fn encode_object(
+    object: &ConstructedProperty,
+    db: &TemplateDatabase,
+    string_pool: Option<&mut StringPool>,
+) -> Result<Vec<u8>> {
+    match db.find_object(object.id) {
+        (_, Some(tmpl)) => encode_type(tmpl, &object.value, db, string_pool),
+        (_, None) => match &object.value {
+            ConstructedPropertyValue::Unknown(data) => Ok(data.clone()),
+            _ => bail!("Missing template for object {:#010X}", object.id),
+        },
+    }
+}
+
+/// Connection criteria, payload or link data as JSON, or `null` if empty.
+fn allocation_json(alloc: &SizeofAllocations, constructed: Option<&ConstructedProperty>) -> Value {
+    if alloc.type_id == 0 {
+        return Value::Null;
+    }
+    let (name, value) = match constructed {
+        Some(p) => (p.name.as_deref(), p.value.clone()),
+        None => (None, ConstructedPropertyValue::Unknown(alloc.data.clone())),
+    };
+    json!({
+        "type": json_key(name, alloc.type_id),
+        "unk": hex_string(&alloc.unk),
+        "properties": value,
+    })
+}
+
+/// Decodes JSON from [`allocation_json`].
+fn allocation_from_json(
+    value: &Value,
+    db: &TemplateDatabase,
+    string_pool: Option<&mut StringPool>,
+) -> Result<SizeofAllocations> {
+    if value.is_null() {
+        return Ok(SizeofAllocations { type_id: 0, unk: vec![], data: vec![] });
+    }
+    let type_id = object_type_from_json(db, &value["type"])?;
+    let object = object_from_json(db, type_id, &value["properties"])?;
+    Ok(SizeofAllocations {
+        type_id,
+        unk: hex_from_json(&value["unk"])?,
+        data: encode_object(&object, db, string_pool)?,
+    })
+}
+
+/// Object type ID from its template name, or hex ID without a template.
+fn object_type_from_json(db: &TemplateDatabase, value: &Value) -> Result<u32> {
+    let key = json_str(value)?;
+    match db.find_object_id(key) {
+        Some(id) => Ok(id),
+        None => Ok(key.parse::<HexU32>().with_context(|| format!("Unknown object type {key}"))?.0),
+    }
+}
+
+/// Decodes an object serialized from [`ConstructedPropertyValue`].
+pub fn object_from_json(
+    db: &TemplateDatabase,
+    id: u32,
+    value: &Value,
+) -> Result<ConstructedProperty> {
+    let (name, tmpl) = db.find_object(id);
+    let value = match tmpl {
+        Some(tmpl) => type_from_json(tmpl, value, db)?,
+        None => unknown_from_json(value)?
+            .ok_or_else(|| anyhow!("Missing template for object {:#010X}", id))?,
+    };
+    Ok(ConstructedProperty { id, name: name.cloned(), value })
+}
+
+/// Decodes a value serialized from [`ConstructedPropertyValue`], using `tmpl` for its types.
+pub fn type_from_json(
+    tmpl: &TypeTemplate,
+    value: &Value,
+    db: &TemplateDatabase,
+) -> Result<ConstructedPropertyValue> {
+    if let Some(value) = unknown_from_json(value)? {
+        return Ok(value);
+    }
+    Ok(match &tmpl.template {
+        TypeTemplateType::PropertyList(plist_tmpl) => {
+            let object = json_object(value)?;
+            let mut properties = Vec::with_capacity(object.len());
+            for (key, value) in object {
+                let (id, prop_tmpl) = match plist_tmpl
+                    .properties
+                    .iter()
+                    .find(|(_, p)| p.name.as_ref() == Some(key))
+                {
+                    Some((id, prop_tmpl)) => (id.0, Some(prop_tmpl)),
+                    None => {
+                        let id = key.parse::<HexU32>().with_context(|| {
+                            format!("Unknown property {} in {}", key, tmpl.name)
+                        })?;
+                        (id.0, plist_tmpl.properties.get(&id))
+                    }
+                };
+                let value = match prop_tmpl {
+                    Some(prop_tmpl) => property_from_json(&prop_tmpl.template, value, db)?,
+                    None => unknown_from_json(value)?
+                        .ok_or_else(|| anyhow!("Expected unknown data for property {}", key))?,
+                };
+                let name = prop_tmpl.and_then(|p| p.name.clone());
+                properties.push(ConstructedProperty { id, name, value });
+            }
+            ConstructedPropertyValue::PropertyList(Box::new(ConstructedPropertyList {
+                name: tmpl.name.clone(),
+                properties,
+            }))
+        }
+        TypeTemplateType::Struct(struct_tmpl) => {
+            let object = json_object(value)?;
+            let mut elements = vec![];
+            for prop_tmpl in &struct_tmpl.elements {
+                if !property_has_value(&prop_tmpl.template, db) {
+                    continue;
+                }
+                let key = element_key(prop_tmpl.name.as_deref(), elements.len());
+                let value = object
+                    .get(&key)
+                    .ok_or_else(|| anyhow!("Missing element {} in struct {}", key, tmpl.name))?;
+                let value = property_from_json(&prop_tmpl.template, value, db)?;
+                elements.push(ConstructedElement { name: prop_tmpl.name.clone(), value });
+            }
+            ensure!(object.len() == elements.len(), "Unknown elements in struct {}", tmpl.name);
+            ConstructedPropertyValue::Struct(Box::new(ConstructedStruct {
+                name: tmpl.name.clone(),
+                elements,
+            }))
+        }
+        TypeTemplateType::Enum(enum_tmpl) => enum_from_json(&tmpl.name, Some(enum_tmpl), value)?,
+    })
+}
+
+fn property_from_json(
+    tmpl: &PropertyTemplateType,
+    value: &Value,
+    db: &TemplateDatabase,
+) -> Result<ConstructedPropertyValue> {
+    if let Some(value) = unknown_from_json(value)? {
+        return Ok(value);
+    }
+    Ok(match tmpl {
+        PropertyTemplateType::Unknown => bail!("Expected unknown data, got {}", value),
+        PropertyTemplateType::Enum(enum_prop) => match db.find_enum(&enum_prop.enum_name) {
+            Some(tmpl) => type_from_json(tmpl, value, db)?,
+            None => enum_from_json(&enum_prop.enum_name, None, value)?,
+        },
+        PropertyTemplateType::Struct(struct_prop) => {
+            let tmpl = db
+                .find_struct(&struct_prop.struct_name)
+                .ok_or_else(|| anyhow!("Missing struct template {}", struct_prop.struct_name))?;
+            type_from_json(tmpl, value, db)?
+        }
+        PropertyTemplateType::Typedef(_) => {
+            let object = json_object(value)?;
+            let (key, value) = match object.iter().next() {
+                Some(entry) if object.len() == 1 => entry,
+                _ => bail!("Expected a single typedef entry, got {}", value),
+            };
+            let id = match db.find_typedef_id(key) {
+                Some(id) => id,
+                None => key.parse::<HexU32>().with_context(|| format!("Unknown typedef {key}"))?.0,
+            };
+            let (name, tmpl) = db.find_typedef(id);
+            let value = match tmpl {
+                Some(tmpl) => type_from_json(tmpl, value, db)?,
+                None => unknown_from_json(value)?
+                    .ok_or_else(|| anyhow!("Missing template for typedef {:#010X}", id))?,
+            };
+            ConstructedPropertyValue::Typedef(Box::new(ConstructedTypedef {
+                id,
+                name: name.cloned(),
+                value,
+            }))
+        }
+        PropertyTemplateType::List(list_prop) => ConstructedPropertyValue::List(
+            value
+                .as_array()
+                .ok_or_else(|| anyhow!("Expected an array, got {}", value))?
+                .iter()
+                .map(|v| property_from_json(&list_prop.element, v, db))
+                .collect::<Result<_>>()?,
+        ),
+        PropertyTemplateType::Id => ConstructedPropertyValue::Id(json_id(value)?),
+        PropertyTemplateType::Color => {
+            let [r, g, b, a] = json_floats(value)?;
+            ConstructedPropertyValue::Color(CColor4f::new(r, g, b, a))
+        }
+        PropertyTemplateType::Vector => {
+            let [x, y, z] = json_floats(value)?;
+            ConstructedPropertyValue::Vector(CVector3f::new(x, y, z))
+        }
+        // Out of range bools are written as numbers
+        PropertyTemplateType::Bool => match value {
+            Value::Bool(v) => ConstructedPropertyValue::Bool(*v),
+            value => ConstructedPropertyValue::U8(json_int(value)?),
+        },
+        PropertyTemplateType::I8 => ConstructedPropertyValue::I8(json_int(value)?),
+        PropertyTemplateType::I16 => ConstructedPropertyValue::I16(json_int(value)?),
+        PropertyTemplateType::I32 => ConstructedPropertyValue::I32(json_int(value)?),
+        PropertyTemplateType::I64 => ConstructedPropertyValue::I64(json_int(value)?),
+        PropertyTemplateType::U8 => ConstructedPropertyValue::U8(json_int(value)?),
+        PropertyTemplateType::U16 => ConstructedPropertyValue::U16(json_int(value)?),
+        PropertyTemplateType::U32 => ConstructedPropertyValue::U32(json_int(value)?),
+        PropertyTemplateType::U64 => ConstructedPropertyValue::U64(json_int(value)?),
+        PropertyTemplateType::F32 => ConstructedPropertyValue::F32(json_float(value)? as f32),
+        PropertyTemplateType::F64 => ConstructedPropertyValue::F64(json_float(value)?),
+        PropertyTemplateType::PooledString => {
+            let inline =
+                value.as_object().filter(|o| o.len() == 1).and_then(|o| o.get(K_JSON_INLINE));
+            let (value, storage) = match inline {
+                Some(value) => (value, StringStorage::Inline),
+                None => (value, StringStorage::Pooled(None)),
+            };
+            ConstructedPropertyValue::String(ConstructedString {
+                value: json_str(value)?.to_string(),
+                storage,
+            })
+        }
+    })
+}
+
+fn enum_from_json(
+    enum_name: &str,
+    tmpl: Option<&EnumTemplate>,
+    value: &Value,
+) -> Result<ConstructedPropertyValue> {
+    let values = tmpl.map_or(&[][..], |t| t.values.as_slice());
+    let value = match value {
+        Value::String(name) => values
+            .iter()
+            .find(|v| v.name.as_ref() == Some(name))
+            .map(|v| v.value.0)
+            .ok_or_else(|| anyhow!("Unknown value {} for enum {}", name, enum_name))?,
+        value => json_int(value)?,
+    };
+    let enum_value = values.iter().find(|v| v.value.0 == value).and_then(|v| v.name.clone());
+    Ok(ConstructedPropertyValue::Enum(Box::new(ConstructedEnumValue {
+        value,
+        enum_name: enum_name.to_string(),
+        enum_value,
+    })))
+}
+
+fn unknown_from_json(value: &Value) -> Result<Option<ConstructedPropertyValue>> {
+    let object = value.as_object().filter(|o| o.len() == 1);
+    let Some(hex) = object.and_then(|o| o.get(K_JSON_UNKNOWN)) else {
+        return Ok(None);
+    };
+    Ok(Some(ConstructedPropertyValue::Unknown(hex_from_json(hex)?)))
+}
+
+fn hex_string(data: &[u8]) -> String { data.iter().map(|b| format!("{b:02x}")).collect() }
+
+fn hex_from_json(value: &Value) -> Result<Vec<u8>> {
+    let hex = json_str(value)?;
+    ensure!(hex.len() % 2 == 0, "Odd length hex data");
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2).unwrap_or_default(), 16))
+        .collect::<Result<Vec<u8>, _>>()
+        .with_context(|| format!("Invalid hex data {hex}"))
+}
+
+fn json_id(value: &Value) -> Result<CObjectId> {
+    let id = json_str(value)?;
+    Ok(CObjectId::from(Uuid::parse_str(id).with_context(|| format!("Invalid ID {id}"))?))
+}
+
+fn json_object(value: &Value) -> Result<&serde_json::Map<String, Value>> {
+    value.as_object().ok_or_else(|| anyhow!("Expected an object, got {}", value))
+}
+
+fn json_str(value: &Value) -> Result<&str> {
+    value.as_str().ok_or_else(|| anyhow!("Expected a string, got {}", value))
+}
+
+fn json_int<T: TryFrom<i128>>(value: &Value) -> Result<T> {
+    let v = match (value.as_i64(), value.as_u64()) {
+        (Some(v), _) => v as i128,
+        (_, Some(v)) => v as i128,
+        _ => bail!("Expected an integer, got {}", value),
+    };
+    T::try_from(v).map_err(|_| anyhow!("Integer out of range: {}", v))
+}
+
+/// NaN and infinity are written as `null`.
+fn json_float(value: &Value) -> Result<f64> {
+    match value {
+        Value::Null => Ok(f64::NAN),
+        value => value.as_f64().ok_or_else(|| anyhow!("Expected a number, got {}", value)),
+    }
+}
+
+fn json_floats<const N: usize>(value: &Value) -> Result<[f32; N]> {
+    let values = value.as_array().filter(|v| v.len() == N);
+    let values = values.ok_or_else(|| anyhow!("Expected an array of {} numbers", N))?;
+    let mut out = [0.0; N];
+    for (out, value) in out.iter_mut().zip(values) {
+        *out = json_float(value)? as f32;
+    }
+    Ok(out)
+}
+
 fn slice_script_data<O>(
     data: &[u8],
     e: Endian,
-) -> Result<(Vec<ComponentProperties>, Vec<SGOComponentInstanceData>)>
+) -> Result<(ScriptDataHeader, Vec<ComponentProperties>, Vec<SGOComponentInstanceData>)>
 where
     O: ByteOrder + 'static,
 {
@@ -734,7 +2406,7 @@ where
     let sdhr = sdhr.ok_or_else(|| anyhow!("Missing SDHR chunk"))?;
     ensure!(sdhr.properties_count as usize == component_properties.len());
     ensure!(sdhr.instance_data_count as usize == instance_data.len());
-    Ok((component_properties, instance_data))
+    Ok((sdhr, component_properties, instance_data))
 }
 
 fn slice_layers<O>(data: &[u8], e: Endian) -> Result<Vec<Layer>>
@@ -748,6 +2420,7 @@ where O: ByteOrder + 'static {
                 K_FORM_LAYR => {
                     let mut header: Option<LayerHeader> = None;
                     let mut components: Vec<GameObjectComponent> = vec![];
+                    let mut gsrp: Option<Vec<u8>> = None;
                     slice_chunks::<O, _, _>(
                         data,
                         |chunk, _data| {
@@ -761,9 +2434,7 @@ where O: ByteOrder + 'static {
                         },
                         |form, data| {
                             match form.id {
-                                K_FORM_GSRP => {
-                                    // TODO
-                                }
+                                K_FORM_GSRP => gsrp = Some(data.to_vec()),
                                 K_FORM_SRIP => {
                                     slice_chunks::<O, _, _>(
                                         data,
@@ -787,7 +2458,7 @@ where O: ByteOrder + 'static {
                         },
                     )?;
                     let header = header.ok_or_else(|| anyhow!("Missing LHED chunk"))?;
-                    layers.push(Layer { header, components });
+                    layers.push(Layer { header, components, gsrp });
                 }
                 id => bail!("Unknown LYRS form: {id:?}"),
             }
@@ -1,24 +1,25 @@
 use std::{
     cmp::max,
     fmt::{Display, Formatter},
-    io::Cursor,
+    io::{Cursor, Seek, Write},
     marker::PhantomData,
     num::NonZeroUsize,
     ops::Range,
 };
 
 use anyhow::{anyhow, bail, ensure, Context, Result};
-use binrw::{binrw, BinReaderExt, Endian};
+use binrw::{binrw, BinReaderExt, BinWriterExt, Endian};
 use image::{
     DynamicImage, GrayImage, ImageBuffer, Luma, LumaA, Pixel, Rgb, RgbImage, Rgba, Rgba32FImage,
     RgbaImage,
 };
+use strum::EnumString;
 use tegra_swizzle::surface::BlockDim;
-use zerocopy::ByteOrder;
+use zerocopy::{ByteOrder, U32};
 
 use crate::{
     format::{chunk::ChunkDescriptor, rfrm::FormDescriptor, FourCC},
-    util::compression::decompress_into,
+    util::compression::{compress_buffer, decompress_into},
 };
 
 // Texture
@@ -173,7 +174,8 @@ pub struct STextureMetaData {
 #[binrw]
 #[repr(u32)]
 #[brw(repr(u32))]
-#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, EnumString)]
+#[strum(ascii_case_insensitive)]
 pub enum ETextureFormat {
     R8Unorm = 0,
     R8Snorm = 1,
@@ -526,7 +528,14 @@ impl ETextureFormat {
     }
 }
 
-fn deswizzle(header: &STextureHeader, data: &[u8]) -> Result<Vec<u8>> {
+struct SurfaceInfo {
+    block_dim: BlockDim,
+    bpp: usize,
+    depth: usize,
+    layers: usize,
+}
+
+fn surface_info(header: &STextureHeader) -> SurfaceInfo {
     let (bw, bh, bd) = header.format.block_size();
     let block_dim = BlockDim {
         width: NonZeroUsize::new(bw as usize).unwrap(),
@@ -539,6 +548,11 @@ fn deswizzle(header: &STextureHeader, data: &[u8]) -> Result<Vec<u8>> {
     } else {
         (1, header.layers as usize)
     };
+    SurfaceInfo { block_dim, bpp, depth, layers }
+}
+
+fn deswizzle(header: &STextureHeader, data: &[u8]) -> Result<Vec<u8>> {
+    let SurfaceInfo { block_dim, bpp, depth, layers } = surface_info(header);
     let expected_size = tegra_swizzle::surface::swizzled_surface_size(
         header.width as usize,
         header.height as usize,
@@ -563,6 +577,55 @@ fn deswizzle(header: &STextureHeader, data: &[u8]) -> Result<Vec<u8>> {
     )?)
 }
 
+fn swizzle(header: &STextureHeader, data: &[u8]) -> Result<Vec<u8>> {
+    let SurfaceInfo { block_dim, bpp, depth, layers } = surface_info(header);
+    let expected_size: u32 = header.mip_sizes.iter().sum();
+    ensure!(
+        data.len() == expected_size as usize,
+        "Texture data size {:#X} doesn't match mip sizes (expected {:#X})",
+        data.len(),
+        expected_size
+    );
+    Ok(tegra_swizzle::surface::swizzle_surface(
+        header.width as usize,
+        header.height as usize,
+        depth,
+        data,
+        block_dim,
+        None,
+        bpp,
+        header.mip_sizes.len(),
+        layers,
+    )?)
+}
+
+/// Calculates the (deswizzled) size of each mip level, including all layers.
+pub fn calculate_mip_sizes(
+    kind: ETextureType,
+    format: ETextureFormat,
+    width: u32,
+    height: u32,
+    layers: u32,
+    mip_count: u32,
+) -> Vec<u32> {
+    let (bw, bh, bd) = format.block_size();
+    let bpp = format.bytes_per_pixel();
+    (0..mip_count)
+        .map(|mip| {
+            let w = max(width >> mip, 1);
+            let h = max(height >> mip, 1);
+            let d = if kind == ETextureType::D3 { max(layers >> mip, 1) } else { layers };
+            let blocks_w = (w + bw as u32 - 1) / bw as u32;
+            let blocks_h = (h + bh as u32 - 1) / bh as u32;
+            let blocks_d = (d + bd as u32 - 1) / bd as u32;
+            blocks_w * blocks_h * blocks_d * bpp
+        })
+        .collect()
+}
+
+/// Maximum size of each compressed GPU buffer
+const GPU_BUFFER_SIZE: usize = 0x20000;
+
 #[derive(Debug, Clone)]
 pub struct TextureData<O: ByteOrder> {
     pub head: STextureHeader,
@@ -605,6 +668,67 @@ impl<O: ByteOrder> TextureData<O> {
         let deswizzled = deswizzle(&head, &buffer)?;
         Ok(Self { head, data: deswizzled, _marker: PhantomData })
     }
+
+    pub fn new(head: STextureHeader, data: Vec<u8>) -> Self {
+        Self { head, data, _marker: PhantomData }
+    }
+
+    /// Writes the TXTR form and returns the metadata describing the GPU buffers.
+    ///
+    /// Unknown metadata fields are left zeroed; callers replacing an existing
+    /// texture should copy them over from the original metadata.
+    pub fn write<W: Write + Seek>(&self, w: &mut W) -> Result<STextureMetaData> {
+        let swizzled = swizzle(&self.head, &self.data)?;
+        let mut buffers = Vec::with_capacity(swizzled.len() / GPU_BUFFER_SIZE + 1);
+        let mut compressed = Vec::new();
+        for (idx, chunk) in swizzled.chunks(GPU_BUFFER_SIZE).enumerate() {
+            let data = compress_buffer(chunk, 3)?;
+            buffers.push(STextureCompressedBufferInfo {
+                index: 0,
+                offset: compressed.len() as u32,
+                size: data.len() as u32,
+                dest_offset: (idx * GPU_BUFFER_SIZE) as u32,
+                dest_size: chunk.len() as u32,
+            });
+            compressed.extend_from_slice(&data);
+        }
+
+        let start = w.stream_position()?;
+        let mut gpu_offset = 0;
+        FormDescriptor::<O> {
+            id: K_FORM_TXTR,
+            reader_version: U32::new(47),
+            writer_version: U32::new(51),
+            ..Default::default()
+        }
+        .write(w, |w| {
+            ChunkDescriptor::<O> { id: K_CHUNK_HEAD, ..Default::default() }.write(w, |w| {
+                w.write_type(&self.head, Endian::Little)?;
+                Ok(())
+            })?;
+            ChunkDescriptor::<O> { id: K_CHUNK_GPU, ..Default::default() }.write(w, |w| {
+                gpu_offset = (w.stream_position()? - start) as u32;
+                w.write_all(&compressed)?;
+                Ok(())
+            })?;
+            Ok(())
+        })?;
+
+        Ok(STextureMetaData {
+            unk1: 0,
+            unk2: 0,
+            alloc_category: 0,
+            gpu_offset,
+            align: 0,
+            decompressed_size: swizzled.len() as u32,
+            info: vec![STextureReadInfo {
+                index: 0,
+                offset: gpu_offset,
+                size: compressed.len() as u32,
+            }],
+            buffers,
+        })
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -805,6 +929,20 @@ pub fn decompress_image(
     })
 }
 
+/// Converts an image to the raw pixel data of an uncompressed texture format.
+pub fn encode_image(format: ETextureFormat, image: &DynamicImage) -> Result<Vec<u8>> {
+    Ok(match format {
+        ETextureFormat::R8Unorm => image.to_luma8().into_raw(),
+        ETextureFormat::R16Unorm => bytemuck::cast_slice(&image.to_luma16()).to_vec(),
+        ETextureFormat::Rg8Unorm => image.to_luma_alpha8().into_raw(),
+        ETextureFormat::Rgb8Unorm => image.to_rgb8().into_raw(),
+        ETextureFormat::Rgba8Unorm | ETextureFormat::Rgba8Srgb => image.to_rgba8().into_raw(),
+        ETextureFormat::Rgba16Unorm => bytemuck::cast_slice(&image.to_rgba16()).to_vec(),
+        ETextureFormat::Rgba32Float => bytemuck::cast_slice(&image.to_rgba32f()).to_vec(),
+        format => bail!("Unsupported conversion to {format:?}"),
+    })
+}
+
 fn decompress_bcn<P, F, const BLOCK_SIZE: usize>(
     data: &[u8],
     w: u32,
@@ -44,3 +44,83 @@ pub fn decompress_into(compressed_data: &[u8], out: &mut [u8]) -> Result<u32> {
     }
     Ok(mode)
 }
+
+/// Compresses `data` with the given mode, prefixed with the mode like [`decompress_into`]
+/// expects. Data whose length isn't a multiple of the mode's group size is stored
+/// uncompressed (mode 0) instead.
+pub fn compress_buffer(data: &[u8], mode: u32) -> Result<Vec<u8>> {
+    let payload = match mode {
+        0 => None,
+        1 => lzss::compress::<1>(data),
+        2 => lzss::compress::<2>(data),
+        3 => lzss::compress::<3>(data),
+        _ => bail!("Unsupported compression mode {}", mode),
+    };
+    let (mode, payload) = match payload {
+        Some(payload) => (mode, payload),
+        None => (0, data.to_vec()),
+    };
+    let mut out = Vec::with_capacity(payload.len() + 4);
+    out.extend_from_slice(&mode.to_le_bytes());
+    out.extend_from_slice(&payload);
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(len: usize) -> Vec<u8> {
+        // Runs of repeated bytes mixed with noise, to exercise both literals and back-references
+        let mut state = 0x12345678u32;
+        (0..len)
+            .map(|i| {
+                state = state.wrapping_mul(1103515245).wrapping_add(12345);
+                if (i / 64) % 2 == 0 {
+                    (i / 8) as u8
+                } else {
+                    (state >> 16) as u8
+                }
+            })
+            .collect()
+    }
+
+    fn round_trip(data: &[u8], mode: u32) -> u32 {
+        let compressed = compress_buffer(data, mode).unwrap();
+        let mut out = vec![0u8; data.len()];
+        let read_mode = decompress_into(&compressed, &mut out).unwrap();
+        assert_eq!(out, data, "mode {} length {}", mode, data.len());
+        read_mode
+    }
+
+    #[test]
+    fn round_trip_all_modes() {
+        for len in [0, 4, 8, 64, 1000, 0x1000, 0x10004] {
+            let data = sample(len);
+            for mode in 0..=3 {
+                assert_eq!(round_trip(&data, mode), mode);
+            }
+        }
+    }
+
+    #[test]
+    fn round_trip_repetitive() {
+        let data = vec![0xABu8; 0x8000];
+        for mode in 1..=3 {
+            let compressed = compress_buffer(&data, mode).unwrap();
+            assert!(compressed.len() < data.len() / 4);
+            assert_eq!(round_trip(&data, mode), mode);
+        }
+    }
+
+    #[test]
+    fn unaligned_falls_back_to_stored() {
+        let data = sample(1001);
+        assert_eq!(round_trip(&data, 1), 1);
+        assert_eq!(round_trip(&data, 2), 0);
+        assert_eq!(round_trip(&data, 3), 0);
+        let data = sample(1002);
+        assert_eq!(round_trip(&data, 2), 2);
+        assert_eq!(round_trip(&data, 3), 0);
+    }
+}
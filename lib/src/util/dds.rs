@@ -1,20 +1,31 @@
-use std::io::Write;
+use std::io::{Read, Write};
 
-use anyhow::Result;
-use ddsfile::{AlphaMode, D3D10ResourceDimension, DxgiFormat, NewDxgiParams};
+use anyhow::{anyhow, ensure, Result};
+use ddsfile::{
+    AlphaMode, Caps2, D3D10ResourceDimension, D3DFormat, DxgiFormat, Header10, MiscFlag,
+    NewDxgiParams,
+};
 
 use crate::format::txtr::{ETextureFormat, ETextureType, STextureHeader};
 
 pub fn write_dds<W: Write>(w: &mut W, head: &STextureHeader, data: Vec<u8>) -> Result<()> {
+    let is_cubemap = matches!(head.kind, ETextureType::Cube | ETextureType::CubeArray);
+    // TXTR layers count cube faces, the DX10 array size counts whole cubes
+    let array_layers = if is_cubemap {
+        ensure!(head.layers % 6 == 0, "Cube texture with {} faces", head.layers);
+        head.layers / 6
+    } else {
+        head.layers
+    };
     let mut dds = ddsfile::Dds::new_dxgi(NewDxgiParams {
         height: head.height,
         width: head.width,
         depth: None,
         format: to_dxgi_format(head.format),
         mipmap_levels: Some(head.mip_sizes.len() as u32),
-        array_layers: Some(head.layers),
+        array_layers: Some(array_layers),
         caps2: None,
-        is_cubemap: matches!(head.kind, ETextureType::Cube | ETextureType::CubeArray),
+        is_cubemap,
         resource_dimension: match head.kind {
             ETextureType::D1 | ETextureType::D1Array => D3D10ResourceDimension::Texture1D,
             ETextureType::D2
@@ -36,6 +47,154 @@ pub fn write_dds<W: Write>(w: &mut W, head: &STextureHeader, data: Vec<u8>) -> R
     Ok(())
 }
 
+/// Texture data read from a DDS file, in the same layout as [`write_dds`] expects.
+pub struct DdsTexture {
+    pub kind: ETextureType,
+    pub format: ETextureFormat,
+    pub width: u32,
+    pub height: u32,
+    pub layers: u32,
+    pub mip_count: u32,
+    pub data: Vec<u8>,
+}
+
+pub fn read_dds<R: Read>(r: &mut R) -> Result<DdsTexture> {
+    let dds = ddsfile::Dds::read(r)?;
+    let format = if let Some(format) = dds.get_dxgi_format() {
+        from_dxgi_format(format)
+    } else {
+        match dds.get_d3d_format() {
+            Some(D3DFormat::DXT1) => Some(ETextureFormat::RgbaBc1Unorm),
+            Some(D3DFormat::DXT3) => Some(ETextureFormat::RgbaBc2Unorm),
+            Some(D3DFormat::DXT5) => Some(ETextureFormat::RgbaBc3Unorm),
+            Some(D3DFormat::A8B8G8R8) => Some(ETextureFormat::Rgba8Unorm),
+            Some(D3DFormat::L8) => Some(ETextureFormat::R8Unorm),
+            _ => None,
+        }
+    }
+    .ok_or_else(|| anyhow!("Unsupported DDS format"))?;
+    let is_3d = dds.get_depth() > 1
+        || matches!(
+            dds.header10,
+            Some(Header10 { resource_dimension: D3D10ResourceDimension::Texture3D, .. })
+        );
+    // Layers count each cube face, as in TXTR
+    let (is_cube, layers) = match &dds.header10 {
+        // DX10 array size counts whole cubes
+        Some(h10) if h10.misc_flag.contains(MiscFlag::TEXTURECUBE) => (true, h10.array_size * 6),
+        Some(h10) => (false, h10.array_size.max(1)),
+        // Legacy cube maps hold a single cube
+        None if dds.header.caps2.contains(Caps2::CUBEMAP) => (true, 6),
+        None => (false, 1),
+    };
+    let (kind, layers) = if is_3d {
+        (ETextureType::D3, dds.get_depth())
+    } else if is_cube {
+        (if layers > 6 { ETextureType::CubeArray } else { ETextureType::Cube }, layers)
+    } else if dds.get_height() == 1 {
+        (if layers > 1 { ETextureType::D1Array } else { ETextureType::D1 }, layers)
+    } else {
+        (if layers > 1 { ETextureType::D2Array } else { ETextureType::D2 }, layers)
+    };
+    Ok(DdsTexture {
+        kind,
+        format,
+        width: dds.get_width(),
+        height: dds.get_height(),
+        layers,
+        mip_count: dds.get_num_mipmap_levels(),
+        data: dds.data,
+    })
+}
+
+fn from_dxgi_format(format: DxgiFormat) -> Option<ETextureFormat> {
+    Some(match format {
+        DxgiFormat::R8_UNorm => ETextureFormat::R8Unorm,
+        DxgiFormat::R8_SNorm => ETextureFormat::R8Snorm,
+        DxgiFormat::R8_UInt => ETextureFormat::R8Uint,
+        DxgiFormat::R8_SInt => ETextureFormat::R8Sint,
+        DxgiFormat::R16_UNorm => ETextureFormat::R16Unorm,
+        DxgiFormat::R16_SNorm => ETextureFormat::R16Snorm,
+        DxgiFormat::R16_UInt => ETextureFormat::R16Uint,
+        DxgiFormat::R16_SInt => ETextureFormat::R16Sint,
+        DxgiFormat::R16_Float => ETextureFormat::R16Float,
+        DxgiFormat::R32_UInt => ETextureFormat::R32Uint,
+        DxgiFormat::R32_SInt => ETextureFormat::R32Sint,
+        DxgiFormat::R8G8B8A8_UNorm => ETextureFormat::Rgba8Unorm,
+        DxgiFormat::R8G8B8A8_UNorm_sRGB => ETextureFormat::Rgba8Srgb,
+        DxgiFormat::R16G16B16A16_Float => ETextureFormat::Rgba16Float,
+        DxgiFormat::R32G32B32A32_Float => ETextureFormat::Rgba32Float,
+        DxgiFormat::D16_UNorm => ETextureFormat::Depth16Unorm,
+        DxgiFormat::D24_UNorm_S8_UInt => ETextureFormat::Depth24S8Unorm,
+        DxgiFormat::D32_Float => ETextureFormat::Depth32Float,
+        DxgiFormat::BC1_UNorm => ETextureFormat::RgbaBc1Unorm,
+        DxgiFormat::BC1_UNorm_sRGB => ETextureFormat::RgbaBc1Srgb,
+        DxgiFormat::BC2_UNorm => ETextureFormat::RgbaBc2Unorm,
+        DxgiFormat::BC2_UNorm_sRGB => ETextureFormat::RgbaBc2Srgb,
+        DxgiFormat::BC3_UNorm => ETextureFormat::RgbaBc3Unorm,
+        DxgiFormat::BC3_UNorm_sRGB => ETextureFormat::RgbaBc3Srgb,
+        DxgiFormat::BC4_UNorm => ETextureFormat::RgbaBc4Unorm,
+        DxgiFormat::BC4_SNorm => ETextureFormat::RgbaBc4Snorm,
+        DxgiFormat::BC5_UNorm => ETextureFormat::RgbaBc5Unorm,
+        DxgiFormat::BC5_SNorm => ETextureFormat::RgbaBc5Snorm,
+        DxgiFormat::R11G11B10_Float => ETextureFormat::Rg11B10Float,
+        DxgiFormat::R32_Float => ETextureFormat::R32Float,
+        DxgiFormat::R8G8_UNorm => ETextureFormat::Rg8Unorm,
+        DxgiFormat::R8G8_SNorm => ETextureFormat::Rg8Snorm,
+        DxgiFormat::R8G8_UInt => ETextureFormat::Rg8Uint,
+        DxgiFormat::R8G8_SInt => ETextureFormat::Rg8Sint,
+        DxgiFormat::R16G16_Float => ETextureFormat::Rg16Float,
+        DxgiFormat::R16G16_UNorm => ETextureFormat::Rg16Unorm,
+        DxgiFormat::R16G16_SNorm => ETextureFormat::Rg16Snorm,
+        DxgiFormat::R16G16_UInt => ETextureFormat::Rg16Uint,
+        DxgiFormat::R16G16_SInt => ETextureFormat::Rg16Sint,
+        DxgiFormat::R10G10B10A2_UNorm => ETextureFormat::Rgb10A2Unorm,
+        DxgiFormat::R10G10B10A2_UInt => ETextureFormat::Rgb10A2Uint,
+        DxgiFormat::R32G32_UInt => ETextureFormat::Rg32Uint,
+        DxgiFormat::R32G32_SInt => ETextureFormat::Rg32Sint,
+        DxgiFormat::R32G32_Float => ETextureFormat::Rg32Float,
+        DxgiFormat::R16G16B16A16_UNorm => ETextureFormat::Rgba16Unorm,
+        DxgiFormat::R16G16B16A16_SNorm => ETextureFormat::Rgba16Snorm,
+        DxgiFormat::R16G16B16A16_UInt => ETextureFormat::Rgba16Uint,
+        DxgiFormat::R16G16B16A16_SInt => ETextureFormat::Rgba16Sint,
+        DxgiFormat::R32G32B32A32_UInt => ETextureFormat::Rgba32Uint,
+        DxgiFormat::R32G32B32A32_SInt => ETextureFormat::Rgba32Sint,
+        DxgiFormat::ASTC_4x4_UNorm => ETextureFormat::RgbaAstc4x4,
+        DxgiFormat::ASTC_5x4_UNorm => ETextureFormat::RgbaAstc5x4,
+        DxgiFormat::ASTC_5x5_UNorm => ETextureFormat::RgbaAstc5x5,
+        DxgiFormat::ASTC_6x5_UNorm => ETextureFormat::RgbaAstc6x5,
+        DxgiFormat::ASTC_6x6_UNorm => ETextureFormat::RgbaAstc6x6,
+        DxgiFormat::ASTC_8x5_UNorm => ETextureFormat::RgbaAstc8x5,
+        DxgiFormat::ASTC_8x6_UNorm => ETextureFormat::RgbaAstc8x6,
+        DxgiFormat::ASTC_8x8_UNorm => ETextureFormat::RgbaAstc8x8,
+        DxgiFormat::ASTC_10x5_UNorm => ETextureFormat::RgbaAstc10x5,
+        DxgiFormat::ASTC_10x6_UNorm => ETextureFormat::RgbaAstc10x6,
+        DxgiFormat::ASTC_10x8_UNorm => ETextureFormat::RgbaAstc10x8,
+        DxgiFormat::ASTC_10x10_UNorm => ETextureFormat::RgbaAstc10x10,
+        DxgiFormat::ASTC_12x10_UNorm => ETextureFormat::RgbaAstc12x10,
+        DxgiFormat::ASTC_12x12_UNorm => ETextureFormat::RgbaAstc12x12,
+        DxgiFormat::ASTC_4x4_UNorm_sRGB => ETextureFormat::RgbaAstc4x4Srgb,
+        DxgiFormat::ASTC_5x4_UNorm_sRGB => ETextureFormat::RgbaAstc5x4Srgb,
+        DxgiFormat::ASTC_5x5_UNorm_sRGB => ETextureFormat::RgbaAstc5x5Srgb,
+        DxgiFormat::ASTC_6x5_UNorm_sRGB => ETextureFormat::RgbaAstc6x5Srgb,
+        DxgiFormat::ASTC_6x6_UNorm_sRGB => ETextureFormat::RgbaAstc6x6Srgb,
+        DxgiFormat::ASTC_8x5_UNorm_sRGB => ETextureFormat::RgbaAstc8x5Srgb,
+        DxgiFormat::ASTC_8x6_UNorm_sRGB => ETextureFormat::RgbaAstc8x6Srgb,
+        DxgiFormat::ASTC_8x8_UNorm_sRGB => ETextureFormat::RgbaAstc8x8Srgb,
+        DxgiFormat::ASTC_10x5_UNorm_sRGB => ETextureFormat::RgbaAstc10x5Srgb,
+        DxgiFormat::ASTC_10x6_UNorm_sRGB => ETextureFormat::RgbaAstc10x6Srgb,
+        DxgiFormat::ASTC_10x8_UNorm_sRGB => ETextureFormat::RgbaAstc10x8Srgb,
+        DxgiFormat::ASTC_10x10_UNorm_sRGB => ETextureFormat::RgbaAstc10x10Srgb,
+        DxgiFormat::ASTC_12x10_UNorm_sRGB => ETextureFormat::RgbaAstc12x10Srgb,
+        DxgiFormat::ASTC_12x12_UNorm_sRGB => ETextureFormat::RgbaAstc12x12Srgb,
+        DxgiFormat::BC6H_UF16 => ETextureFormat::BptcUfloat,
+        DxgiFormat::BC6H_SF16 => ETextureFormat::BptcSfloat,
+        DxgiFormat::BC7_UNorm => ETextureFormat::BptcUnorm,
+        DxgiFormat::BC7_UNorm_sRGB => ETextureFormat::BptcUnormSrgb,
+        _ => return None,
+    })
+}
+
 fn to_dxgi_format(format: ETextureFormat) -> DxgiFormat {
     match format {
         ETextureFormat::R8Unorm => DxgiFormat::R8_UNorm,
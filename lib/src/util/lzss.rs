@@ -34,3 +34,99 @@ pub fn decompress<const M: u8>(mut input: &[u8], output: &mut [u8]) -> bool {
 
     out_cur == output.len()
 }
+
+const HASH_BITS: u32 = 16;
+const MAX_CHAIN: usize = 256;
+
+#[inline]
+fn hash(data: &[u8]) -> usize {
+    let v = (data[0] as u32) << 16 | (data[1] as u32) << 8 | data[2] as u32;
+    (v.wrapping_mul(0x9E3779B1) >> (32 - HASH_BITS)) as usize
+}
+
+#[inline]
+fn insert(input: &[u8], pos: usize, idx: usize, head: &mut [u32], prev: &mut [u32]) {
+    if pos + 3 <= input.len() {
+        let h = hash(&input[pos..]);
+        prev[idx] = head[h];
+        head[h] = idx as u32;
+    }
+}
+
+/// Compresses `input` into a stream accepted by [`decompress`] with the same mode.
+///
+/// Data is processed in groups of `2^(M-1)` bytes, so the input length must be a
+/// multiple of the group size. Returns `None` otherwise.
+pub fn compress<const M: u8>(input: &[u8]) -> Option<Vec<u8>> {
+    let group_len = 2usize.pow(M as u32 - 1);
+    if input.len() % group_len != 0 {
+        return None;
+    }
+    let num_groups = input.len() / group_len;
+    let min_count = 4 - M as usize;
+    let max_count = min_count + 0xF;
+
+    // Hash chains over group indices
+    let mut head = vec![u32::MAX; 1 << HASH_BITS];
+    let mut prev = vec![u32::MAX; num_groups];
+    let group_eq = |a: usize, b: usize| {
+        input[a * group_len..(a + 1) * group_len] == input[b * group_len..(b + 1) * group_len]
+    };
+
+    let mut output = Vec::with_capacity(input.len() + input.len() / 8 + 1);
+    let mut header_pos = 0usize;
+    let mut group = 0u8;
+    let mut cur = 0usize;
+    while cur < num_groups {
+        if group == 0 {
+            header_pos = output.len();
+            output.push(0);
+            group = 8;
+        }
+
+        let mut best_count = 0usize;
+        let mut best_dist = 0usize;
+        let pos = cur * group_len;
+        if pos + 3 <= input.len() {
+            let mut candidate = head[hash(&input[pos..])];
+            let mut chain = 0;
+            while candidate != u32::MAX && chain < MAX_CHAIN {
+                let dist = cur - candidate as usize;
+                if dist > 0xFFF {
+                    break;
+                }
+                let limit = max_count.min(num_groups - cur);
+                let mut count = 0;
+                // Overlapping copies are fine; the decoder copies forward
+                while count < limit && group_eq(candidate as usize + count, cur + count) {
+                    count += 1;
+                }
+                if count > best_count {
+                    best_count = count;
+                    best_dist = dist;
+                    if count == limit {
+                        break;
+                    }
+                }
+                candidate = prev[candidate as usize];
+                chain += 1;
+            }
+        }
+
+        if best_count >= min_count {
+            output[header_pos] |= 1 << (group - 1);
+            output.push((((best_count - min_count) << 4) | (best_dist >> 8)) as u8);
+            output.push(best_dist as u8);
+            for idx in cur..cur + best_count {
+                insert(input, idx * group_len, idx, &mut head, &mut prev);
+            }
+            cur += best_count;
+        } else {
+            output.extend_from_slice(&input[pos..pos + group_len]);
+            insert(input, pos, cur, &mut head, &mut prev);
+            cur += 1;
+        }
+        group -= 1;
+    }
+    Some(output)
+}
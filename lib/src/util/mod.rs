@@ -5,6 +5,7 @@ pub mod file;
 pub mod lzss;
 pub mod read;
 pub mod templates;
+pub mod world;
 
 /// Creates a fixed-size array reference from a slice.
 #[macro_export]
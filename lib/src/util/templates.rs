@@ -1,6 +1,10 @@
-use std::{fmt, io::BufReader, path::Path};
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
 
-use anyhow::Context;
+use anyhow::{anyhow, ensure, Context};
 use indexmap::IndexMap;
 use serde::{de, ser};
 use serde_derive::{Deserialize, Serialize};
@@ -25,18 +29,141 @@ impl TemplateDatabase {
         (self.root.typedefs.get(&HexU32(id)), self.typedefs.get(&HexU32(id)))
     }
 
+    /// Object type ID for a template name.
+    pub fn find_object_id(&self, name: &str) -> Option<u32> {
+        self.root.objects.iter().find(|(_, n)| *n == name).map(|(id, _)| id.0)
+    }
+
+    /// Typedef type ID for a template name.
+    pub fn find_typedef_id(&self, name: &str) -> Option<u32> {
+        self.root.typedefs.iter().find(|(_, n)| *n == name).map(|(id, _)| id.0)
+    }
+
     pub fn find_struct(&self, name: &str) -> Option<&TypeTemplate> { self.structs.get(name) }
 
     pub fn find_enum(&self, name: &str) -> Option<&TypeTemplate> { self.enums.get(name) }
 }
 
+/// Environment variable overriding the directory bundled templates are read from, when they
+/// aren't embedded.
+pub const TEMPLATES_DIR_ENV: &str = "RETROTOOL_TEMPLATES_DIR";
+
+/// Game whose bundled templates are used by default.
+pub const DEFAULT_GAME: &str = "mp1r";
+
+#[cfg(feature = "embed_templates")]
+mod embedded {
+    include!(concat!(env!("OUT_DIR"), "/templates.rs"));
+}
+
+/// Loads a template directory (containing `root.json`) from disk.
 pub fn load_templates(path: &Path) -> anyhow::Result<Box<TemplateDatabase>> {
+    load_templates_with(&path.display().to_string(), |name| {
+        let file_path = path.join(name);
+        if !file_path.exists() {
+            return Ok(None);
+        }
+        std::fs::read_to_string(&file_path)
+            .map(Some)
+            .with_context(|| format!("Failed to read {}", file_path.display()))
+    })
+}
+
+/// Loads the templates for `game`, or from `path` if given.
+///
+/// With the `embed_templates` feature, bundled templates are read from the binary;
+/// otherwise from the directory returned by `templates_dir`.
+pub fn load_game_templates(
+    game: &str,
+    path: Option<&Path>,
+) -> anyhow::Result<Box<TemplateDatabase>> {
+    match path {
+        Some(path) => load_templates(path),
+        None => load_bundled_templates(game),
+    }
+}
+
+#[cfg(feature = "embed_templates")]
+fn load_bundled_templates(game: &str) -> anyhow::Result<Box<TemplateDatabase>> {
+    let (_, files) = embedded::TEMPLATES
+        .iter()
+        .find(|(name, _)| *name == game)
+        .ok_or_else(|| anyhow!("No embedded templates for game '{}'", game))?;
+    load_templates_with(game, |name| {
+        Ok(files.iter().find(|(path, _)| *path == name).map(|(_, data)| data.to_string()))
+    })
+}
+
+#[cfg(not(feature = "embed_templates"))]
+fn load_bundled_templates(game: &str) -> anyhow::Result<Box<TemplateDatabase>> {
+    load_templates(&templates_dir().join(game)).with_context(|| {
+        format!(
+            "Failed to load templates for game '{}'; set {} to the templates directory",
+            game, TEMPLATES_DIR_ENV
+        )
+    })
+}
+
+/// Directory the bundled templates are read from when they aren't embedded, one
+/// subdirectory per game: [`TEMPLATES_DIR_ENV`] if set, otherwise `templates` next to the
+/// executable.
+#[cfg(not(feature = "embed_templates"))]
+pub fn templates_dir() -> PathBuf {
+    if let Some(dir) = std::env::var_os(TEMPLATES_DIR_ENV) {
+        return PathBuf::from(dir);
+    }
+    std::env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(|dir| dir.join("templates")))
+        .unwrap_or_else(|| PathBuf::from("templates"))
+}
+
+/// Directory the bundled templates for `game` are read from, or `None` if they are embedded
+/// in the binary.
+#[cfg(feature = "embed_templates")]
+pub fn bundled_templates_dir(_game: &str) -> Option<PathBuf> { None }
+
+/// Directory the bundled templates for `game` are read from, or `None` if they are embedded
+/// in the binary.
+#[cfg(not(feature = "embed_templates"))]
+pub fn bundled_templates_dir(game: &str) -> Option<PathBuf> { Some(templates_dir().join(game)) }
+
+/// Games with bundled templates.
+#[cfg(feature = "embed_templates")]
+pub fn bundled_games() -> Vec<String> {
+    embedded::TEMPLATES.iter().map(|(name, _)| name.to_string()).collect()
+}
+
+/// Games with bundled templates.
+#[cfg(not(feature = "embed_templates"))]
+pub fn bundled_games() -> Vec<String> {
+    let Ok(entries) = std::fs::read_dir(templates_dir()) else {
+        return vec![];
+    };
+    let mut games = entries
+        .filter_map(|e| e.ok())
+        .filter(|e| e.path().join("root.json").is_file())
+        .filter_map(|e| e.file_name().into_string().ok())
+        .collect::<Vec<_>>();
+    games.sort();
+    games
+}
+
+/// Builds a database from `read`, which returns the contents of a file relative to the
+/// template root, or `None` if it doesn't exist. `source` is only used for messages.
+fn load_templates_with<F>(source: &str, read: F) -> anyhow::Result<Box<TemplateDatabase>>
+where F: Fn(&str) -> anyhow::Result<Option<String>> {
+    let parse = |name: &str| -> anyhow::Result<Option<TypeTemplate>> {
+        read(name)?
+            .map(|data| serde_json::from_str(&data))
+            .transpose()
+            .with_context(|| format!("While parsing {}/{}", source, name))
+    };
     let root: TemplateRoot = {
-        let root_path = path.join("root.json");
-        let file = std::fs::File::open(root_path.as_path())
-            .with_context(|| format!("Failed to open {}", root_path.display()))?;
-        serde_json::from_reader(BufReader::new(file))
-            .with_context(|| format!("While reading {}", root_path.display()))?
+        let data =
+            read("root.json")?.ok_or_else(|| anyhow!("Failed to open {}/root.json", source))?;
+        serde_json::from_str(&data)
+            .with_context(|| format!("While reading {}/root.json", source))?
     };
     let mut database = Box::new(TemplateDatabase {
         root,
@@ -46,58 +173,135 @@ pub fn load_templates(path: &Path) -> anyhow::Result<Box<TemplateDatabase>> {
         enums: IndexMap::new(),
     });
     for (id, name) in &database.root.objects {
-        let type_path = path.join("objects").join(format!("{}.json", name));
-        if !type_path.exists() {
+        let Some(template) = parse(&format!("objects/{}.json", name))? else {
             continue;
-        }
-        let file = std::fs::File::open(type_path.as_path())
-            .with_context(|| format!("Failed to read {}", type_path.display()))?;
-        let template = serde_json::from_reader(BufReader::new(file))
-            .with_context(|| format!("While parsing {}", type_path.display()))?;
+        };
         database.objects.insert(*id, template);
         log::info!("Loaded object template {}", name)
     }
     for (id, name) in &database.root.typedefs {
-        let type_path = path.join("typedefs").join(format!("{}.json", name));
-        if !type_path.exists() {
+        let Some(template) = parse(&format!("typedefs/{}.json", name))? else {
             continue;
-        }
-        let file = std::fs::File::open(type_path.as_path())
-            .with_context(|| format!("Failed to read {}", type_path.display()))?;
-        let template = serde_json::from_reader(BufReader::new(file))
-            .with_context(|| format!("While parsing {}", type_path.display()))?;
+        };
         database.typedefs.insert(*id, template);
         log::info!("Loaded typedef template {}", name)
     }
     for name in &database.root.structs {
-        let type_path = path.join("structs").join(format!("{}.json", name));
-        if !type_path.exists() {
+        let Some(template) = parse(&format!("structs/{}.json", name))? else {
             log::warn!("Struct template {} not found", name);
             continue;
-        }
-        let file = std::fs::File::open(type_path.as_path())
-            .with_context(|| format!("Failed to read {}", type_path.display()))?;
-        let template = serde_json::from_reader(BufReader::new(file))
-            .with_context(|| format!("While parsing {}", type_path.display()))?;
+        };
         database.structs.insert(name.clone(), template);
         log::info!("Loaded struct template {}", name)
     }
     for name in &database.root.enums {
-        let type_path = path.join("enums").join(format!("{}.json", name));
-        if !type_path.exists() {
+        let Some(template) = parse(&format!("enums/{}.json", name))? else {
             log::warn!("Enum template {} not found", name);
             continue;
-        }
-        let file = std::fs::File::open(type_path.as_path())
-            .with_context(|| format!("Failed to read {}", type_path.display()))?;
-        let template = serde_json::from_reader(BufReader::new(file))
-            .with_context(|| format!("While parsing {}", type_path.display()))?;
+        };
         database.enums.insert(name.clone(), template);
         log::info!("Loaded enum template {}", name)
     }
     Ok(database)
 }
 
+/// Schema reference for type templates, relative to their directory.
+pub const TYPE_SCHEMA: &str = "../type.schema.json";
+
+/// Where a type template is stored and how `root.json` refers to it.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, EnumIter, EnumMessage)]
+pub enum TemplateKind {
+    #[strum(message = "Object")]
+    Object,
+    #[strum(message = "Typedef")]
+    Typedef,
+    #[strum(message = "Struct")]
+    Struct,
+    #[strum(message = "Enum")]
+    Enum,
+}
+
+impl TemplateKind {
+    /// Template subdirectory, also the `root.json` key.
+    pub fn dir_name(self) -> &'static str {
+        match self {
+            TemplateKind::Object => "objects",
+            TemplateKind::Typedef => "typedefs",
+            TemplateKind::Struct => "structs",
+            TemplateKind::Enum => "enums",
+        }
+    }
+
+    /// Whether `root.json` maps a type ID to the template name.
+    pub fn has_id(self) -> bool { matches!(self, TemplateKind::Object | TemplateKind::Typedef) }
+}
+
+/// Writes `template` to `{dir}/{kind}/{name}.json` and registers it in `dir/root.json`.
+///
+/// Objects and typedefs are registered under `id`, replacing the name of an existing entry.
+/// The rest of `root.json` is kept as is, including the formatting of existing IDs.
+pub fn save_template(
+    dir: &Path,
+    kind: TemplateKind,
+    name: &str,
+    id: Option<HexU32>,
+    template: &TypeTemplate,
+) -> anyhow::Result<()> {
+    ensure!(
+        !name.is_empty() && !name.contains(['/', '\\', '.']),
+        "Invalid template name '{}'",
+        name
+    );
+    let root_path = dir.join("root.json");
+    let mut root: serde_json::Value = {
+        let data = std::fs::read_to_string(&root_path)
+            .with_context(|| format!("Failed to read {}", root_path.display()))?;
+        serde_json::from_str(&data)
+            .with_context(|| format!("While parsing {}", root_path.display()))?
+    };
+    let entry = root
+        .as_object_mut()
+        .ok_or_else(|| anyhow!("Expected an object in {}", root_path.display()))?
+        .entry(kind.dir_name());
+    if kind.has_id() {
+        let id = id.ok_or_else(|| anyhow!("Template '{}' needs a type ID", name))?;
+        let map = entry
+            .or_insert_with(|| serde_json::Value::Object(Default::default()))
+            .as_object_mut()
+            .ok_or_else(|| anyhow!("Expected \"{}\" to be an object", kind.dir_name()))?;
+        let key = map
+            .keys()
+            .find(|key| HexU32::from_str(key).ok() == Some(id))
+            .cloned()
+            .unwrap_or_else(|| format!("{:#010x}", id.0));
+        map.insert(key, name.into());
+    } else {
+        let list = entry
+            .or_insert_with(|| serde_json::Value::Array(vec![]))
+            .as_array_mut()
+            .ok_or_else(|| anyhow!("Expected \"{}\" to be an array", kind.dir_name()))?;
+        if !list.iter().any(|v| v.as_str() == Some(name)) {
+            // Lists are sorted by name
+            let index = list.partition_point(|v| v.as_str().map_or(true, |s| s < name));
+            list.insert(index, name.into());
+        }
+    }
+
+    let type_dir = dir.join(kind.dir_name());
+    std::fs::create_dir_all(&type_dir)
+        .with_context(|| format!("Failed to create {}", type_dir.display()))?;
+    write_json(&type_dir.join(format!("{}.json", name)), template)?;
+    write_json(&root_path, &root)
+}
+
+/// Writes pretty-printed JSON with a trailing newline, like the bundled templates.
+fn write_json<T>(path: &Path, value: &T) -> anyhow::Result<()>
+where T: ser::Serialize + ?Sized {
+    let mut out = serde_json::to_string_pretty(value)?;
+    out.push('\n');
+    std::fs::write(path, out).with_context(|| format!("Failed to write {}", path.display()))
+}
+
 pub fn load_type_template(contents: &str) -> anyhow::Result<TypeTemplate> {
     serde_json::from_str(contents).map_err(|e| e.into())
 }
@@ -175,6 +379,17 @@ impl fmt::Debug for HexU32 {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result { write!(f, "{:#010X}", self.0) }
 }
 
+impl FromStr for HexU32 {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let value = s
+            .strip_prefix("0x")
+            .ok_or_else(|| anyhow!("Expected a hex string starting with \"0x\""))?;
+        Ok(Self(u32::from_str_radix(value, 16)?))
+    }
+}
+
 impl ser::Serialize for HexU32 {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where S: ser::Serializer {
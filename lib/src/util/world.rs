@@ -0,0 +1,215 @@
+use std::{collections::BTreeMap, io::Write};
+
+use anyhow::Result;
+use uuid::Uuid;
+
+use crate::format::{
+    pack::Package,
+    room::{RoomData, K_FORM_ROOM},
+    ByteOrderExt,
+};
+
+/// Room in the connectivity graph.
+#[derive(Debug, Clone)]
+pub struct RoomNode {
+    pub id: Uuid,
+    pub name: Option<String>,
+    pub parent_id: Option<Uuid>,
+    pub dock_ids: Vec<Uuid>,
+    /// The room has a DOCK chunk that didn't match the guessed layout, so its dock
+    /// connections are missing from the graph.
+    pub docks_undecoded: bool,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd)]
+pub enum RoomEdgeKind {
+    /// Dock connection between two rooms
+    Dock,
+    /// `SGameAreaHeader::parent_room_id`
+    Parent,
+}
+
+#[derive(Debug, Clone)]
+pub struct RoomEdge {
+    pub kind: RoomEdgeKind,
+    pub from_room: Uuid,
+    pub from_dock: Option<Uuid>,
+    pub to_room: Uuid,
+    pub to_dock: Option<Uuid>,
+}
+
+/// Room adjacency, built from ROOM docks and parent references.
+///
+/// The DOCK layout hasn't been verified against retail data yet. Doors connect both ways, so a
+/// correct layout yields a reciprocated edge for every dock link between loaded rooms; use
+/// [`WorldGraph::dock_check`] to measure that before trusting dock edges, and
+/// [`WorldGraph::is_reciprocated`] to filter out the ones that aren't.
+#[derive(Debug, Clone, Default)]
+pub struct WorldGraph {
+    pub rooms: BTreeMap<Uuid, RoomNode>,
+    pub edges: Vec<RoomEdge>,
+    /// Rooms that failed to read, with the error. Edges pointing at them stay unresolved.
+    pub failed: BTreeMap<Uuid, String>,
+}
+
+/// Result of [`WorldGraph::dock_check`].
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub struct DockCheck {
+    /// Dock edges whose target room was loaded, so they could be checked.
+    pub checked: usize,
+    /// Checked dock edges that have a matching edge back.
+    pub reciprocated: usize,
+}
+
+impl DockCheck {
+    /// Whether every checked dock link was found in both directions.
+    pub fn is_symmetric(&self) -> bool { self.checked == self.reciprocated }
+}
+
+impl WorldGraph {
+    /// Adds every ROOM in a package. Rooms already in the graph are skipped, and rooms that
+    /// fail to read are logged and recorded in `failed`.
+    pub fn add_package<O>(&mut self, data: &[u8]) -> Result<()>
+    where O: ByteOrderExt + 'static {
+        for entry in Package::<O>::read_sparse(data)? {
+            if entry.kind != K_FORM_ROOM
+                || self.rooms.contains_key(&entry.id)
+                || self.failed.contains_key(&entry.id)
+            {
+                continue;
+            }
+            // Only headers and docks are needed, so script properties are left unparsed
+            let result = Package::<O>::read_asset(data, entry.id)
+                .and_then(|room_data| RoomData::<O>::slice(&room_data, None));
+            match result {
+                Ok(room) => self.add_room(entry.id, entry.names.first().cloned(), &room),
+                Err(e) => {
+                    log::warn!("Failed to read room {}: {e:?}", entry.id);
+                    self.failed.insert(entry.id, format!("{e:#}"));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn add_room<O>(&mut self, id: Uuid, name: Option<String>, room: &RoomData<O>)
+    where O: ByteOrderExt + 'static {
+        let parent_id = Some(room.room_header.parent_room_id)
+            .filter(|id| !id.is_nil())
+            .map(|id| id.into_inner());
+        if let Some(parent_id) = parent_id {
+            self.edges.push(RoomEdge {
+                kind: RoomEdgeKind::Parent,
+                from_room: id,
+                from_dock: None,
+                to_room: parent_id,
+                to_dock: None,
+            });
+        }
+        let docks = room.docks().into_iter().flat_map(|d| &d.docks);
+        let mut dock_ids = vec![];
+        for dock in docks {
+            dock_ids.push(dock.id.into_inner());
+            for connection in &dock.connections {
+                if connection.room_id.is_nil() {
+                    continue;
+                }
+                self.edges.push(RoomEdge {
+                    kind: RoomEdgeKind::Dock,
+                    from_room: id,
+                    from_dock: Some(dock.id.into_inner()),
+                    to_room: connection.room_id.into_inner(),
+                    to_dock: Some(connection.dock_id.into_inner()).filter(|id: &Uuid| !id.is_nil()),
+                });
+            }
+        }
+        let docks_undecoded = room.docks.is_some() && room.docks().is_none();
+        self.rooms.insert(id, RoomNode { id, name, parent_id, dock_ids, docks_undecoded });
+    }
+
+    /// Whether an edge points at a room that was loaded.
+    pub fn is_resolved(&self, edge: &RoomEdge) -> bool {
+        match edge.to_dock {
+            Some(dock) => {
+                self.rooms.get(&edge.to_room).map_or(false, |room| room.dock_ids.contains(&dock))
+            }
+            None => self.rooms.contains_key(&edge.to_room),
+        }
+    }
+
+    /// Whether a resolved dock edge has a matching edge from the target room back to the
+    /// source. Parent and unresolved edges are never reciprocated.
+    pub fn is_reciprocated(&self, edge: &RoomEdge) -> bool {
+        if edge.kind != RoomEdgeKind::Dock || !self.is_resolved(edge) {
+            return false;
+        }
+        self.edges.iter().any(|other| {
+            other.kind == RoomEdgeKind::Dock
+                && other.from_room == edge.to_room
+                && other.to_room == edge.from_room
+                && (edge.to_dock.is_none() || other.from_dock == edge.to_dock)
+                && (other.to_dock.is_none() || other.to_dock == edge.from_dock)
+        })
+    }
+
+    /// Checks dock links for symmetry across all loaded rooms. A wrong DOCK layout that still
+    /// happens to parse shows up here as one-sided links.
+    pub fn dock_check(&self) -> DockCheck {
+        let mut check = DockCheck::default();
+        for edge in &self.edges {
+            if edge.kind != RoomEdgeKind::Dock || !self.is_resolved(edge) {
+                continue;
+            }
+            check.checked += 1;
+            if self.is_reciprocated(edge) {
+                check.reciprocated += 1;
+            }
+        }
+        check
+    }
+
+    pub fn room_name(&self, id: Uuid) -> String {
+        match self.rooms.get(&id).and_then(|room| room.name.as_ref()) {
+            Some(name) => name.clone(),
+            None => id.to_string(),
+        }
+    }
+
+    /// Writes the graph in GraphViz DOT format.
+    /// Dock edges are drawn solid, parent edges dashed, unresolved targets in red and dock
+    /// edges without a matching edge back dotted in orange.
+    pub fn write_dot<W: Write>(&self, w: &mut W) -> Result<()> {
+        writeln!(w, "digraph world {{")?;
+        writeln!(w, "    node [shape=box];")?;
+        for room in self.rooms.values() {
+            writeln!(w, "    \"{}\" [label=\"{}\"];", room.id, escape(&self.room_name(room.id)))?;
+        }
+        for edge in &self.edges {
+            let mut attrs = vec![];
+            if edge.kind == RoomEdgeKind::Parent {
+                attrs.push("style=dashed");
+            }
+            if !self.is_resolved(edge) {
+                attrs.push("color=red");
+            } else if edge.kind == RoomEdgeKind::Dock && !self.is_reciprocated(edge) {
+                attrs.push("style=dotted");
+                attrs.push("color=orange");
+            }
+            if attrs.is_empty() {
+                writeln!(w, "    \"{}\" -> \"{}\";", edge.from_room, edge.to_room)?;
+            } else {
+                writeln!(
+                    w,
+                    "    \"{}\" -> \"{}\" [{}];",
+                    edge.from_room,
+                    edge.to_room,
+                    attrs.join(", ")
+                )?;
+            }
+        }
+        writeln!(w, "}}")?;
+        Ok(())
+    }
+}
+
+fn escape(s: &str) -> String { s.replace('\\', "\\\\").replace('"', "\\\"") }
@@ -0,0 +1,35 @@
+use anyhow::Error;
+use bevy::{
+    asset::{AssetLoader, BoxedFuture, LoadContext, LoadedAsset},
+    prelude::*,
+};
+use retrolib::format::clsn::CollisionData;
+use zerocopy::LittleEndian;
+
+#[derive(Debug, Clone, bevy::reflect::TypeUuid)]
+#[uuid = "5a4c2c3e-2d0b-4b8e-9f3e-6f1f0d8c7a21"]
+pub struct CollisionAsset {
+    pub inner: CollisionData<LittleEndian>,
+}
+
+pub struct CollisionAssetLoader;
+
+impl FromWorld for CollisionAssetLoader {
+    fn from_world(_world: &mut World) -> Self { Self }
+}
+
+impl AssetLoader for CollisionAssetLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, anyhow::Result<(), Error>> {
+        Box::pin(async move {
+            let collision = CollisionData::<LittleEndian>::slice(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(CollisionAsset { inner: collision }));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] { &["clsn", "dcln"] }
+}
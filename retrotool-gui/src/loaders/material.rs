@@ -1,15 +1,21 @@
 use anyhow::Error;
 use bevy::{
-    asset::{AssetLoader, BoxedFuture, LoadContext},
+    asset::{AssetLoader, BoxedFuture, LoadContext, LoadedAsset},
     prelude::{FromWorld, World},
 };
-use retrolib::format::{cmdl::ModelData, foot::locate_meta, mtrl::MaterialData};
+use retrolib::format::{
+    foot::{locate_asset_id, locate_meta},
+    mtrl::{MaterialData, K_FORM_MTRL},
+};
 use zerocopy::LittleEndian;
 
+use crate::AssetRef;
+
 #[derive(Debug, Clone, bevy::reflect::TypeUuid)]
 #[uuid = "83269869-1209-408e-8835-bc6f2496e82a"]
 pub struct MaterialAsset {
-    pub inner: ModelData<LittleEndian>,
+    pub asset_ref: AssetRef,
+    pub inner: MaterialData<LittleEndian>,
 }
 
 pub struct MaterialAssetLoader;
@@ -22,18 +28,16 @@ impl AssetLoader for MaterialAssetLoader {
     fn load<'a>(
         &'a self,
         bytes: &'a [u8],
-        _load_context: &'a mut LoadContext,
+        load_context: &'a mut LoadContext,
     ) -> BoxedFuture<'a, anyhow::Result<(), Error>> {
         Box::pin(async move {
+            let id = locate_asset_id::<LittleEndian>(bytes)?;
             let meta = locate_meta::<LittleEndian>(bytes)?;
-            // let (desc, data, remain) = FormDescriptor::slice(bytes, Endian::Little)?;
-            // log::info!("Loading material {:?}", desc);
-            let _mtrl = MaterialData::<LittleEndian>::slice(bytes, meta)?;
-            // fs::write("mtrl.out", &mtrl.decompressed)?;
-            // load_context.set_default_asset(
-            //     LoadedAsset::new(ModelAsset { inner: data, textures })
-            //         .with_dependencies(dependencies.into_values().collect()),
-            // );
+            let data = MaterialData::<LittleEndian>::slice(bytes, meta)?;
+            load_context.set_default_asset(LoadedAsset::new(MaterialAsset {
+                asset_ref: AssetRef { id, kind: K_FORM_MTRL },
+                inner: data,
+            }));
             Ok(())
         })
     }
@@ -1,3 +1,4 @@
+pub mod collision;
 pub mod lightprobe;
 pub mod material;
 pub mod modcon;
@@ -15,13 +16,16 @@ impl Plugin for RetroAssetPlugin {
         if !app.is_plugin_added::<package::RetroAssetIoPlugin>() {
             panic!("RetroAssetIoPlugin must be added before AssetPlugin");
         }
-        app.add_asset::<material::MaterialAsset>()
+        app.init_resource::<room::SharedTemplates>()
+            .add_asset::<collision::CollisionAsset>()
+            .add_asset::<material::MaterialAsset>()
             .add_asset::<modcon::ModConAsset>()
             .add_asset::<model::ModelAsset>()
             .add_asset::<package::PackageDirectory>()
             .add_asset::<texture::TextureAsset>()
             .add_asset::<lightprobe::LightProbeAsset>()
             .add_asset::<room::RoomAsset>()
+            .init_asset_loader::<collision::CollisionAssetLoader>()
             .init_asset_loader::<material::MaterialAssetLoader>()
             .init_asset_loader::<modcon::ModConAssetLoader>()
             .init_asset_loader::<model::ModelAssetLoader>()
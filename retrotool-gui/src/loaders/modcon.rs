@@ -5,7 +5,7 @@ use bevy::{
     asset::{AssetLoader, AssetPath, BoxedFuture, LoadContext, LoadedAsset},
     prelude::*,
 };
-use retrolib::format::mcon::ModConData;
+use retrolib::format::{foot::Footer, mcon::ModConData};
 use zerocopy::LittleEndian;
 
 use crate::loaders::model::ModelAsset;
@@ -14,6 +14,8 @@ use crate::loaders::model::ModelAsset;
 #[uuid = "83269869-1209-408e-8835-bc6f2496e82b"]
 pub struct ModConAsset {
     pub inner: ModConData<LittleEndian>,
+    /// FOOT form of the extracted file, re-emitted when saving
+    pub footer: Option<Footer<LittleEndian>>,
     pub models: Vec<Handle<ModelAsset>>,
 }
 
@@ -31,6 +33,7 @@ impl AssetLoader for ModConAssetLoader {
     ) -> BoxedFuture<'a, anyhow::Result<(), Error>> {
         Box::pin(async move {
             let mcon = ModConData::<LittleEndian>::slice(bytes)?;
+            let footer = Footer::<LittleEndian>::slice(bytes).ok();
             // println!("Loaded MCON: {:?}", mcon);
             let mut dependencies = vec![];
             let mut models = vec![];
@@ -44,7 +47,7 @@ impl AssetLoader for ModConAssetLoader {
                 }
             }
             load_context.set_default_asset(
-                LoadedAsset::new(ModConAsset { inner: mcon, models })
+                LoadedAsset::new(ModConAsset { inner: mcon, footer, models })
                     .with_dependencies(dependencies),
             );
             Ok(())
@@ -1,21 +1,93 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+};
+
 use anyhow::Error;
 use bevy::{
     asset::{AssetLoader, BoxedFuture, LoadContext, LoadedAsset},
     prelude::*,
 };
-use retrolib::format::room::RoomData;
+use retrolib::{
+    format::{foot::Footer, room::RoomData},
+    util::templates::{bundled_templates_dir, load_game_templates, TemplateDatabase, DEFAULT_GAME},
+};
 use zerocopy::LittleEndian;
 
 #[derive(Debug, Clone, bevy::reflect::TypeUuid)]
 #[uuid = "12ae034e-f1f7-404a-8b7e-d04d9f8f34a7"]
 pub struct RoomAsset {
     pub inner: RoomData<LittleEndian>,
+    /// FOOT form of the extracted file, if present
+    pub footer: Option<Footer<LittleEndian>>,
+}
+
+/// Script templates used to construct ROOM properties, shared with the asset loader.
+#[derive(Debug, Clone, Resource)]
+pub struct SharedTemplates {
+    /// Game whose bundled templates are used
+    pub game: String,
+    /// Template directory overriding the bundled templates, if not empty
+    pub path: String,
+    /// Error from the last reload
+    pub status: Option<String>,
+    /// Bumped when open rooms should construct their properties again
+    pub revision: u32,
+    db: Arc<RwLock<Option<Arc<TemplateDatabase>>>>,
+}
+
+impl Default for SharedTemplates {
+    fn default() -> Self {
+        let mut templates = Self {
+            game: DEFAULT_GAME.to_string(),
+            path: String::new(),
+            status: None,
+            revision: 0,
+            db: Default::default(),
+        };
+        templates.reload();
+        templates
+    }
 }
 
-pub struct RoomAssetLoader;
+impl SharedTemplates {
+    /// Loads templates from the current settings. Rooms loaded afterwards use them;
+    /// on failure, the previous templates are kept.
+    pub fn reload(&mut self) {
+        let path = (!self.path.is_empty()).then(|| Path::new(&self.path));
+        match load_game_templates(&self.game, path) {
+            Ok(db) => {
+                *self.db.write().unwrap() = Some(db.into());
+                self.status = None;
+            }
+            Err(e) => {
+                log::error!("Failed to load templates: {:?}", e);
+                self.status = Some(format!("{:#}", e));
+            }
+        }
+    }
+
+    /// Directory the current templates are loaded from and saved to, or `None` if they are
+    /// embedded in the binary.
+    pub fn dir(&self) -> Option<PathBuf> {
+        if self.path.is_empty() {
+            bundled_templates_dir(&self.game)
+        } else {
+            Some(PathBuf::from(&self.path))
+        }
+    }
+
+    pub fn get(&self) -> Option<Arc<TemplateDatabase>> { self.db.read().unwrap().clone() }
+}
+
+pub struct RoomAssetLoader {
+    templates: SharedTemplates,
+}
 
 impl FromWorld for RoomAssetLoader {
-    fn from_world(_world: &mut World) -> Self { Self }
+    fn from_world(world: &mut World) -> Self {
+        Self { templates: world.get_resource_or_insert_with(SharedTemplates::default).clone() }
+    }
 }
 
 impl AssetLoader for RoomAssetLoader {
@@ -25,11 +97,13 @@ impl AssetLoader for RoomAssetLoader {
         load_context: &'a mut LoadContext,
     ) -> BoxedFuture<'a, anyhow::Result<(), Error>> {
         Box::pin(async move {
-            let room = RoomData::<LittleEndian>::slice(bytes)?;
+            let db = self.templates.get();
+            let room = RoomData::<LittleEndian>::slice(bytes, db.as_deref())?;
+            let footer = Footer::<LittleEndian>::slice(bytes).ok();
             // println!("Loaded ROOM: {:?}", room);
             let dependencies = vec![];
             load_context.set_default_asset(
-                LoadedAsset::new(RoomAsset { inner: room }).with_dependencies(dependencies),
+                LoadedAsset::new(RoomAsset { inner: room, footer }).with_dependencies(dependencies),
             );
             Ok(())
         })
@@ -20,7 +20,7 @@ use bevy_egui::{egui, EguiContext, EguiContexts, EguiPlugin};
 use bevy_mod_raycast::{DefaultPluginState, DefaultRaycastingPlugin};
 use egui::{FontFamily, FontId, Frame, Rounding};
 use egui_dock::{NodeIndex, TabIndex, TabViewer as DockTabViewer};
-use retrolib::format::FourCC;
+use retrolib::{format::FourCC, util::templates::bundled_games};
 use uuid::Uuid;
 use walkdir::{DirEntry, WalkDir};
 
@@ -28,14 +28,15 @@ use crate::{
     loaders::{
         model::ModelAsset,
         package::{PackageDirectory, RetroAssetIoPlugin},
+        room::SharedTemplates,
         texture::TextureAsset,
         RetroAssetPlugin,
     },
     material::CustomMaterial,
     render::{grid::GridPlugin, TemporaryLabel},
     tabs::{
-        modcon::ModConRaycastSet, project::ProjectTab, splash::SplashTab, EditorTab, TabState,
-        TabType, TabViewer,
+        collision::CollisionRaycastSet, modcon::ModConRaycastSet, project::ProjectTab,
+        room::RoomRaycastSet, splash::SplashTab, EditorTab, TabState, TabType, TabViewer,
     },
 };
 
@@ -88,6 +89,8 @@ fn main() {
         // Ray casting
         .add_plugin(DefaultRaycastingPlugin::<ModConRaycastSet>::default())
         .insert_resource(DefaultPluginState::<ModConRaycastSet>::default().with_debug_cursor())
+        .add_plugin(DefaultRaycastingPlugin::<RoomRaycastSet>::default())
+        .add_plugin(DefaultRaycastingPlugin::<CollisionRaycastSet>::default())
         // Diagnostics
         .add_plugin(FrameTimeDiagnosticsPlugin::default())
         .add_plugin(EntityCountDiagnosticsPlugin::default())
@@ -213,6 +216,9 @@ fn ui_system(world: &mut World) {
                             ui.close_menu();
                         }
                     });
+                    egui::menu::menu_button(ui, "Templates", |ui| {
+                        templates_menu_ui(ui, world.resource_mut::<SharedTemplates>().as_mut());
+                    });
                 });
             });
 
@@ -351,6 +357,26 @@ fn ui_system(world: &mut World) {
     });
 }
 
+fn templates_menu_ui(ui: &mut egui::Ui, templates: &mut SharedTemplates) {
+    let mut reload = false;
+    egui::ComboBox::from_label("Game").selected_text(templates.game.as_str()).show_ui(ui, |ui| {
+        for game in bundled_games() {
+            reload |= ui.selectable_value(&mut templates.game, game.clone(), game).changed();
+        }
+    });
+    ui.add(egui::TextEdit::singleline(&mut templates.path).hint_text("Custom templates directory"));
+    if ui.button("Reload").clicked() {
+        reload = true;
+    }
+    if reload {
+        templates.reload();
+    }
+    if let Some(status) = &templates.status {
+        ui.colored_label(egui::Color32::RED, status);
+    }
+    ui.weak("Applies to rooms opened afterwards");
+}
+
 fn setup_egui(mut context: EguiContexts, state: ResMut<UiState>) {
     let ctx = context.ctx_mut();
 
@@ -21,6 +21,16 @@ pub fn convert_transform(xf: &CTransform4f) -> Transform {
     Transform::from_matrix(mtx.into())
 }
 
+#[inline]
+pub fn convert_to_transform4f(xf: &Transform) -> CTransform4f {
+    let [c0, c1, c2, c3] = xf.compute_matrix().to_cols_array_2d();
+    CTransform4f::from(mint::RowMatrix3x4::from([
+        [c0[0], c1[0], c2[0], c3[0]],
+        [c0[1], c1[1], c2[1], c3[1]],
+        [c0[2], c1[2], c2[2], c3[2]],
+    ]))
+}
+
 #[inline]
 pub fn convert_color(value: &CColor4f) -> Color {
     Color::rgba_linear(value.r, value.g, value.b, value.a)
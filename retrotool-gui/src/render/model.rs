@@ -20,9 +20,10 @@ use retrolib::{
 use wgpu_types::PrimitiveTopology;
 
 use crate::{
-    loaders::model::ModelAsset,
+    loaders::model::{MaterialKey, ModelAsset},
     material::{
-        ATTRIBUTE_TANGENT_1, ATTRIBUTE_TANGENT_2, ATTRIBUTE_UV_1, ATTRIBUTE_UV_2, ATTRIBUTE_UV_3,
+        CustomMaterial, ATTRIBUTE_TANGENT_1, ATTRIBUTE_TANGENT_2, ATTRIBUTE_UV_1, ATTRIBUTE_UV_2,
+        ATTRIBUTE_UV_3,
     },
     render::convert_aabb,
 };
@@ -134,6 +135,37 @@ pub fn load_model(asset: &ModelAsset, meshes: &mut Assets<Mesh>) -> Result<Built
     })
 }
 
+/// Spawns the meshes of the first LOD as children, each with `B` attached.
+pub fn spawn_model_meshes<B: Bundle + Default>(
+    builder: &mut ChildBuilder,
+    asset: &mut ModelAsset,
+    built: &BuiltModel,
+    mirrored: bool,
+    materials: &mut Assets<CustomMaterial>,
+) {
+    for idx in built.lod[0].meshes.iter() {
+        let mesh = &built.meshes[idx];
+        let material = match asset.material(
+            &MaterialKey {
+                material_idx: mesh.material_idx,
+                mesh_flags: mesh.flags,
+                mesh_mirrored: mirrored,
+            },
+            materials,
+        ) {
+            Ok(handle) => handle,
+            Err(e) => {
+                log::warn!("Failed to build material: {:?}", e);
+                continue;
+            }
+        };
+        builder.spawn((
+            MaterialMeshBundle::<CustomMaterial> { mesh: mesh.mesh.clone(), material, ..default() },
+            B::default(),
+        ));
+    }
+}
+
 #[derive(Debug, Clone, Default)]
 struct VertexBufferInfo {
     pub attributes: Vec<(MeshVertexAttribute, VertexAttributeValues)>,
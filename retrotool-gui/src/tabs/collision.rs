@@ -0,0 +1,547 @@
+use bevy::{
+    asset::LoadState,
+    core_pipeline::{clear_color::ClearColorConfig, tonemapping::Tonemapping},
+    ecs::system::{lifetimeless::*, *},
+    prelude::*,
+    render::{
+        camera::Viewport, mesh::VertexAttributeValues, primitives::Aabb,
+        render_resource::PrimitiveTopology, view::RenderLayers,
+    },
+};
+use bevy_mod_raycast::{Intersection, RaycastMesh, RaycastSource};
+use egui::Widget;
+use retrolib::format::clsn::{CCollisionMaterial, CollisionData, CollisionTree, K_FORM_CLSN};
+use zerocopy::LittleEndian;
+
+use crate::{
+    icon,
+    loaders::collision::CollisionAsset,
+    render::{camera::ModelCamera, grid::GridSettings, TemporaryLabel},
+    tabs::{property_with_value, EditorTabSystem, TabState},
+    AssetRef,
+};
+
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq)]
+enum ColorMode {
+    #[default]
+    MaterialType,
+    WorldType,
+    Orientation,
+    Behavior,
+    Filter,
+    Material,
+}
+
+impl ColorMode {
+    const ALL: [ColorMode; 6] = [
+        ColorMode::MaterialType,
+        ColorMode::WorldType,
+        ColorMode::Orientation,
+        ColorMode::Behavior,
+        ColorMode::Filter,
+        ColorMode::Material,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            ColorMode::MaterialType => "Material type",
+            ColorMode::WorldType => "World type",
+            ColorMode::Orientation => "Orientation",
+            ColorMode::Behavior => "Behavior list",
+            ColorMode::Filter => "Filter list",
+            ColorMode::Material => "Material index",
+        }
+    }
+
+    fn value(self, material_idx: u16, material: &CCollisionMaterial) -> u32 {
+        match self {
+            ColorMode::MaterialType => material.material_type,
+            ColorMode::WorldType => material.world_type,
+            ColorMode::Orientation => material.orientation,
+            ColorMode::Behavior => material.behavior_list,
+            ColorMode::Filter => material.filter_list,
+            ColorMode::Material => material_idx as u32 + 1,
+        }
+    }
+}
+
+pub struct TreeDepth {
+    pub entity: Entity,
+    pub nodes: usize,
+    pub visible: bool,
+}
+
+pub struct LoadedCollision {
+    pub mesh: Handle<Mesh>,
+    pub entity: Entity,
+    pub depths: Vec<TreeDepth>,
+}
+
+impl LoadedCollision {
+    fn entities(&self) -> impl Iterator<Item = Entity> + '_ {
+        std::iter::once(self.entity).chain(self.depths.iter().map(|d| d.entity))
+    }
+}
+
+pub struct CollisionTab {
+    pub asset_ref: AssetRef,
+    pub handle: Handle<CollisionAsset>,
+    pub loaded: Option<LoadedCollision>,
+    pub camera: ModelCamera,
+    pub show_mesh: bool,
+    pub selected_triangle: Option<usize>,
+    color_mode: ColorMode,
+    colors_dirty: bool,
+}
+
+impl Default for CollisionTab {
+    fn default() -> Self {
+        Self {
+            asset_ref: default(),
+            handle: default(),
+            loaded: None,
+            camera: default(),
+            show_mesh: true,
+            selected_triangle: None,
+            color_mode: default(),
+            colors_dirty: false,
+        }
+    }
+}
+
+impl CollisionTab {
+    pub fn new(asset_ref: AssetRef, handle: Handle<CollisionAsset>) -> Box<Self> {
+        Box::new(Self { asset_ref, handle, ..default() })
+    }
+}
+
+pub struct CollisionRaycastSet;
+
+impl EditorTabSystem for CollisionTab {
+    type LoadParam = (
+        SCommands,
+        SResMut<Assets<Mesh>>,
+        SResMut<Assets<StandardMaterial>>,
+        SRes<Assets<CollisionAsset>>,
+    );
+    type UiParam = (
+        SCommands,
+        SRes<AssetServer>,
+        SRes<Assets<CollisionAsset>>,
+        SQuery<(Entity, Read<Intersection<CollisionRaycastSet>>)>,
+    );
+
+    fn load(&mut self, query: SystemParamItem<Self::LoadParam>) {
+        let (mut commands, mut meshes, mut materials, collision_assets) = query;
+        let Some(asset) = collision_assets.get(&self.handle) else {
+            return;
+        };
+        if let Some(loaded) = &self.loaded {
+            if self.colors_dirty {
+                if let Some(mesh) = meshes.get_mut(&loaded.mesh) {
+                    mesh.insert_attribute(
+                        Mesh::ATTRIBUTE_COLOR,
+                        triangle_colors(&asset.inner, self.color_mode, self.selected_triangle),
+                    );
+                }
+                self.colors_dirty = false;
+            }
+            for entity in loaded.entities() {
+                if let Some(mut commands) = commands.get_entity(entity) {
+                    commands.insert(Visibility::Hidden);
+                }
+            }
+            return;
+        }
+
+        let data = &asset.inner;
+        let mut positions = Vec::with_capacity(data.triangles.len() * 3);
+        let mut normals = Vec::with_capacity(data.triangles.len() * 3);
+        let mut min = Vec3::splat(f32::MAX);
+        let mut max = Vec3::splat(f32::MIN);
+        for idx in 0..data.triangles.len() {
+            let v = triangle_positions(data, idx);
+            let normal = (v[1] - v[0]).cross(v[2] - v[0]).normalize_or_zero();
+            for p in v {
+                min = min.min(p);
+                max = max.max(p);
+                positions.push(p.to_array());
+                normals.push(normal.to_array());
+            }
+        }
+        let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+        mesh.insert_attribute(
+            Mesh::ATTRIBUTE_COLOR,
+            triangle_colors(data, self.color_mode, self.selected_triangle),
+        );
+        let mesh = meshes.add(mesh);
+        let entity = commands
+            .spawn((
+                PbrBundle {
+                    mesh: mesh.clone(),
+                    material: materials.add(StandardMaterial {
+                        base_color: Color::WHITE,
+                        unlit: true,
+                        double_sided: true,
+                        cull_mode: None,
+                        ..default()
+                    }),
+                    visibility: Visibility::Hidden,
+                    ..default()
+                },
+                RaycastMesh::<CollisionRaycastSet>::default(),
+            ))
+            .id();
+
+        let mut depths = vec![];
+        for (depth, lines) in
+            data.tree().map(tree_lines).unwrap_or_default().into_iter().enumerate()
+        {
+            let nodes = lines.len() / 24;
+            let normals = vec![[0.0, 1.0, 0.0]; lines.len()];
+            let mut mesh = Mesh::new(PrimitiveTopology::LineList);
+            mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, lines);
+            mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+            let entity = commands
+                .spawn(PbrBundle {
+                    mesh: meshes.add(mesh),
+                    material: materials.add(StandardMaterial {
+                        base_color: depth_color(depth),
+                        unlit: true,
+                        ..default()
+                    }),
+                    visibility: Visibility::Hidden,
+                    ..default()
+                })
+                .id();
+            depths.push(TreeDepth { entity, nodes, visible: false });
+        }
+
+        self.loaded = Some(LoadedCollision { mesh, entity, depths });
+        if !data.triangles.is_empty() {
+            self.camera.init(&Aabb::from_min_max(min, max), true);
+        }
+    }
+
+    fn close(&mut self, query: SystemParamItem<Self::LoadParam>) -> bool {
+        let (mut commands, _, _, _) = query;
+        if let Some(loaded) = &self.loaded {
+            for entity in loaded.entities() {
+                if let Some(commands) = commands.get_entity(entity) {
+                    commands.despawn_recursive();
+                }
+            }
+        }
+        true
+    }
+
+    fn ui(
+        &mut self,
+        ui: &mut egui::Ui,
+        query: SystemParamItem<Self::UiParam>,
+        state: &mut TabState,
+    ) {
+        let scale = ui.ctx().pixels_per_point();
+        let rect = ui.available_rect_before_wrap();
+        let left_top = rect.left_top().to_vec2() * scale;
+        let size = rect.size() * scale;
+        let viewport = Viewport {
+            physical_position: UVec2::new(left_top.x as u32, left_top.y as u32),
+            physical_size: UVec2::new(size.x as u32, size.y as u32),
+            depth: 0.0..1.0,
+        };
+        let response =
+            ui.interact(rect, ui.make_persistent_id("background"), egui::Sense::click_and_drag());
+        self.camera.update(&rect, &response, ui.input(|i| i.scroll_delta));
+
+        let (mut commands, server, collision_assets, intersection_query) = query;
+        let (Some(loaded), Some(asset)) = (&mut self.loaded, collision_assets.get(&self.handle))
+        else {
+            ui.centered_and_justified(|ui| {
+                match server.get_load_state(&self.handle) {
+                    LoadState::Failed => egui::Label::new(
+                        egui::RichText::from("Loading failed").heading().color(egui::Color32::RED),
+                    )
+                    .ui(ui),
+                    _ => egui::Spinner::new().size(50.0).ui(ui),
+                };
+            });
+            return;
+        };
+        let data = &asset.inner;
+
+        if response.clicked() {
+            let hit = intersection_query
+                .iter()
+                .find(|(entity, _)| *entity == loaded.entity)
+                .and_then(|(_, intersection)| intersection.position().copied());
+            let selected = hit.and_then(|pos| triangle_at(data, pos));
+            if selected != self.selected_triangle {
+                self.selected_triangle = selected;
+                self.colors_dirty = true;
+            }
+        }
+
+        let camera =
+            Camera { viewport: Some(viewport), order: state.render_layer as isize, ..default() };
+        let mut entity = commands.spawn((
+            Camera3dBundle {
+                camera_3d: Camera3d { clear_color: ClearColorConfig::None, ..default() },
+                camera: camera.clone(),
+                tonemapping: Tonemapping::TonyMcMapface,
+                transform: self.camera.transform,
+                ..default()
+            },
+            GridSettings {
+                clear_color: if state.render_layer == 0 {
+                    ClearColorConfig::Default
+                } else {
+                    ClearColorConfig::None
+                },
+            },
+            RenderLayers::layer(state.render_layer),
+            TemporaryLabel,
+        ));
+        if response.hovered() {
+            if let Some(pos) = ui.input(|i| {
+                i.pointer.hover_pos().map(|pos| Vec2::new(pos.x, i.screen_rect.height() - pos.y))
+            }) {
+                entity.insert(RaycastSource::<CollisionRaycastSet>::new_screenspace(
+                    pos,
+                    &camera,
+                    &GlobalTransform::default(),
+                ));
+            }
+        }
+
+        egui::Frame::group(ui.style()).show(ui, |ui| {
+            egui::ScrollArea::vertical().max_height(rect.height() * 0.25).show(ui, |ui| {
+                ui.label(format!(
+                    "{} ({} tree)",
+                    data.kind,
+                    if data.kind == K_FORM_CLSN { "AABB" } else { "OBB" }
+                ));
+                ui.label(format!(
+                    "Vertices: {} | Triangles: {} | Materials: {} | Nodes: {}",
+                    data.vertices.len(),
+                    data.triangles.len(),
+                    data.materials.len(),
+                    data.tree().map_or(0, |t| t.len())
+                ));
+                ui.checkbox(&mut self.show_mesh, "Show mesh");
+                egui::ComboBox::from_label("Colour by")
+                    .selected_text(self.color_mode.name())
+                    .show_ui(ui, |ui| {
+                        for mode in ColorMode::ALL {
+                            let response =
+                                ui.selectable_value(&mut self.color_mode, mode, mode.name());
+                            if response.changed() {
+                                self.colors_dirty = true;
+                            }
+                        }
+                    });
+                ui.collapsing(format!("Tree depths: {}", loaded.depths.len()), |ui| {
+                    ui.horizontal(|ui| {
+                        if ui.button("Show all").clicked() {
+                            loaded.depths.iter_mut().for_each(|d| d.visible = true);
+                        }
+                        if ui.button("Hide all").clicked() {
+                            loaded.depths.iter_mut().for_each(|d| d.visible = false);
+                        }
+                    });
+                    for (idx, depth) in loaded.depths.iter_mut().enumerate() {
+                        let color = depth_color(idx).as_rgba_u8();
+                        ui.horizontal(|ui| {
+                            ui.colored_label(
+                                egui::Color32::from_rgb(color[0], color[1], color[2]),
+                                format!("{}", icon::MESH_CUBE),
+                            );
+                            ui.checkbox(
+                                &mut depth.visible,
+                                format!("Depth {idx} ({} nodes)", depth.nodes),
+                            );
+                        });
+                    }
+                });
+            });
+        });
+
+        if let Some(tri_idx) = self.selected_triangle {
+            egui::Frame::group(ui.style()).fill(egui::Color32::from_black_alpha(200)).show(
+                ui,
+                |ui| {
+                    if ui
+                        .small_button(format!("{}", icon::PANEL_CLOSE))
+                        .on_hover_text_at_pointer("Close")
+                        .clicked()
+                    {
+                        self.selected_triangle = None;
+                        self.colors_dirty = true;
+                    }
+                    triangle_ui(ui, data, tri_idx);
+                },
+            );
+        }
+
+        if let Some(mut commands) = commands.get_entity(loaded.entity) {
+            commands.insert((
+                if self.show_mesh { Visibility::Visible } else { Visibility::Hidden },
+                RenderLayers::layer(state.render_layer),
+            ));
+        }
+        for depth in &loaded.depths {
+            if let Some(mut commands) = commands.get_entity(depth.entity) {
+                commands.insert((
+                    if depth.visible { Visibility::Visible } else { Visibility::Hidden },
+                    RenderLayers::layer(state.render_layer),
+                ));
+            }
+        }
+
+        state.render_layer += 1;
+    }
+
+    fn title(&self) -> egui::WidgetText {
+        format!("{} {} {}", icon::MOD_PHYSICS, self.asset_ref.kind, self.asset_ref.id).into()
+    }
+
+    fn id(&self) -> String { format!("{} {}", self.asset_ref.kind, self.asset_ref.id) }
+
+    fn clear_background(&self) -> bool { false }
+
+    fn asset(&self) -> Option<AssetRef> { Some(self.asset_ref) }
+}
+
+fn triangle_ui(ui: &mut egui::Ui, data: &CollisionData<LittleEndian>, tri_idx: usize) {
+    let tri = &data.triangles[tri_idx];
+    let material = &data.materials[tri.material as usize];
+    property_with_value(ui, "Triangle", format!("{tri_idx}"));
+    property_with_value(ui, "Vertices", format!("{} {} {}", tri.idx1, tri.idx2, tri.idx3));
+    property_with_value(ui, "Unk", format!("{:#06X}", tri.unk));
+    property_with_value(ui, "Material", format!("{}", tri.material));
+    for (name, value) in [
+        ("Orientation", material.orientation),
+        ("Material type", material.material_type),
+        ("World type", material.world_type),
+        ("Behavior list", material.behavior_list),
+        ("Filter list", material.filter_list),
+    ] {
+        property_with_value(ui, name, format!("{value:#010X} ({value:032b})"));
+    }
+}
+
+fn triangle_positions(data: &CollisionData<LittleEndian>, tri_idx: usize) -> [Vec3; 3] {
+    data.triangle_vertices(&data.triangles[tri_idx]).map(|v| Vec3::from(mint::Vector3::from(v)))
+}
+
+/// Stable, distinct colour for a material value. Zero is drawn grey.
+fn value_color(value: u32) -> Color {
+    if value == 0 {
+        return Color::GRAY;
+    }
+    let hash = value.wrapping_mul(0x9E3779B1);
+    Color::hsl((hash >> 16) as f32 / 65536.0 * 360.0, 0.65, 0.55)
+}
+
+fn depth_color(depth: usize) -> Color { Color::hsl((depth * 47 % 360) as f32, 0.8, 0.6) }
+
+/// Per-vertex colours for the triangle mesh, with simple directional shading baked in.
+fn triangle_colors(
+    data: &CollisionData<LittleEndian>,
+    mode: ColorMode,
+    selected: Option<usize>,
+) -> VertexAttributeValues {
+    let light = Vec3::new(0.3, 1.0, 0.5).normalize();
+    let mut colors = Vec::with_capacity(data.triangles.len() * 3);
+    for (idx, tri) in data.triangles.iter().enumerate() {
+        let color = if selected == Some(idx) {
+            Color::YELLOW
+        } else {
+            value_color(mode.value(tri.material, &data.materials[tri.material as usize]))
+        };
+        let v = triangle_positions(data, idx);
+        let normal = (v[1] - v[0]).cross(v[2] - v[0]).normalize_or_zero();
+        let shade = 0.6 + 0.4 * normal.dot(light).abs();
+        let [r, g, b, a] = color.as_linear_rgba_f32();
+        colors.extend([[r * shade, g * shade, b * shade, a]; 3]);
+    }
+    VertexAttributeValues::Float32x4(colors)
+}
+
+/// Line list positions for each depth of the collision tree.
+fn tree_lines(tree: &CollisionTree) -> Vec<Vec<[f32; 3]>> {
+    let corners: Vec<[Vec3; 8]> = match tree {
+        CollisionTree::AABox(nodes) => nodes
+            .iter()
+            .map(|node| {
+                let min = Vec3::from(mint::Vector3::from(node.bounds.min));
+                let max = Vec3::from(mint::Vector3::from(node.bounds.max));
+                box_corners(|x, y, z| {
+                    Vec3::new(
+                        if x { max.x } else { min.x },
+                        if y { max.y } else { min.y },
+                        if z { max.z } else { min.z },
+                    )
+                })
+            })
+            .collect(),
+        CollisionTree::OBBox(nodes) => nodes
+            .iter()
+            .map(|node| {
+                let mtx: Mat4 = mint::ColumnMatrix4::from(node.bounds.xf).into();
+                let extents = Vec3::from(mint::Vector3::from(node.bounds.extents));
+                box_corners(|x, y, z| {
+                    let sign = |b: bool| if b { 1.0 } else { -1.0 };
+                    mtx.transform_point3(extents * Vec3::new(sign(x), sign(y), sign(z)))
+                })
+            })
+            .collect(),
+    };
+    let mut out: Vec<Vec<[f32; 3]>> = vec![];
+    for (depth, corners) in tree.depths().into_iter().zip(corners) {
+        let depth = depth as usize;
+        if out.len() <= depth {
+            out.resize_with(depth + 1, Vec::new);
+        }
+        for i in 0..8 {
+            for bit in [1, 2, 4] {
+                if i & bit == 0 {
+                    out[depth].push(corners[i].to_array());
+                    out[depth].push(corners[i | bit].to_array());
+                }
+            }
+        }
+    }
+    out
+}
+
+fn box_corners(f: impl Fn(bool, bool, bool) -> Vec3) -> [Vec3; 8] {
+    std::array::from_fn(|i| f(i & 1 != 0, i & 2 != 0, i & 4 != 0))
+}
+
+/// Finds the triangle containing a ray cast hit on the collision mesh.
+fn triangle_at(data: &CollisionData<LittleEndian>, pos: Vec3) -> Option<usize> {
+    const EPSILON: f32 = 1.0e-3;
+    let mut closest: Option<(usize, f32)> = None;
+    for idx in 0..data.triangles.len() {
+        let [a, b, c] = triangle_positions(data, idx);
+        let normal = (b - a).cross(c - a);
+        let area = normal.length_squared();
+        if area <= f32::EPSILON {
+            continue;
+        }
+        // Barycentric coordinates of the hit projected onto the triangle's plane
+        let u = (c - b).cross(pos - b).dot(normal) / area;
+        let v = (a - c).cross(pos - c).dot(normal) / area;
+        if u < -EPSILON || v < -EPSILON || u + v > 1.0 + EPSILON {
+            continue;
+        }
+        let dist = (pos - a).dot(normal).abs() / area.sqrt();
+        if closest.map_or(true, |(_, best)| dist < best) {
+            closest = Some((idx, dist));
+        }
+    }
+    closest.map(|(idx, _)| idx)
+}
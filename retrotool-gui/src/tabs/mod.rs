@@ -1,3 +1,4 @@
+pub mod collision;
 pub mod lightprobe;
 pub mod modcon;
 pub mod model;
@@ -1,3 +1,8 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+};
+
 use bevy::{
     asset::LoadState,
     core_pipeline::{clear_color::ClearColorConfig, tonemapping::Tonemapping},
@@ -8,19 +13,18 @@ use bevy::{
 };
 use bevy_mod_raycast::{Intersection, RaycastMesh, RaycastSource};
 use egui::{Sense, Widget};
-use retrolib::format::SumBy;
+use retrolib::format::{mcon::SModConVisualData, CTransform4f, MaybeParsed, SumBy};
 use uuid::Uuid;
 
 use crate::{
     icon,
-    loaders::{
-        modcon::ModConAsset,
-        model::{MaterialKey, ModelAsset},
-        texture::TextureAsset,
-    },
+    loaders::{modcon::ModConAsset, model::ModelAsset, texture::TextureAsset},
     material::CustomMaterial,
     render::{
-        camera::ModelCamera, convert_transform, grid::GridSettings, model::load_model,
+        camera::ModelCamera,
+        convert_to_transform4f, convert_transform,
+        grid::GridSettings,
+        model::{load_model, spawn_model_meshes},
         TemporaryLabel,
     },
     tabs::{model::ModelTab, EditorTabSystem, TabState},
@@ -36,6 +40,8 @@ pub struct ModelInfo {
     pub handle: Handle<ModelAsset>,
     pub loaded: Vec<LoadedModel>,
     pub transforms: Vec<Transform>,
+    /// Instance index in the visual data for each transform
+    pub instances: Vec<usize>,
     pub aabb: Aabb,
 }
 
@@ -49,6 +55,13 @@ pub struct ModConTab {
     pub specular_map: Handle<Image>,
     pub env_light: bool,
     pub selected_model: Option<AssetRef>,
+    pub data: Option<SModConVisualData>,
+    pub selected_instance: Option<usize>,
+    pub rebuild: bool,
+    pub camera_init: bool,
+    pub add_model_idx: usize,
+    pub save_path: String,
+    pub status: Option<String>,
 }
 
 impl Default for ModConTab {
@@ -63,13 +76,171 @@ impl Default for ModConTab {
             specular_map: default(),
             env_light: true,
             selected_model: None,
+            data: None,
+            selected_instance: None,
+            rebuild: false,
+            camera_init: false,
+            add_model_idx: 0,
+            save_path: String::new(),
+            status: None,
         }
     }
 }
 
 impl ModConTab {
     pub fn new(asset_ref: AssetRef, handle: Handle<ModConAsset>) -> Box<Self> {
-        Box::new(Self { asset_ref, handle, ..default() })
+        let save_path = format!("{}.{}", asset_ref.id, asset_ref.kind);
+        Box::new(Self { asset_ref, handle, save_path, ..default() })
+    }
+
+    /// Entity and transform slot for a visual data instance.
+    fn find_instance(&mut self, instance: usize) -> Option<(Entity, &mut Transform)> {
+        self.models.iter_mut().find_map(|info| {
+            let pos = info.instances.iter().position(|&i| i == instance)?;
+            let entity = info.loaded.get(pos)?.entity;
+            Some((entity, &mut info.transforms[pos]))
+        })
+    }
+
+    fn edit_ui(&mut self, ui: &mut egui::Ui, commands: &mut Commands) {
+        let Some(data) = &mut self.data else {
+            return;
+        };
+        ui.separator();
+        // Adding and removing instances needs all per-instance data decoded
+        let editable = data.check_instances().map_err(|e| format!("{e}"));
+        if let Err(e) = &editable {
+            ui.colored_label(egui::Color32::YELLOW, e);
+        }
+        ui.horizontal(|ui| {
+            let selected_text =
+                data.models.get(self.add_model_idx).map(|id| id.to_string()).unwrap_or_default();
+            egui::ComboBox::from_id_source("add_model").selected_text(selected_text).show_ui(
+                ui,
+                |ui| {
+                    for (idx, id) in data.models.iter().enumerate() {
+                        ui.selectable_value(&mut self.add_model_idx, idx, id.to_string());
+                    }
+                },
+            );
+            if ui
+                .add_enabled(
+                    editable.is_ok(),
+                    egui::Button::new(format!("{} Add instance", icon::ADD)),
+                )
+                .clicked()
+            {
+                match data.add_instance(self.add_model_idx, CTransform4f::default()) {
+                    Ok(idx) => {
+                        self.selected_instance = Some(idx);
+                        self.rebuild = true;
+                    }
+                    Err(e) => self.status = Some(format!("{e:?}")),
+                }
+            }
+        });
+
+        let Some(instance) = self.selected_instance.filter(|&i| i < data.transforms.len()) else {
+            return;
+        };
+        let Some(model_idx) = data.shorts_1.get(instance).map(|&idx| idx as usize) else {
+            return;
+        };
+        let model_name =
+            data.models.get(model_idx).map(|id| id.to_string()).unwrap_or_else(|| "?".into());
+        ui.label(format!("Selected instance {instance}: {model_name}"));
+        let mut transform = convert_transform(&data.transforms[instance]);
+        let was_mirrored = transform.scale.x.is_sign_negative()
+            ^ transform.scale.y.is_sign_negative()
+            ^ transform.scale.z.is_sign_negative();
+        let mut changed = false;
+        egui::Grid::new("instance_transform").num_columns(4).show(ui, |ui| {
+            ui.label("Translation");
+            for v in transform.translation.as_mut().iter_mut() {
+                changed |= ui.add(egui::DragValue::new(v).speed(0.1)).changed();
+            }
+            ui.end_row();
+            ui.label("Rotation");
+            let (x, y, z) = transform.rotation.to_euler(EulerRot::XYZ);
+            let mut euler = [x, y, z].map(f32::to_degrees);
+            let mut rotated = false;
+            for v in &mut euler {
+                rotated |= ui.add(egui::DragValue::new(v).speed(1.0).suffix("°")).changed();
+            }
+            if rotated {
+                let [x, y, z] = euler.map(f32::to_radians);
+                transform.rotation = Quat::from_euler(EulerRot::XYZ, x, y, z);
+                changed = true;
+            }
+            ui.end_row();
+            ui.label("Scale");
+            for v in transform.scale.as_mut().iter_mut() {
+                changed |= ui.add(egui::DragValue::new(v).speed(0.01)).changed();
+            }
+            ui.end_row();
+        });
+        ui.horizontal(|ui| {
+            if ui
+                .add_enabled(
+                    editable.is_ok(),
+                    egui::Button::new(format!("{} Duplicate", icon::DUPLICATE)),
+                )
+                .clicked()
+            {
+                let xf = data.transforms[instance];
+                match data.add_instance(model_idx, xf) {
+                    Ok(idx) => {
+                        self.selected_instance = Some(idx);
+                        self.rebuild = true;
+                    }
+                    Err(e) => self.status = Some(format!("{e:?}")),
+                }
+            }
+            if ui
+                .add_enabled(editable.is_ok(), egui::Button::new(format!("{} Delete", icon::TRASH)))
+                .clicked()
+            {
+                match data.remove_instance(instance) {
+                    Ok(()) => {
+                        self.selected_instance = None;
+                        self.rebuild = true;
+                    }
+                    Err(e) => self.status = Some(format!("{e:?}")),
+                }
+            }
+        });
+        if !changed || self.rebuild {
+            return;
+        }
+
+        if let Err(e) = data.set_transform(instance, convert_to_transform4f(&transform)) {
+            self.status = Some(format!("{e:?}"));
+            return;
+        }
+        let is_mirrored = transform.scale.x.is_sign_negative()
+            ^ transform.scale.y.is_sign_negative()
+            ^ transform.scale.z.is_sign_negative();
+        if is_mirrored != was_mirrored {
+            // Materials depend on winding order
+            self.rebuild = true;
+        } else if let Some((entity, slot)) = self.find_instance(instance) {
+            *slot = transform;
+            if let Some(mut commands) = commands.get_entity(entity) {
+                commands.insert(transform);
+            }
+        }
+    }
+
+    fn save(&self, mod_con: &ModConAsset) -> anyhow::Result<()> {
+        let mut data = mod_con.inner.clone();
+        data.visual_data = self.data.clone();
+        let mut file = BufWriter::new(File::create(&self.save_path)?);
+        data.write(&mut file)?;
+        if let Some(footer) = &mod_con.footer {
+            footer.write(&mut file)?;
+        }
+        file.flush()?;
+        Ok(())
     }
 
     fn get_load_state(
@@ -108,6 +279,7 @@ pub struct ModConRaycastSet;
 pub struct ModelLabel {
     pub asset_ref: AssetRef,
     pub tab_id: Uuid,
+    pub instance: usize,
 }
 
 impl EditorTabSystem for ModConTab {
@@ -142,9 +314,21 @@ impl EditorTabSystem for ModConTab {
             mod_con_assets,
         ) = query;
 
+        if self.rebuild {
+            for model in self.models.iter().flat_map(|l| &l.loaded) {
+                if let Some(commands) = commands.get_entity(model.entity) {
+                    commands.despawn_recursive();
+                }
+            }
+            self.models.clear();
+            self.rebuild = false;
+        }
         if self.models.is_empty() {
             if let Some(mod_con) = mod_con_assets.get(&self.handle) {
-                let data = match &mod_con.inner.visual_data {
+                if self.data.is_none() {
+                    self.data = mod_con.inner.visual_data.clone();
+                }
+                let data = match &self.data {
                     Some(value) => value,
                     None => return,
                 };
@@ -153,13 +337,17 @@ impl EditorTabSystem for ModConTab {
                         handle: handle.clone(),
                         loaded: vec![],
                         transforms: vec![],
+                        instances: vec![],
                         aabb: Default::default(),
                     });
                 }
-                for (idx, &model_idx) in data.shorts_1.iter().enumerate() {
-                    self.models[model_idx as usize]
-                        .transforms
-                        .push(convert_transform(&data.transforms[idx]));
+                for (instance, (model_idx, xf)) in data.transform_models().enumerate() {
+                    let Some(info) = self.models.get_mut(model_idx) else {
+                        log::warn!("Instance {instance}: model index {model_idx} out of range");
+                        continue;
+                    };
+                    info.transforms.push(convert_transform(xf));
+                    info.instances.push(instance);
                 }
                 self.models.retain(|info| !info.transforms.is_empty());
             }
@@ -195,41 +383,23 @@ impl EditorTabSystem for ModConTab {
                     continue;
                 }
             };
-            for &transform in &info.transforms {
+            for (&transform, &instance) in info.transforms.iter().zip(&info.instances) {
                 let is_mirrored = transform.scale.x.is_sign_negative()
                     ^ transform.scale.y.is_sign_negative()
                     ^ transform.scale.z.is_sign_negative();
                 let entity = commands
                     .spawn((
                         SpatialBundle { transform, visibility: Visibility::Hidden, ..default() },
-                        ModelLabel { asset_ref: asset.asset_ref, tab_id: self.tab_id },
+                        ModelLabel { asset_ref: asset.asset_ref, tab_id: self.tab_id, instance },
                     ))
                     .with_children(|builder| {
-                        for idx in built.lod[0].meshes.iter() {
-                            let mesh = &built.meshes[idx];
-                            let material = match asset.material(
-                                &MaterialKey {
-                                    material_idx: mesh.material_idx,
-                                    mesh_flags: mesh.flags,
-                                    mesh_mirrored: is_mirrored,
-                                },
-                                &mut materials,
-                            ) {
-                                Ok(handle) => handle,
-                                Err(e) => {
-                                    log::warn!("Failed to build material: {:?}", e);
-                                    continue;
-                                }
-                            };
-                            builder.spawn((
-                                MaterialMeshBundle::<CustomMaterial> {
-                                    mesh: mesh.mesh.clone(),
-                                    material,
-                                    ..default()
-                                },
-                                RaycastMesh::<ModConRaycastSet>::default(),
-                            ));
-                        }
+                        spawn_model_meshes::<RaycastMesh<ModConRaycastSet>>(
+                            builder,
+                            asset,
+                            &built,
+                            is_mirrored,
+                            &mut materials,
+                        );
                     })
                     .id();
                 info.loaded.push(LoadedModel { entity, visible: true });
@@ -238,7 +408,7 @@ impl EditorTabSystem for ModConTab {
             loaded = true;
         }
 
-        if loaded && self.models.iter().all(|m| !m.loaded.is_empty()) {
+        if loaded && !self.camera_init && self.models.iter().all(|m| !m.loaded.is_empty()) {
             let mut min = Vec3A::splat(f32::MAX);
             let mut max = Vec3A::splat(f32::MIN);
             for info in &self.models {
@@ -251,6 +421,7 @@ impl EditorTabSystem for ModConTab {
             }
             let aabb = Aabb::from_min_max(min.into(), max.into());
             self.camera.init(&aabb, true);
+            self.camera_init = true;
         }
 
         // FIXME
@@ -290,7 +461,10 @@ impl EditorTabSystem for ModConTab {
         self.camera.update(&rect, &response, ui.input(|i| i.scroll_delta));
 
         let (mut commands, server, models, mod_con_assets, intersection_query, model_query) = query;
-        if self.models.is_empty() || !self.models.iter().all(|m| !m.loaded.is_empty()) {
+        let no_instances = self.data.as_ref().map_or(false, |d| d.transforms.is_empty());
+        if !no_instances
+            && (self.models.is_empty() || !self.models.iter().all(|m| !m.loaded.is_empty()))
+        {
             ui.centered_and_justified(|ui| {
                 match self.get_load_state(&server, &mod_con_assets, &models) {
                     LoadState::Failed => egui::Label::new(
@@ -303,20 +477,68 @@ impl EditorTabSystem for ModConTab {
             return;
         }
 
+        let mut hovered_instance = None;
         if let Some(parent) = intersection_query.iter().next() {
             let (label, _) = model_query.get(parent.get()).unwrap();
             if label.tab_id == self.tab_id {
                 self.selected_model = Some(label.asset_ref);
+                hovered_instance = Some(label.instance);
             }
         }
+        if response.clicked() {
+            self.selected_instance = hovered_instance;
+        }
         egui::Frame::group(ui.style()).show(ui, |ui| {
             egui::ScrollArea::vertical().max_height(rect.height() * 0.25).show(ui, |ui| {
                 ui.checkbox(&mut self.env_light, "Environment lighting");
                 ui.label(format!("Models: {}", self.models.len()));
                 ui.label(format!("Instances: {}", self.models.sum_by(|m| m.loaded.len())));
+                if let Some(mod_con) = mod_con_assets.get(&self.handle) {
+                    let inner = &mod_con.inner;
+                    if let Some(data) = &inner.visual_data {
+                        ui.label(format!("Object transforms: {}", data.object_transforms.len()));
+                        if !data.unparsed.is_empty() {
+                            ui.label(format!(
+                                "MCVD tail: {} bytes (not decoded)",
+                                data.unparsed.len()
+                            ));
+                        }
+                    }
+                    match &inner.header {
+                        Some(MaybeParsed::Parsed(header)) => {
+                            ui.label(format!("Header unk: {:#X}", header.unk));
+                        }
+                        Some(MaybeParsed::Raw(data)) => {
+                            ui.label(format!("MCHD: {} bytes (not decoded)", data.len()));
+                        }
+                        None => {}
+                    }
+                    if let Some(data) = &inner.connection_data {
+                        ui.label(format!("MCCD: {} bytes (not decoded)", data.len()));
+                    }
+                    if !inner.peek.is_empty() {
+                        ui.label(format!("PEEK: {} bytes (not decoded)", inner.peek.len()));
+                    }
+                }
                 if let Some(selected) = &self.selected_model {
                     ui.label(format!("Hovering: {}", selected.id));
                 }
+                self.edit_ui(ui, &mut commands);
+                ui.separator();
+                ui.horizontal(|ui| {
+                    ui.text_edit_singleline(&mut self.save_path);
+                    if ui.button(format!("{} Save", icon::DISK)).clicked() {
+                        if let Some(mod_con) = mod_con_assets.get(&self.handle) {
+                            self.status = Some(match self.save(mod_con) {
+                                Ok(()) => format!("Saved {}", self.save_path),
+                                Err(e) => format!("Failed to save: {e:?}"),
+                            });
+                        }
+                    }
+                });
+                if let Some(status) = &self.status {
+                    ui.label(status);
+                }
             });
         });
 
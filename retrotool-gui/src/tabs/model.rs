@@ -11,6 +11,7 @@ use bevy_egui::EguiUserTextures;
 use egui::Widget;
 use retrolib::format::{
     cmdl::{CMaterialCache, CMaterialDataInner, CMaterialTextureTokenData},
+    mtrl::K_FORM_MTRL,
     txtr::K_FORM_TXTR,
 };
 use uuid::Uuid;
@@ -18,6 +19,7 @@ use uuid::Uuid;
 use crate::{
     icon,
     loaders::{
+        material::MaterialAsset,
         model::{MaterialKey, ModelAsset},
         texture::TextureAsset,
     },
@@ -62,6 +64,7 @@ pub struct ModelTab {
     pub diffuse_map: Handle<Image>,
     pub specular_map: Handle<Image>,
     pub egui_textures: HashMap<Uuid, UiTexture>,
+    pub shaders: HashMap<Uuid, Handle<MaterialAsset>>,
 }
 
 impl ModelTab {
@@ -94,7 +97,8 @@ impl EditorTabSystem for ModelTab {
         SResMut<AssetServer>,
         SResMut<EguiUserTextures>,
     );
-    type UiParam = (SCommands, SRes<AssetServer>, SRes<Assets<ModelAsset>>);
+    type UiParam =
+        (SCommands, SRes<AssetServer>, SRes<Assets<ModelAsset>>, SRes<Assets<MaterialAsset>>);
 
     fn load(&mut self, query: SystemParamItem<Self::LoadParam>) {
         let (
@@ -217,7 +221,7 @@ impl EditorTabSystem for ModelTab {
             ui.interact(rect, ui.make_persistent_id("background"), egui::Sense::click_and_drag());
         self.camera.update(&rect, &response, ui.input(|i| i.scroll_delta));
 
-        let (mut commands, server, models) = query;
+        let (mut commands, server, models, shaders) = query;
         if let Some(loaded) = &mut self.loaded {
             commands.spawn((
                 Camera3dBundle {
@@ -325,13 +329,20 @@ impl EditorTabSystem for ModelTab {
                                     {
                                         self.selected_material = None;
                                     }
+                                    let mat = &loaded.materials[material_idx];
+                                    let path = format!("{}.{}", mat.shader_id, K_FORM_MTRL);
+                                    let shader = self
+                                        .shaders
+                                        .entry(mat.shader_id)
+                                        .or_insert_with(|| server.load(path));
                                     material_ui(
                                         ui,
-                                        &loaded.materials[material_idx],
+                                        mat,
                                         &self.egui_textures,
                                         state,
                                         server.as_ref(),
                                     );
+                                    shader_ui(ui, shader, &shaders, server.as_ref());
                                 });
                         },
                     );
@@ -393,6 +404,27 @@ fn texture_ui(
     }
 }
 
+fn shader_ui(
+    ui: &mut egui::Ui,
+    handle: &Handle<MaterialAsset>,
+    shaders: &Assets<MaterialAsset>,
+    server: &AssetServer,
+) {
+    let Some(asset) = shaders.get(handle) else {
+        let text = match server.get_load_state(handle) {
+            LoadState::Failed => "Shader: failed to load",
+            _ => "Shader: loading...",
+        };
+        ui.label(text);
+        return;
+    };
+    property_with_value(
+        ui,
+        "Shader",
+        format!("{} bytes (not decoded)", asset.inner.decompressed.len()),
+    );
+}
+
 fn material_ui(
     ui: &mut egui::Ui,
     mat: &CMaterialCache,
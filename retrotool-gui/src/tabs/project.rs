@@ -7,6 +7,7 @@ use bevy::{
 use bevy_egui::EguiUserTextures;
 use egui::{text::LayoutJob, Color32, TextFormat, Widget};
 use retrolib::format::{
+    clsn::{K_FORM_CLSN, K_FORM_DCLN},
     cmdl::{K_FORM_CMDL, K_FORM_SMDL, K_FORM_WMDL},
     ltpb::K_FORM_LTPB,
     mcon::K_FORM_MCON,
@@ -18,8 +19,8 @@ use crate::{
     icon,
     loaders::{package::PackageDirectory, texture::TextureAsset},
     tabs::{
-        lightprobe::LightProbeTab, modcon::ModConTab, model::ModelTab, room::RoomTab,
-        texture::TextureTab, EditorTabSystem, TabState,
+        collision::CollisionTab, lightprobe::LightProbeTab, modcon::ModConTab, model::ModelTab,
+        room::RoomTab, texture::TextureTab, EditorTabSystem, TabState,
     },
     AssetRef,
 };
@@ -177,6 +178,7 @@ impl EditorTabSystem for ProjectTab {
                                 K_FORM_FMV0 => icon::FILE_MOVIE,
                                 K_FORM_ROOM | K_FORM_MCON => icon::SCENE_DATA,
                                 K_FORM_LTPB => icon::LIGHTPROBE_GRID,
+                                K_FORM_CLSN | K_FORM_DCLN => icon::MOD_PHYSICS,
                                 _ => icon::FILE,
                             },
                             entry.kind,
@@ -224,7 +226,11 @@ impl EditorTabSystem for ProjectTab {
                                 state.open_tab(LightProbeTab::new(asset_ref, server.load(path)));
                             }
                             K_FORM_ROOM => {
-                                state.open_tab(RoomTab::new(asset_ref, server.load(path)));
+                                let handle = server.load(path);
+                                state.open_tab(RoomTab::new(asset_ref, handle, &package.path));
+                            }
+                            K_FORM_CLSN | K_FORM_DCLN => {
+                                state.open_tab(CollisionTab::new(asset_ref, server.load(path)));
                             }
                             _ => {}
                         }
@@ -1,65 +1,569 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
 use bevy::{
+    asset::LoadState,
     core_pipeline::{clear_color::ClearColorConfig, tonemapping::Tonemapping},
     ecs::system::{lifetimeless::*, *},
+    math::Vec3A,
     prelude::*,
-    render::{camera::Viewport, view::RenderLayers},
+    render::{
+        camera::Viewport, mesh::Indices, primitives::Aabb, render_resource::PrimitiveTopology,
+        view::RenderLayers,
+    },
 };
-use bevy_mod_raycast::{Intersection, RaycastSource};
-use egui::Sense;
-use retrolib::format::room::{ConstructedProperty, ConstructedPropertyValue};
+use bevy_mod_raycast::{Intersection, RaycastMesh, RaycastSource};
+use egui::{Align2, Color32, FontId, Pos2, Rect, Sense, Stroke};
+use retrolib::format::{
+    cmdl::{K_FORM_CMDL, K_FORM_WMDL},
+    foot::Footer,
+    mcon::K_FORM_MCON,
+    room::{
+        ConstructedConnection, ConstructedProperty, ConstructedPropertyValue, LoadUnit, RoomData,
+        StringStorage,
+    },
+    CObjectId, MaybeParsed,
+};
+use uuid::Uuid;
+use zerocopy::LittleEndian;
 
 use crate::{
     icon,
-    loaders::{model::ModelAsset, room::RoomAsset, texture::TextureAsset},
+    loaders::{
+        modcon::ModConAsset,
+        model::ModelAsset,
+        room::{RoomAsset, SharedTemplates},
+        texture::TextureAsset,
+    },
     material::CustomMaterial,
-    render::{camera::ModelCamera, grid::GridSettings, TemporaryLabel},
-    tabs::{modcon::ModelLabel, property_with_id, property_with_value, EditorTabSystem, TabState},
+    render::{
+        camera::ModelCamera,
+        convert_transform,
+        grid::GridSettings,
+        model::{load_model, spawn_model_meshes, BuiltModel},
+        TemporaryLabel,
+    },
+    tabs::{property_with_id, property_with_value, EditorTabSystem, TabState},
     AssetRef,
 };
 
 pub struct RoomTab {
+    pub tab_id: Uuid,
     pub asset_ref: AssetRef,
     pub handle: Handle<RoomAsset>,
     pub camera: ModelCamera,
+    pub diffuse_map: Handle<Image>,
+    pub specular_map: Handle<Image>,
+    /// Index into `RoomData::instance_data`
+    pub selected_instance: Option<usize>,
+    pub show_graph: bool,
+    pub graph: Option<ScriptGraph>,
+    /// Root entities spawned for this tab
+    pub entities: Vec<Entity>,
+    pub placements: Vec<RoomPlacement>,
+    pub models: Vec<RoomModel>,
+    /// MCON resources waiting to be expanded into `models`
+    pub mod_cons: Vec<Handle<ModConAsset>>,
+    pub gizmo_material: Handle<StandardMaterial>,
+    pub selected_material: Handle<StandardMaterial>,
+    /// Instance currently drawn with `selected_material`
+    pub highlighted: Option<usize>,
+    pub spawned: bool,
+    pub camera_init: bool,
+    /// Edited copy of the room
+    pub data: Option<RoomData<LittleEndian>>,
+    /// FOOT form of the loaded asset, re-emitted when saving
+    pub footer: Option<Footer<LittleEndian>>,
+    pub new_layer_name: String,
+    pub save_path: String,
+    pub status: Option<String>,
+    /// `SharedTemplates::revision` that `data` was constructed with
+    pub templates_revision: u32,
 }
 
 impl Default for RoomTab {
-    fn default() -> Self { Self { asset_ref: default(), handle: default(), camera: default() } }
+    fn default() -> Self {
+        Self {
+            tab_id: Uuid::new_v4(),
+            asset_ref: default(),
+            handle: default(),
+            camera: default(),
+            diffuse_map: default(),
+            specular_map: default(),
+            selected_instance: None,
+            show_graph: false,
+            graph: None,
+            entities: vec![],
+            placements: vec![],
+            models: vec![],
+            mod_cons: vec![],
+            gizmo_material: default(),
+            selected_material: default(),
+            highlighted: None,
+            spawned: false,
+            camera_init: false,
+            data: None,
+            footer: None,
+            new_layer_name: String::new(),
+            save_path: String::new(),
+            status: None,
+            templates_revision: 0,
+        }
+    }
+}
+
+/// Script instance drawn in the 3D view.
+pub struct RoomPlacement {
+    pub instance_idx: usize,
+    pub transform: Transform,
+    pub entity: Entity,
+    /// Gizmo child, highlighted when the instance is selected
+    pub gizmo: Entity,
+}
+
+/// Model shared by one or more parent entities.
+pub struct RoomModel {
+    pub handle: Handle<ModelAsset>,
+    /// Parent entities with their world transform
+    pub targets: Vec<(Entity, Transform)>,
+    /// Number of `targets` with meshes spawned
+    pub spawned: usize,
+    pub built: Option<BuiltModel>,
+    pub failed: bool,
+}
+
+#[derive(Component, Clone, Debug)]
+pub struct RoomLabel {
+    pub tab_id: Uuid,
+    /// Index into `RoomData::instance_data`, or none for world geometry
+    pub instance: Option<usize>,
+}
+
+const GRAPH_NODE_SIZE: egui::Vec2 = egui::vec2(160.0, 36.0);
+const GRAPH_SPACING: egui::Vec2 = egui::vec2(240.0, 64.0);
+
+pub struct ScriptGraphNode {
+    pub instance_idx: usize,
+    pub label: String,
+    pub layer_idx: Option<usize>,
+    pub pos: Pos2,
+}
+
+pub struct ScriptGraphEdge {
+    pub from: usize,
+    pub to: usize,
+    pub label: String,
+}
+
+/// Instances as nodes, connections as edges.
+pub struct ScriptGraph {
+    pub nodes: Vec<ScriptGraphNode>,
+    pub edges: Vec<ScriptGraphEdge>,
+    pub offset: egui::Vec2,
+    pub only_connected: bool,
+}
+
+impl ScriptGraph {
+    pub fn new(room: &RoomData<LittleEndian>) -> Self {
+        let mut nodes = room
+            .instance_data
+            .iter()
+            .enumerate()
+            .map(|(instance_idx, instance)| {
+                let layer_idx = room.layers.iter().position(|l| {
+                    l.components.iter().any(|c| c.instance_index as usize == instance_idx)
+                });
+                ScriptGraphNode {
+                    instance_idx,
+                    label: instance_name(room, instance.id),
+                    layer_idx,
+                    pos: Pos2::ZERO,
+                }
+            })
+            .collect::<Vec<_>>();
+        let edges = room
+            .constructed_connections
+            .iter()
+            .filter_map(|c| {
+                let from = room.instance_data.iter().position(|i| i.id == c.source_id)?;
+                let to = room.instance_data.iter().position(|i| i.id == c.target_id)?;
+                Some(ScriptGraphEdge {
+                    from,
+                    to,
+                    label: format!("{:#X} / {:#X}", c.event, c.action),
+                })
+            })
+            .collect::<Vec<_>>();
+
+        // Place nodes in columns by distance from the start of each chain
+        let mut depth = vec![0usize; nodes.len()];
+        for _ in 0..nodes.len().min(16) {
+            let mut changed = false;
+            for edge in &edges {
+                if edge.from != edge.to && depth[edge.to] < depth[edge.from] + 1 {
+                    depth[edge.to] = depth[edge.from] + 1;
+                    changed = true;
+                }
+            }
+            if !changed {
+                break;
+            }
+        }
+        let mut rows = vec![0usize; depth.iter().max().map_or(0, |d| d + 1)];
+        for (node, &depth) in nodes.iter_mut().zip(&depth) {
+            node.pos =
+                Pos2::new(depth as f32 * GRAPH_SPACING.x, rows[depth] as f32 * GRAPH_SPACING.y);
+            rows[depth] += 1;
+        }
+        Self { nodes, edges, offset: egui::vec2(16.0, 16.0), only_connected: true }
+    }
+
+    fn is_connected(&self, node_idx: usize) -> bool {
+        self.edges.iter().any(|e| e.from == node_idx || e.to == node_idx)
+    }
+}
+
+fn layer_color(layer_idx: Option<usize>) -> Color32 {
+    match layer_idx {
+        Some(idx) => {
+            let hue = (idx as f32 * 0.618_034).fract();
+            egui::ecolor::Hsva::new(hue, 0.55, 0.45, 1.0).into()
+        }
+        None => Color32::from_gray(60),
+    }
+}
+
+fn script_graph_ui(ui: &mut egui::Ui, graph: &mut ScriptGraph, selected: &mut Option<usize>) {
+    ui.checkbox(&mut graph.only_connected, "Only connected instances");
+    let (response, painter) = ui.allocate_painter(ui.available_size(), Sense::drag());
+    if response.dragged() {
+        graph.offset += response.drag_delta();
+    }
+    let origin = response.rect.min.to_vec2() + graph.offset;
+    let visible = (0..graph.nodes.len())
+        .map(|i| !graph.only_connected || graph.is_connected(i))
+        .collect::<Vec<_>>();
+    let node_rect =
+        |node: &ScriptGraphNode| Rect::from_min_size(node.pos + origin, GRAPH_NODE_SIZE);
+
+    let text_color = ui.visuals().text_color();
+    for edge in &graph.edges {
+        let from = node_rect(&graph.nodes[edge.from]);
+        let to = node_rect(&graph.nodes[edge.to]);
+        let stroke = Stroke::new(1.5, Color32::from_gray(160));
+        if edge.from == edge.to {
+            let start = from.right_center();
+            painter.circle_stroke(start + egui::vec2(0.0, -12.0), 12.0, stroke);
+            continue;
+        }
+        let (start, end) = (from.right_center(), to.left_center());
+        painter.arrow(start, end - start, stroke);
+        painter.text(
+            start + (end - start) * 0.5,
+            Align2::CENTER_BOTTOM,
+            &edge.label,
+            FontId::monospace(10.0),
+            text_color,
+        );
+    }
+    for (node_idx, node) in graph.nodes.iter_mut().enumerate() {
+        if !visible[node_idx] {
+            continue;
+        }
+        let rect = Rect::from_min_size(node.pos + origin, GRAPH_NODE_SIZE);
+        let node_response = ui.interact(rect, response.id.with(node_idx), Sense::click_and_drag());
+        if node_response.dragged() {
+            node.pos += node_response.drag_delta();
+        }
+        if node_response.clicked() {
+            *selected = Some(node.instance_idx);
+        }
+        let stroke = if *selected == Some(node.instance_idx) {
+            Stroke::new(2.0, Color32::YELLOW)
+        } else {
+            Stroke::new(1.0, Color32::from_gray(20))
+        };
+        painter.rect(rect, 4.0, layer_color(node.layer_idx), stroke);
+        painter.text(
+            rect.center(),
+            Align2::CENTER_CENTER,
+            &node.label,
+            FontId::proportional(12.0),
+            Color32::WHITE,
+        );
+        node_response.on_hover_text_at_pointer(&node.label);
+    }
 }
 
 impl RoomTab {
-    pub fn new(asset_ref: AssetRef, handle: Handle<RoomAsset>) -> Box<Self> {
-        Box::new(Self { asset_ref, handle, ..default() })
-    }
-
-    // fn get_load_state(
-    //     &self,
-    //     server: &AssetServer,
-    //     assets: &Assets<RoomAsset>,
-    //     models: &Assets<ModelAsset>,
-    // ) -> LoadState {
-    //     match server.get_load_state(&self.handle) {
-    //         LoadState::Loaded => {}
-    //         state => return state,
-    //     };
-    //     let asset = match assets.get(&self.handle) {
-    //         Some(v) => v,
-    //         None => return LoadState::Failed,
-    //     };
-    //     // Ensure all dependencies loaded
-    //     match server.get_group_load_state(asset.models.iter().map(|h| h.id())) {
-    //         LoadState::Loaded => {}
-    //         state => return state,
-    //     }
-    //     for model in &asset.models {
-    //         let model = models.get(model).unwrap();
-    //         match model.get_load_state(server) {
-    //             LoadState::Loaded => {}
-    //             state => return state,
-    //         }
-    //     }
-    //     LoadState::Loaded
-    // }
+    /// `package_path` is the package the room was opened from; saving defaults to its directory.
+    pub fn new(asset_ref: AssetRef, handle: Handle<RoomAsset>, package_path: &Path) -> Box<Self> {
+        let save_path = package_path
+            .with_file_name(format!("{}.{}", asset_ref.id, asset_ref.kind))
+            .to_string_lossy()
+            .into_owned();
+        Box::new(Self { asset_ref, handle, save_path, ..default() })
+    }
+
+    fn add_model_target(&mut self, handle: Handle<ModelAsset>, entity: Entity, xf: Transform) {
+        match self.models.iter_mut().find(|m| m.handle == handle) {
+            Some(model) => model.targets.push((entity, xf)),
+            None => self.models.push(RoomModel {
+                handle,
+                targets: vec![(entity, xf)],
+                spawned: 0,
+                built: None,
+                failed: false,
+            }),
+        }
+    }
+
+    /// Spawns script instance placements and queues world geometry from the load units.
+    fn spawn_room(
+        &mut self,
+        room: &RoomData<LittleEndian>,
+        commands: &mut Commands,
+        meshes: &mut Assets<Mesh>,
+        materials: &mut Assets<StandardMaterial>,
+        server: &AssetServer,
+    ) {
+        let gizmo_mesh = meshes.add(shape::Cube { size: 0.5 }.into());
+        let direction_mesh = meshes.add(shape::Box::new(0.1, 1.0, 0.1).into());
+        let volume_mesh = meshes.add(wire_box_mesh());
+        self.gizmo_material = materials.add(StandardMaterial {
+            base_color: Color::rgb(0.2, 0.6, 1.0),
+            unlit: true,
+            ..default()
+        });
+        self.selected_material =
+            materials.add(StandardMaterial { base_color: Color::YELLOW, unlit: true, ..default() });
+        let volume_material = materials.add(StandardMaterial {
+            base_color: Color::rgb(1.0, 0.5, 0.1),
+            unlit: true,
+            ..default()
+        });
+
+        for instance_idx in 0..room.instance_data.len() {
+            let Some(xf) = room.instance_transform(instance_idx) else {
+                continue;
+            };
+            let [rx, ry, rz] = xf.rotation.to_array().map(f32::to_radians);
+            let transform = Transform {
+                translation: Vec3::from_array(xf.position.to_array()),
+                rotation: Quat::from_euler(EulerRot::ZYX, rz, ry, rx),
+                scale: Vec3::ONE,
+            };
+            let scale = Vec3::from_array(xf.scale.to_array());
+            let volume = room.instance_volume(instance_idx);
+            let label = RoomLabel { tab_id: self.tab_id, instance: Some(instance_idx) };
+
+            let gizmo = commands
+                .spawn((
+                    PbrBundle {
+                        mesh: gizmo_mesh.clone(),
+                        material: self.gizmo_material.clone(),
+                        ..default()
+                    },
+                    RaycastMesh::<RoomRaycastSet>::default(),
+                ))
+                .id();
+            // Points along +Y, the forward axis
+            let direction = commands
+                .spawn(PbrBundle {
+                    mesh: direction_mesh.clone(),
+                    material: self.gizmo_material.clone(),
+                    transform: Transform::from_xyz(0.0, 0.75, 0.0),
+                    ..default()
+                })
+                .id();
+            let scaled = commands
+                .spawn((SpatialBundle::from_transform(Transform::from_scale(scale)), label.clone()))
+                .id();
+            let entity = commands
+                .spawn((
+                    SpatialBundle { transform, visibility: Visibility::Hidden, ..default() },
+                    label,
+                ))
+                .push_children(&[gizmo, direction, scaled])
+                .id();
+            if let Some(volume) = volume {
+                let volume = commands
+                    .spawn(PbrBundle {
+                        mesh: volume_mesh.clone(),
+                        material: volume_material.clone(),
+                        transform: Transform::from_translation(Vec3::from_array(
+                            volume.offset.to_array(),
+                        ))
+                        .with_scale(Vec3::from_array(volume.size.to_array())),
+                        ..default()
+                    })
+                    .id();
+                commands.entity(entity).add_child(volume);
+            }
+            for id in room.instance_models(instance_idx) {
+                let handle = server.load(format!("{id}.CMDL"));
+                self.add_model_target(handle, scaled, transform * Transform::from_scale(scale));
+            }
+            self.placements.push(RoomPlacement { instance_idx, transform, entity, gizmo });
+            self.entities.push(entity);
+        }
+
+        let resources = room.load_units.iter().filter_map(|u| u.resources());
+        for resource in resources.flat_map(|r| &r.resources) {
+            match resource.kind {
+                K_FORM_MCON => {
+                    let handle = server.load(format!("{}.{}", resource.id, resource.kind));
+                    if !self.mod_cons.contains(&handle) {
+                        self.mod_cons.push(handle);
+                    }
+                }
+                K_FORM_CMDL | K_FORM_WMDL => {
+                    let entity = self.spawn_world_root(commands, Transform::IDENTITY);
+                    let handle = server.load(format!("{}.{}", resource.id, resource.kind));
+                    self.add_model_target(handle, entity, Transform::IDENTITY);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn spawn_world_root(&mut self, commands: &mut Commands, transform: Transform) -> Entity {
+        let entity = commands
+            .spawn((
+                SpatialBundle { transform, visibility: Visibility::Hidden, ..default() },
+                RoomLabel { tab_id: self.tab_id, instance: None },
+            ))
+            .id();
+        self.entities.push(entity);
+        entity
+    }
+
+    fn layers_ui(&mut self, ui: &mut egui::Ui) {
+        let Some(room) = &mut self.data else {
+            return;
+        };
+        let layer_names = room.layers.iter().map(|l| l.header.name.clone()).collect::<Vec<_>>();
+        // Moves are refused for layers with undecoded IDs, see `RoomData::move_component`
+        let movable = room.layers.iter().map(|l| l.header.ids.is_empty()).collect::<Vec<_>>();
+        let mut move_component = None;
+        for layer_idx in 0..room.layers.len() {
+            let title = format!("Layer {} ({})", layer_idx, room.layers[layer_idx].header.name);
+            egui::CollapsingHeader::new(title).id_source(("layer", layer_idx)).show(ui, |ui| {
+                let header = &mut room.layers[layer_idx].header;
+                ui.horizontal(|ui| {
+                    ui.label("Name");
+                    ui.text_edit_singleline(&mut header.name);
+                });
+                property_with_value(ui, "ID", header.id.to_string());
+                property_with_value(ui, "Unk", header.unk.to_string());
+                ui.horizontal(|ui| {
+                    ui.label("Unk2");
+                    ui.add(egui::DragValue::new(&mut header.unk2));
+                });
+                ui.colored_label(
+                    Color32::YELLOW,
+                    "Default-active flag not identified, toggling it is unsupported",
+                );
+                for id in &header.ids {
+                    property_with_value(ui, "Unk ID", id.to_string());
+                }
+                if !header.ids.is_empty() {
+                    ui.colored_label(
+                        Color32::YELLOW,
+                        "Layer has undecoded IDs, components can't be moved in or out",
+                    );
+                }
+
+                let layer = &room.layers[layer_idx];
+                for (component_idx, component) in layer.components.iter().enumerate() {
+                    let property = &room.constructed_properties[component.property_index as usize];
+                    let title = if let Some(name) = &property.name {
+                        format!("Component {} ({})", component_idx, name)
+                    } else {
+                        format!("Component {} ({:#X})", component_idx, component.component_type)
+                    };
+                    ui.collapsing(title, |ui| {
+                        property_with_value(
+                            ui,
+                            "Instance index",
+                            component.instance_index.to_string(),
+                        );
+                        ui.add_enabled_ui(movable[layer_idx], |ui| {
+                            egui::ComboBox::from_id_source(("move_component", component_idx))
+                                .selected_text("Move to layer")
+                                .show_ui(ui, |ui| {
+                                    for (target_idx, name) in layer_names.iter().enumerate() {
+                                        if target_idx != layer_idx
+                                            && ui
+                                                .add_enabled(
+                                                    movable[target_idx],
+                                                    egui::Button::new(name),
+                                                )
+                                                .clicked()
+                                        {
+                                            move_component =
+                                                Some((layer_idx, component_idx, target_idx));
+                                        }
+                                    }
+                                });
+                        });
+                        property_ui(ui, property);
+                        connections_ui(ui, room, component.instance_index as usize);
+                    });
+                }
+            });
+        }
+        if let Some((from_layer, component_idx, to_layer)) = move_component {
+            if let Err(e) = room.move_component(from_layer, component_idx, to_layer) {
+                self.status = Some(format!("{e:?}"));
+            }
+            // Layer colors are cached in the graph
+            self.graph = None;
+        }
+
+        ui.horizontal(|ui| {
+            ui.text_edit_singleline(&mut self.new_layer_name);
+            if ui.button(format!("{} Add layer", icon::ADD)).clicked() {
+                let id = CObjectId::from(Uuid::new_v4());
+                room.add_layer(id, std::mem::take(&mut self.new_layer_name));
+            }
+        });
+        ui.separator();
+        ui.horizontal(|ui| {
+            ui.text_edit_singleline(&mut self.save_path);
+            if ui.button(format!("{} Save", icon::DISK)).clicked() {
+                self.status = Some(match self.save() {
+                    Ok(()) => format!("Saved {}", self.save_path),
+                    Err(e) => format!("Failed to save: {e:?}"),
+                });
+            }
+        });
+        if let Some(status) = &self.status {
+            ui.label(status);
+        }
+    }
+
+    fn save(&self) -> anyhow::Result<()> {
+        let Some(data) = &self.data else {
+            return Ok(());
+        };
+        let mut file = BufWriter::new(File::create(&self.save_path)?);
+        data.write(&mut file)?;
+        if let Some(footer) = &self.footer {
+            footer.write(&mut file)?;
+        }
+        file.flush()?;
+        Ok(())
+    }
+
+    fn is_loading(&self) -> bool {
+        !self.spawned
+            || !self.mod_cons.is_empty()
+            || self.models.iter().any(|m| m.built.is_none() && !m.failed)
+    }
 }
 
 pub struct RoomRaycastSet;
@@ -69,11 +573,13 @@ impl EditorTabSystem for RoomTab {
         SCommands,
         SResMut<Assets<Mesh>>,
         SResMut<Assets<CustomMaterial>>,
+        SResMut<Assets<StandardMaterial>>,
         SResMut<Assets<ModelAsset>>,
         SResMut<Assets<TextureAsset>>,
         SResMut<Assets<Image>>,
         SResMut<AssetServer>,
         SResMut<Assets<RoomAsset>>,
+        SRes<Assets<ModConAsset>>,
     );
     type UiParam = (
         SCommands,
@@ -81,29 +587,145 @@ impl EditorTabSystem for RoomTab {
         SRes<Assets<ModelAsset>>,
         SRes<Assets<RoomAsset>>,
         SQuery<Read<Parent>, With<Intersection<RoomRaycastSet>>>,
-        SQuery<Read<ModelLabel>>,
+        SQuery<Read<RoomLabel>>,
+        SQuery<Read<Children>>,
+        SRes<SharedTemplates>,
     );
 
     fn load(&mut self, query: SystemParamItem<Self::LoadParam>) {
         let (
-            _commands,
-            _meshes,
-            _materials,
-            _models,
-            _texture_assets,
-            _images,
-            _server,
-            _room_assets,
+            mut commands,
+            mut meshes,
+            mut materials,
+            mut standard_materials,
+            mut models,
+            mut texture_assets,
+            mut images,
+            server,
+            room_assets,
+            mod_con_assets,
         ) = query;
+
+        if !self.spawned {
+            let Some(room) = room_assets.get(&self.handle) else {
+                return;
+            };
+            self.spawn_room(
+                &room.inner,
+                &mut commands,
+                &mut meshes,
+                &mut standard_materials,
+                &server,
+            );
+            self.spawned = true;
+        }
+        for &entity in &self.entities {
+            if let Some(mut commands) = commands.get_entity(entity) {
+                commands.insert(Visibility::Hidden);
+            }
+        }
+
+        for handle in std::mem::take(&mut self.mod_cons) {
+            let Some(mod_con) = mod_con_assets.get(&handle) else {
+                if server.get_load_state(&handle) != LoadState::Failed {
+                    self.mod_cons.push(handle);
+                }
+                continue;
+            };
+            let Some(data) = &mod_con.inner.visual_data else {
+                continue;
+            };
+            for (model_idx, xf) in data.transform_models() {
+                let transform = convert_transform(xf);
+                let entity = self.spawn_world_root(&mut commands, transform);
+                self.add_model_target(mod_con.models[model_idx].clone(), entity, transform);
+            }
+        }
+
+        for model in &mut self.models {
+            if model.built.is_none() && !model.failed {
+                let Some(asset) = models.get_mut(&model.handle) else {
+                    model.failed = server.get_load_state(&model.handle) == LoadState::Failed;
+                    continue;
+                };
+                match asset.get_load_state(&server) {
+                    LoadState::Loaded => {}
+                    LoadState::Failed => {
+                        model.failed = true;
+                        continue;
+                    }
+                    _ => continue,
+                }
+                asset.build_texture_images(&mut texture_assets, &mut images);
+                match load_model(asset, &mut meshes) {
+                    Ok(built) => model.built = Some(built),
+                    Err(e) => {
+                        log::error!("Failed to load model: {e:?}");
+                        model.failed = true;
+                        continue;
+                    }
+                }
+            }
+            let (Some(built), Some(asset)) = (&model.built, models.get_mut(&model.handle)) else {
+                continue;
+            };
+            for &(entity, transform) in &model.targets[model.spawned..] {
+                let is_mirrored = transform.scale.x.is_sign_negative()
+                    ^ transform.scale.y.is_sign_negative()
+                    ^ transform.scale.z.is_sign_negative();
+                if let Some(mut commands) = commands.get_entity(entity) {
+                    commands.with_children(|builder| {
+                        spawn_model_meshes::<RaycastMesh<RoomRaycastSet>>(
+                            builder,
+                            asset,
+                            built,
+                            is_mirrored,
+                            &mut materials,
+                        );
+                    });
+                }
+            }
+            model.spawned = model.targets.len();
+        }
+
+        if !self.camera_init && !self.is_loading() {
+            let mut min = Vec3A::splat(f32::MAX);
+            let mut max = Vec3A::splat(f32::MIN);
+            for model in &self.models {
+                let Some(built) = &model.built else {
+                    continue;
+                };
+                let m_min = Vec3::from(built.aabb.min());
+                let m_max = Vec3::from(built.aabb.max());
+                for &(_, xf) in &model.targets {
+                    min = min.min(Vec3A::from(xf * m_min));
+                    max = max.max(Vec3A::from(xf * m_max));
+                }
+            }
+            for placement in &self.placements {
+                min = min.min(Vec3A::from(placement.transform.translation));
+                max = max.max(Vec3A::from(placement.transform.translation));
+            }
+            if min.cmple(max).all() {
+                self.camera.init(&Aabb::from_min_max(min.into(), max.into()), true);
+            }
+            self.camera_init = true;
+        }
+
+        // FIXME
+        if self.diffuse_map.is_weak() {
+            self.diffuse_map = server.load("papermill_diffuse_rgb9e5_zstd.ktx2");
+            self.specular_map = server.load("papermill_specular_rgb9e5_zstd.ktx2");
+        }
     }
 
     fn close(&mut self, query: SystemParamItem<Self::LoadParam>) -> bool {
-        let (_commands, _, _, _, _, _, _, _) = query;
-        // for model in self.models.iter().flat_map(|l| &l.loaded) {
-        //     if let Some(commands) = commands.get_entity(model.entity) {
-        //         commands.despawn_recursive();
-        //     }
-        // }
+        let (mut commands, _, _, _, _, _, _, _, _, _) = query;
+        for &entity in &self.entities {
+            if let Some(commands) = commands.get_entity(entity) {
+                commands.despawn_recursive();
+            }
+        }
         true
     }
 
@@ -126,20 +748,74 @@ impl EditorTabSystem for RoomTab {
             ui.interact(rect, ui.make_persistent_id("background"), Sense::click_and_drag());
         self.camera.update(&rect, &response, ui.input(|i| i.scroll_delta));
 
-        let (mut commands, _server, _models, room_assets, _intersection_query, _model_query) =
-            query;
+        let (
+            mut commands,
+            _server,
+            _models,
+            room_assets,
+            intersection_query,
+            label_query,
+            children_query,
+            templates,
+        ) = query;
         let room_asset = match room_assets.get(&self.handle) {
             Some(v) => v,
             None => return,
         };
+        if self.data.is_none() {
+            self.templates_revision = templates.revision;
+            self.footer = room_asset.footer.clone();
+        }
+        let room = self.data.get_or_insert_with(|| room_asset.inner.clone());
+        if self.templates_revision != templates.revision {
+            // Keep layer edits, only the properties are constructed again
+            self.templates_revision = templates.revision;
+            room.construct_properties(templates.get().as_deref());
+            self.graph = None;
+        }
 
-        // if let Some(parent) = intersection_query.iter().next() {
-        //     self.selected_model = Some(model_query.get(parent.get()).unwrap().clone());
-        // }
+        let mut hovered_instance = None;
+        if let Some(parent) = intersection_query.iter().next() {
+            if let Ok(label) = label_query.get(parent.get()) {
+                if label.tab_id == self.tab_id {
+                    hovered_instance = label.instance;
+                }
+            }
+        }
+        if response.clicked() {
+            self.selected_instance = hovered_instance;
+        }
+        if self.show_graph {
+            let graph = self.graph.get_or_insert_with(|| ScriptGraph::new(room));
+            egui::Window::new(format!("{} Script graph", icon::NODETREE))
+                .id(egui::Id::new(("script_graph", self.asset_ref.id)))
+                .open(&mut self.show_graph)
+                .default_size([720.0, 480.0])
+                .show(ui.ctx(), |ui| script_graph_ui(ui, graph, &mut self.selected_instance));
+        }
         egui::Frame::group(ui.style()).show(ui, |ui| {
             egui::ScrollArea::vertical()
                 // .max_height(rect.height() * 0.25)
                 .show(ui, |ui| {
+                    ui.toggle_value(
+                        &mut self.show_graph,
+                        format!("{} Script graph", icon::NODETREE),
+                    );
+                    if self.is_loading() {
+                        ui.horizontal(|ui| {
+                            ui.spinner();
+                            ui.label("Loading models");
+                        });
+                    }
+                    if let Some(instance) =
+                        self.selected_instance.and_then(|i| room_asset.inner.instance_data.get(i))
+                    {
+                        property_with_value(
+                            ui,
+                            "Selected",
+                            instance_name(&room_asset.inner, instance.id),
+                        );
+                    }
                     if !room_asset.inner.room_header.parent_room_id.is_nil() {
                         property_with_id(
                             ui,
@@ -216,44 +892,8 @@ impl EditorTabSystem for RoomTab {
                     if let Some(light_probe) = &room_asset.inner.baked_lighting.light_probe {
                         property_with_id(ui, "Light Probe", light_probe.ltpb_id.into_inner());
                     }
-                    for (layer_idx, layer) in room_asset.inner.layers.iter().enumerate() {
-                        ui.collapsing(
-                            format!("Layer {} ({})", layer_idx, layer.header.name),
-                            |ui| {
-                                property_with_value(ui, "Name", layer.header.name.clone());
-                                property_with_value(ui, "ID", layer.header.id.to_string());
-                                property_with_value(ui, "Unk", layer.header.unk.to_string());
-                                for id in &layer.header.ids {
-                                    property_with_value(ui, "Unk ID", id.to_string());
-                                }
-                                property_with_value(ui, "Unk2", layer.header.unk2.to_string());
-                                for (component_idx, component) in
-                                    layer.components.iter().enumerate()
-                                {
-                                    let property = &room_asset.inner.constructed_properties
-                                        [component.property_index as usize];
-                                    ui.collapsing(
-                                        if let Some(name) = &property.name {
-                                            format!("Component {} ({})", component_idx, name)
-                                        } else {
-                                            format!(
-                                                "Component {} ({:#X})",
-                                                component_idx, component.component_type
-                                            )
-                                        },
-                                        |ui| {
-                                            property_with_value(
-                                                ui,
-                                                "Instance index",
-                                                component.instance_index.to_string(),
-                                            );
-                                            property_ui(ui, property);
-                                        },
-                                    );
-                                }
-                            },
-                        );
-                    }
+                    head_chunks_ui(ui, &room_asset.inner);
+                    self.layers_ui(ui);
                 });
         });
 
@@ -282,12 +922,10 @@ impl EditorTabSystem for RoomTab {
             RenderLayers::layer(state.render_layer),
             TemporaryLabel,
         ));
-        // if self.env_light {
-        //     entity.insert(EnvironmentMapLight {
-        //         diffuse_map: self.diffuse_map.clone(),
-        //         specular_map: self.specular_map.clone(),
-        //     });
-        // }
+        entity.insert(EnvironmentMapLight {
+            diffuse_map: self.diffuse_map.clone(),
+            specular_map: self.specular_map.clone(),
+        });
         if response.hovered() {
             if let Some(pos) = ui.input(|i| {
                 i.pointer.hover_pos().map(|pos| Vec2::new(pos.x, i.screen_rect.height() - pos.y))
@@ -313,16 +951,26 @@ impl EditorTabSystem for RoomTab {
             // ));
         }
 
-        // for info in &self.models {
-        //     for model in &info.loaded {
-        //         if let Some(mut commands) = commands.get_entity(model.entity) {
-        //             commands.insert((
-        //                 if model.visible { Visibility::Visible } else { Visibility::Hidden },
-        //                 RenderLayers::layer(state.render_layer),
-        //             ));
-        //         }
-        //     }
-        // }
+        if self.highlighted != self.selected_instance {
+            for placement in &self.placements {
+                let material = if Some(placement.instance_idx) == self.selected_instance {
+                    self.selected_material.clone()
+                } else {
+                    self.gizmo_material.clone()
+                };
+                if let Some(mut commands) = commands.get_entity(placement.gizmo) {
+                    commands.insert(material);
+                }
+            }
+            self.highlighted = self.selected_instance;
+        }
+        let layer = RenderLayers::layer(state.render_layer);
+        for &entity in &self.entities {
+            if let Some(mut commands) = commands.get_entity(entity) {
+                commands.insert(Visibility::Visible);
+            }
+            set_render_layer(&mut commands, &children_query, entity, layer);
+        }
 
         state.render_layer += 1;
     }
@@ -338,6 +986,221 @@ impl EditorTabSystem for RoomTab {
     fn asset(&self) -> Option<AssetRef> { Some(self.asset_ref) }
 }
 
+/// Inserts `layer` on an entity and all of its descendants.
+fn set_render_layer(
+    commands: &mut Commands,
+    children_query: &Query<&Children>,
+    entity: Entity,
+    layer: RenderLayers,
+) {
+    if let Some(mut commands) = commands.get_entity(entity) {
+        commands.insert(layer);
+    }
+    if let Ok(children) = children_query.get(entity) {
+        for &child in children.iter() {
+            set_render_layer(commands, children_query, child, layer);
+        }
+    }
+}
+
+/// Unit box outline centered at the origin, for instance volumes.
+fn wire_box_mesh() -> Mesh {
+    let corner = |i: u32, bit: u32| if i & bit != 0 { 0.5 } else { -0.5 };
+    let positions =
+        (0..8u32).map(|i| [corner(i, 1), corner(i, 2), corner(i, 4)]).collect::<Vec<_>>();
+    #[rustfmt::skip]
+    let indices = vec![
+        0, 1, 2, 3, 4, 5, 6, 7,
+        0, 2, 1, 3, 4, 6, 5, 7,
+        0, 4, 1, 5, 2, 6, 3, 7,
+    ];
+    let mut mesh = Mesh::new(PrimitiveTopology::LineList);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, vec![[0.0f32, 0.0, 1.0]; 8]);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, vec![[0.0f32, 0.0]; 8]);
+    mesh.set_indices(Some(Indices::U32(indices)));
+    mesh
+}
+
+fn head_chunks_ui(ui: &mut egui::Ui, room: &RoomData<LittleEndian>) {
+    match &room.performance_groups {
+        Some(MaybeParsed::Parsed(groups)) => {
+            ui.collapsing(format!("Performance groups: {}", groups.groups.len()), |ui| {
+                for group in &groups.groups {
+                    property_with_value(ui, &group.id.to_string(), format!("{:#X}", group.flags));
+                }
+                unparsed_ui(ui, &groups.unparsed);
+            });
+        }
+        Some(MaybeParsed::Raw(data)) => raw_chunk_ui(ui, "Performance groups", data),
+        None => {}
+    }
+    match &room.generated_objects {
+        Some(MaybeParsed::Parsed(generated)) => {
+            ui.collapsing(format!("Generated objects: {}", generated.objects.len()), |ui| {
+                for object in &generated.objects {
+                    ui.group(|ui| {
+                        property_with_value(ui, "Generator", object.generator_id.to_string());
+                        property_with_value(ui, "Object", object.object_id.to_string());
+                    });
+                }
+                unparsed_ui(ui, &generated.unparsed);
+            });
+        }
+        Some(MaybeParsed::Raw(data)) => raw_chunk_ui(ui, "Generated objects", data),
+        None => {}
+    }
+    match &room.docks {
+        Some(MaybeParsed::Parsed(docks)) => {
+            ui.collapsing(format!("Docks: {}", docks.docks.len()), |ui| {
+                for (dock_idx, dock) in docks.docks.iter().enumerate() {
+                    ui.collapsing(format!("Dock {dock_idx}"), |ui| {
+                        property_with_value(ui, "ID", dock.id.to_string());
+                        for connection in &dock.connections {
+                            ui.group(|ui| {
+                                property_with_id(ui, "Room", connection.room_id.into_inner());
+                                property_with_value(ui, "Dock", connection.dock_id.to_string());
+                            });
+                        }
+                        for vertex in &dock.vertices {
+                            property_with_value(ui, "Vertex", format!("{:?}", vertex.to_array()));
+                        }
+                    });
+                }
+                unparsed_ui(ui, &docks.unparsed);
+            });
+        }
+        Some(MaybeParsed::Raw(data)) => raw_chunk_ui(ui, "Docks", data),
+        None => {}
+    }
+    match &room.load_unit_count {
+        Some(MaybeParsed::Parsed(load_unit_count)) => {
+            property_with_value(ui, "Load unit count", load_unit_count.count.to_string());
+        }
+        Some(MaybeParsed::Raw(data)) => raw_chunk_ui(ui, "Load unit count", data),
+        None => {}
+    }
+    for (load_unit_idx, load_unit) in room.load_units.iter().enumerate() {
+        let name = load_unit.header().map(|h| h.name.as_str()).unwrap_or("?");
+        ui.collapsing(format!("Load unit {load_unit_idx} ({name})"), |ui| {
+            load_unit_ui(ui, room, load_unit);
+        });
+    }
+}
+
+fn load_unit_ui(ui: &mut egui::Ui, room: &RoomData<LittleEndian>, load_unit: &LoadUnit) {
+    match &load_unit.header {
+        MaybeParsed::Parsed(header) => {
+            property_with_value(ui, "Name", header.name.clone());
+            property_with_value(ui, "ID", header.id.to_string());
+            unparsed_ui(ui, &header.unparsed);
+        }
+        MaybeParsed::Raw(data) => raw_chunk_ui(ui, "Header", data),
+    }
+    match &load_unit.resources {
+        MaybeParsed::Parsed(resources) => {
+            ui.collapsing(format!("Resources: {}", resources.resources.len()), |ui| {
+                for resource in &resources.resources {
+                    property_with_id(ui, &resource.kind.to_string(), resource.id.into_inner());
+                }
+                unparsed_ui(ui, &resources.unparsed);
+            });
+        }
+        MaybeParsed::Raw(data) => raw_chunk_ui(ui, "Resources", data),
+    }
+    match &load_unit.layers {
+        MaybeParsed::Parsed(layers) => {
+            ui.collapsing(format!("Layers: {}", layers.layer_ids.len()), |ui| {
+                for id in &layers.layer_ids {
+                    match room.layers.iter().find(|l| l.header.id == *id) {
+                        Some(layer) => property_with_value(ui, "Layer", layer.header.name.clone()),
+                        None => property_with_value(ui, "Layer", id.to_string()),
+                    };
+                }
+                unparsed_ui(ui, &layers.unparsed);
+            });
+        }
+        MaybeParsed::Raw(data) => raw_chunk_ui(ui, "Layers", data),
+    }
+}
+
+/// Name of the first component attached to instance `id`.
+fn instance_name(room: &RoomData<LittleEndian>, id: CObjectId) -> String {
+    let instance_idx = room.instance_data.iter().position(|i| i.id == id);
+    let component = room
+        .layers
+        .iter()
+        .flat_map(|l| &l.components)
+        .find(|c| Some(c.instance_index as usize) == instance_idx);
+    let property = component.map(|c| &room.constructed_properties[c.property_index as usize]);
+    match property.and_then(|p| p.name.as_ref()) {
+        Some(name) => format!("{name} ({id})"),
+        None => id.to_string(),
+    }
+}
+
+fn connections_ui(ui: &mut egui::Ui, room: &RoomData<LittleEndian>, instance_idx: usize) {
+    let Some(instance) = room.instance_data.get(instance_idx) else {
+        return;
+    };
+    let outgoing = room.outgoing_connections(instance.id).collect::<Vec<_>>();
+    let incoming = room.incoming_connections(instance.id).collect::<Vec<_>>();
+    if !outgoing.is_empty() {
+        ui.collapsing(format!("Outgoing connections: {}", outgoing.len()), |ui| {
+            for (idx, connection) in outgoing.into_iter().enumerate() {
+                ui.push_id(idx, |ui| {
+                    ui.group(|ui| {
+                        property_with_value(
+                            ui,
+                            "Target",
+                            instance_name(room, connection.target_id),
+                        );
+                        connection_ui(ui, connection);
+                    })
+                });
+            }
+        });
+    }
+    if !incoming.is_empty() {
+        ui.collapsing(format!("Incoming connections: {}", incoming.len()), |ui| {
+            for (idx, connection) in incoming.into_iter().enumerate() {
+                ui.push_id(idx, |ui| {
+                    ui.group(|ui| {
+                        property_with_value(
+                            ui,
+                            "Source",
+                            instance_name(room, connection.source_id),
+                        );
+                        connection_ui(ui, connection);
+                    })
+                });
+            }
+        });
+    }
+}
+
+fn connection_ui(ui: &mut egui::Ui, connection: &ConstructedConnection) {
+    property_with_value(ui, "Event", format!("{:#X}", connection.event));
+    property_with_value(ui, "Action", format!("{:#X}", connection.action));
+    if let Some(criteria) = &connection.event_criteria {
+        ui.collapsing("Event criteria", |ui| property_ui(ui, criteria));
+    }
+    if let Some(payload) = &connection.action_payload {
+        ui.collapsing("Action payload", |ui| property_ui(ui, payload));
+    }
+}
+
+fn unparsed_ui(ui: &mut egui::Ui, data: &[u8]) {
+    if !data.is_empty() {
+        property_with_value(ui, "Unparsed", format!("{} bytes", data.len()));
+    }
+}
+
+/// Chunk that didn't match its guessed layout.
+fn raw_chunk_ui(ui: &mut egui::Ui, label: &str, data: &[u8]) {
+    property_with_value(ui, label, format!("{} bytes (not decoded)", data.len()));
+}
+
 fn property_ui(ui: &mut egui::Ui, property: &ConstructedProperty) {
     property_with_value(ui, "ID", format!("{:#X}", property.id));
     if let Some(name) = &property.name {
@@ -443,7 +1306,11 @@ fn property_value_ui(ui: &mut egui::Ui, value: &ConstructedPropertyValue) {
             property_with_value(ui, "Double", format!("{value}"));
         }
         ConstructedPropertyValue::String(value) => {
-            property_with_value(ui, "String", value.clone());
+            let type_name = match value.storage {
+                StringStorage::Inline => "String (inline)",
+                StringStorage::Pooled(_) => "String",
+            };
+            property_with_value(ui, type_name, value.value.clone());
         }
     }
 }
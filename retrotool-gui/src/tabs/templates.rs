@@ -1,11 +1,15 @@
 use std::str::FromStr;
 
-use bevy::ecs::system::SystemParamItem;
+use anyhow::anyhow;
+use bevy::{
+    asset::Assets,
+    ecs::system::{lifetimeless::SResMut, SystemParamItem},
+};
 use egui::Widget;
 use retrolib::util::templates::{
-    load_type_template, FromRepr, HexU32, IntoRepr, PropertyTemplate, PropertyTemplateType,
-    PropertyTemplateTypeDiscriminants, TypeTemplate, TypeTemplateType,
-    TypeTemplateTypeDiscriminants,
+    load_type_template, save_template, FromRepr, HexU32, IntoRepr, PropertyTemplate,
+    PropertyTemplateType, PropertyTemplateTypeDiscriminants, TemplateDatabase, TemplateKind,
+    TypeTemplate, TypeTemplateType, TypeTemplateTypeDiscriminants, TYPE_SCHEMA,
 };
 use serde::{
     de::{value::StringDeserializer, IntoDeserializer},
@@ -15,12 +19,19 @@ use strum::{EnumMessage, IntoEnumIterator};
 
 use crate::{
     icon,
+    loaders::room::{RoomAsset, SharedTemplates},
     tabs::{EditorTabSystem, TabState},
 };
 
 pub struct TemplatesTab {
     pub current: TypeTemplate,
     pub editing_key: Option<(HexU32, String)>,
+    pub kind: TemplateKind,
+    /// File name and `root.json` entry, defaults to the template name
+    pub file_name: String,
+    /// Type ID for objects and typedefs, as hex
+    pub type_id: String,
+    pub status: Option<String>,
 }
 
 impl TemplatesTab {
@@ -31,20 +42,148 @@ impl TemplatesTab {
             ))
             .unwrap(),
             editing_key: None,
+            kind: TemplateKind::Object,
+            file_name: "Render".to_string(),
+            type_id: "0xDF31EC16".to_string(),
+            status: None,
+        })
+    }
+
+    fn new_template(&mut self) {
+        let template = match self.kind {
+            TemplateKind::Object | TemplateKind::Typedef => {
+                TypeTemplateType::PropertyList(Default::default())
+            }
+            TemplateKind::Struct => TypeTemplateType::Struct(Default::default()),
+            TemplateKind::Enum => TypeTemplateType::Enum(Default::default()),
+        };
+        self.current = TypeTemplate {
+            schema: TYPE_SCHEMA.to_string(),
+            name: String::new(),
+            description: None,
+            template,
+        };
+        self.editing_key = None;
+        self.file_name.clear();
+        self.type_id.clear();
+    }
+
+    fn open_ui(&mut self, ui: &mut egui::Ui, db: &TemplateDatabase) {
+        let objects = db.objects.iter().filter_map(|(id, template)| {
+            Some((TemplateKind::Object, db.root.objects.get(id)?, Some(*id), template))
+        });
+        let typedefs = db.typedefs.iter().filter_map(|(id, template)| {
+            Some((TemplateKind::Typedef, db.root.typedefs.get(id)?, Some(*id), template))
+        });
+        let structs =
+            db.structs.iter().map(|(name, template)| (TemplateKind::Struct, name, None, template));
+        let enums =
+            db.enums.iter().map(|(name, template)| (TemplateKind::Enum, name, None, template));
+        let entries = objects.chain(typedefs).chain(structs).chain(enums);
+        egui::ComboBox::from_id_source("open_template")
+            .selected_text(format!("{} Open", icon::FILE_FOLDER))
+            .show_ui(ui, |ui| {
+                for (kind, name, id, template) in entries {
+                    let label = format!("{} ({})", name, kind.get_message().unwrap_or_default());
+                    if ui.selectable_label(false, label).clicked() {
+                        self.current = template.clone();
+                        self.editing_key = None;
+                        self.kind = kind;
+                        self.file_name = name.clone();
+                        self.type_id = id.map(|id| id.to_string()).unwrap_or_default();
+                    }
+                }
+            });
+    }
+
+    fn save(&self, templates: &mut SharedTemplates) -> anyhow::Result<String> {
+        let name = if self.file_name.is_empty() { &self.current.name } else { &self.file_name };
+        let type_id =
+            if self.kind.has_id() { Some(HexU32::from_str(&self.type_id)?) } else { None };
+        let dir = templates.dir().ok_or_else(|| {
+            anyhow!("Templates are embedded in this build, set a custom templates directory")
+        })?;
+        save_template(&dir, self.kind, name, type_id, &self.current)?;
+        templates.reload();
+        Ok(match &templates.status {
+            Some(status) => format!("Saved {}, but failed to reload: {}", name, status),
+            None => format!("Saved {} to {}", name, dir.display()),
         })
     }
 }
 
 impl EditorTabSystem for TemplatesTab {
     type LoadParam = ();
-    type UiParam = ();
+    type UiParam = (SResMut<SharedTemplates>, SResMut<Assets<RoomAsset>>);
 
     fn ui(
         &mut self,
         ui: &mut egui::Ui,
-        _query: SystemParamItem<Self::UiParam>,
+        query: SystemParamItem<Self::UiParam>,
         _state: &mut TabState,
     ) {
+        let (mut templates, mut room_assets) = query;
+        ui.horizontal(|ui| {
+            if let Some(db) = templates.get() {
+                self.open_ui(ui, &db);
+            }
+            if ui.button(format!("{} New", icon::FILE_NEW)).clicked() {
+                self.new_template();
+            }
+            if ui.button(format!("{} Save", icon::DISK)).clicked() {
+                self.status = Some(match self.save(&mut templates) {
+                    Ok(status) => status,
+                    Err(e) => format!("Failed to save: {e:?}"),
+                });
+            }
+            if ui
+                .button(format!("{} Re-parse open rooms", icon::FILE_REFRESH))
+                .on_hover_text("Construct room properties again with the loaded templates")
+                .clicked()
+            {
+                let db = templates.get();
+                let mut count = 0;
+                for (_, room) in room_assets.iter_mut() {
+                    room.inner.construct_properties(db.as_deref());
+                    count += 1;
+                }
+                templates.revision += 1;
+                self.status = Some(match (&db, &templates.status) {
+                    (None, _) => format!("No templates loaded, re-parsed {count} rooms without"),
+                    (Some(_), Some(e)) => format!(
+                        "Re-parsed {count} rooms with the previous templates, reload failed: {e}"
+                    ),
+                    (Some(_), None) => format!("Re-parsed {count} rooms"),
+                });
+            }
+        });
+        ui.horizontal(|ui| {
+            egui::ComboBox::from_label("Kind")
+                .selected_text(self.kind.get_message().unwrap_or_default())
+                .show_ui(ui, |ui| {
+                    for kind in TemplateKind::iter() {
+                        ui.selectable_value(
+                            &mut self.kind,
+                            kind,
+                            kind.get_message().unwrap_or_default(),
+                        );
+                    }
+                });
+            egui::TextEdit::singleline(&mut self.file_name)
+                .hint_text(if self.current.name.is_empty() {
+                    "File name"
+                } else {
+                    self.current.name.as_str()
+                })
+                .ui(ui);
+            if self.kind.has_id() {
+                egui::TextEdit::singleline(&mut self.type_id).hint_text("Type ID").ui(ui);
+            }
+        });
+        if let Some(status) = &self.status {
+            ui.label(status);
+        }
+        ui.separator();
         egui::TextEdit::singleline(&mut self.current.name).hint_text("Name").ui(ui);
         optional_text_edit(ui, &mut self.current.description, "Description");
         enum_ui::<_, TypeTemplateTypeDiscriminants>(
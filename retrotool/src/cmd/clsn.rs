@@ -1,130 +1,31 @@
 use std::{
+    collections::HashMap,
+    fs,
     fs::File,
-    io::{Cursor, Write},
-    path::PathBuf,
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
 };
 
-use anyhow::{ensure, Result};
+use anyhow::{anyhow, bail, ensure, Context, Result};
 use argh::FromArgs;
-use binrw::{binrw, BinReaderExt, Endian};
+use gltf_json as json;
+use json::validation::Checked::Valid;
 use retrolib::{
     format::{
-        chunk::ChunkDescriptor, foot::K_FORM_FOOT, rfrm::FormDescriptor, CAABox, COBBox, CVector3f,
-        FourCC,
+        clsn::{
+            CCollisionMaterial, CIndexedTriangle, CollisionData, CollisionTree, K_FORM_CLSN,
+            K_FORM_DCLN,
+        },
+        foot::{Footer, K_FORM_FOOT},
+        rfrm::FormDescriptor,
+        CVector3f,
     },
     util::file::map_file,
 };
+use serde_json::{json, Value};
 use zerocopy::LittleEndian;
 
-// CAABoxCollisionTree
-pub const K_FORM_CLSN: FourCC = FourCC(*b"CLSN");
-// COBBoxCollisionTree
-pub const K_FORM_DCLN: FourCC = FourCC(*b"DCLN");
-
-// COBBCollisionTree Header (only used in DCLN)
-//pub const K_CHUNK_INFO: FourCC = FourCC(*b"INFO");
-
-// Vertex data
-pub const K_CHUNK_VERT: FourCC = FourCC(*b"VERT");
-
-// Material data
-pub const K_CHUNK_MTRL: FourCC = FourCC(*b"MTRL");
-
-// Triangle data
-pub const K_CHUNK_TRIS: FourCC = FourCC(*b"TRIS");
-
-// Octree data
-//pub const K_CHUNK_TREE: FourCC = FourCC(*b"TREE");
-
-pub const K_CLSN_READER_VERSION: u32 = 11;
-pub const K_CLSN_WRITER_VERSION: u32 = 22;
-
-pub const K_DCLN_READER_VERSION: u32 = 9;
-pub const K_DCLN_WRITER_VERSION: u32 = 18;
-
-#[binrw]
-#[derive(Clone, Debug)]
-pub struct Vertices {
-    pub count: u32,
-    #[br(count = count)]
-    vertices: Vec<CVector3f>,
-}
-
-#[binrw]
-#[derive(Clone, Debug)]
-pub struct Materials {
-    pub count: u32,
-    #[br(count = count)]
-    materials: Vec<CCollisionMaterial>,
-}
-
-#[binrw]
-#[derive(Clone, Debug)]
-pub struct CCollisionMaterial {
-    orientation: u32,
-    material_type: u32,
-    world_type: u32,
-    behavior_list: u32,
-    filter_list: u32,
-}
-
-#[binrw]
-#[derive(Clone, Debug)]
-pub struct CIndexedTriangle {
-    idx1: u32,
-    idx2: u32,
-    idx3: u32,
-    material: u16,
-    unk: u16,
-}
-
-#[binrw]
-#[derive(Clone, Debug)]
-pub struct Triangles {
-    count: u32,
-    #[br(count = count)]
-    triangles: Vec<CIndexedTriangle>,
-}
-
-#[binrw]
-#[derive(Clone, Debug)]
-pub struct AABoxTreeNode {
-    bounds: CAABox,
-    start: u32,
-    end: u32,
-    unk1: u8,
-    unk2: u8,
-    unk3: u8,
-    unk4: u8,
-}
-
-#[binrw]
-#[derive(Clone, Debug)]
-pub struct OBBoxTreeNode {
-    bounds: COBBox,
-    start: u32,
-    end: u32,
-    unk1: u8,
-    unk2: u8,
-    unk3: u8,
-    unk4: u8,
-}
-
-#[binrw]
-#[derive(Clone, Debug)]
-pub struct AABoxCollisionTree {
-    count: u32,
-    #[br(count = count)]
-    nodes: Vec<AABoxTreeNode>,
-}
-
-#[binrw]
-#[derive(Clone, Debug)]
-pub struct OBBoxCollisionTree {
-    count: u32,
-    #[br(count = count)]
-    nodes: Vec<OBBoxTreeNode>,
-}
+use crate::util::gltf::{transform_point, GltfInput};
 
 #[derive(FromArgs, PartialEq, Debug)]
 /// process CLSN/DCLN files
@@ -138,101 +39,721 @@ pub struct Args {
 #[argh(subcommand)]
 enum SubCommand {
     Convert(ConvertArgs),
+    Import(ImportArgs),
 }
 
 #[derive(FromArgs, PartialEq, Eq, Debug)]
-/// converts a CLSN/DCLN to obj
+/// converts a CLSN/DCLN to glTF (or obj)
 #[argh(subcommand, name = "convert")]
 pub struct ConvertArgs {
     #[argh(positional)]
     /// input CLSN/DCLN
     input: PathBuf,
     #[argh(positional)]
-    /// output file
+    /// output file (.gltf or .obj)
     out: PathBuf,
+    #[argh(switch, short = 't')]
+    /// export bounding volume tree nodes (glTF only)
+    tree: bool,
+}
+
+#[derive(FromArgs, PartialEq, Eq, Debug)]
+/// builds a CLSN/DCLN from an obj or glTF mesh (experimental)
+#[argh(subcommand, name = "import")]
+pub struct ImportArgs {
+    #[argh(positional)]
+    /// input mesh (.obj or .gltf)
+    input: PathBuf,
+    #[argh(positional)]
+    /// original CLSN/DCLN (footer is reused)
+    template: PathBuf,
+    #[argh(positional)]
+    /// output CLSN/DCLN
+    output: PathBuf,
+    #[argh(option, short = 'k')]
+    /// output form, CLSN (AABB tree) or DCLN (OBB tree); defaults to the template's
+    kind: Option<String>,
+    #[argh(option, short = 'm')]
+    /// JSON file mapping material names to collision material fields
+    materials: Option<PathBuf>,
+    #[argh(switch)]
+    /// build even though the tree layout is unverified
+    experimental: bool,
 }
 
 #[allow(unused)]
 pub fn run(args: Args) -> Result<()> {
     match args.command {
         SubCommand::Convert(c_args) => convert(c_args),
+        SubCommand::Import(c_args) => import(c_args),
     }
 }
 
 fn convert(args: ConvertArgs) -> Result<()> {
     ensure!(args.input != args.out);
 
-    // TODO: Migrate to real model format (glTF?)
     let data = map_file(&args.input)?;
-    let (form_desc, mut col_data, remain) = FormDescriptor::<LittleEndian>::slice(&data)?;
-
-    if form_desc.id == K_FORM_DCLN {
-        ensure!(form_desc.reader_version.get() == K_DCLN_READER_VERSION);
-        ensure!(form_desc.writer_version.get() == K_DCLN_WRITER_VERSION);
-    } else if form_desc.id == K_FORM_CLSN {
-        ensure!(form_desc.reader_version.get() == K_CLSN_READER_VERSION);
-        ensure!(form_desc.writer_version.get() == K_CLSN_WRITER_VERSION);
-    }
-
+    let collision = CollisionData::<LittleEndian>::slice(&data)?;
+    let (_, _, remain) = FormDescriptor::<LittleEndian>::slice(&data)?;
     let (foot_desc, _, remain) = FormDescriptor::<LittleEndian>::slice(remain)?;
     ensure!(foot_desc.id == K_FORM_FOOT);
     ensure!(foot_desc.reader_version.get() == 1);
     ensure!(foot_desc.writer_version.get() == 1);
     ensure!(remain.is_empty());
+    log::debug!("Bounds: {:#?}", collision.bounds());
+    log::debug!("Materials: {:#?}", collision.materials);
+    log::debug!("Tree: {} nodes", collision.tree().map_or(0, |t| t.len()));
 
-    //let mut bounds: Option<CAABox> = None;
-    let mut vertices: Option<Vertices> = None;
-    //let mut materials: Option<Materials> = None;
-    let mut triangles: Option<Triangles> = None;
-    //let mut aboxtree: Option<AABoxCollisionTree> = None;
-    //let mut oboxtree: Option<OBBoxCollisionTree> = None;
-
-    while !col_data.is_empty() {
-        let (desc, data, remain) = ChunkDescriptor::<LittleEndian>::slice(col_data)?;
-        /*
-        if desc.id == K_CHUNK_INFO {
-            //bounds = Some(Cursor::new(data).read_type(Endian::Little)?);
-            //log::debug!("Bounds: {bounds:#?}");
-        } else */
-        if desc.id == K_CHUNK_VERT {
-            vertices = Some(Cursor::new(data).read_type(Endian::Little)?);
-            log::debug!("Vertices: {vertices:#?}");
-        } else if desc.id == K_CHUNK_MTRL {
-            //materials = Some(Cursor::new(data).read_type(Endian::Little)?);
-            //log::debug!("Materials: {materials:#?}");
-        } else if desc.id == K_CHUNK_TRIS {
-            triangles = Some(Cursor::new(data).read_type(Endian::Little)?);
-            log::debug!("Triangles: {triangles:#?}");
-        } /* else if desc.id == K_CHUNK_TREE {
-              if form_desc.id == K_FORM_CLSN {
-                  //aboxtree = Some(Cursor::new(data).read_type(Endian::Little)?);
-                  //log::debug!("Tree: {aboxtree:#?}");
-              } else {
-                  //oboxtree = Some(Cursor::new(data).read_type(Endian::Little)?);
-                  //log::debug!("Tree: {oboxtree:#?}");
-              }
-          } */
-        col_data = remain;
-    }
-
-    let mut file = File::create(&args.out)?;
-    if let Some(verts) = vertices {
-        let tris = triangles.unwrap();
+    if args.out.extension() == Some("obj".as_ref()) {
+        write_obj(&collision, &args.out)
+    } else {
+        write_gltf(&collision, &args.out, args.tree)
+    }
+}
+
+fn write_obj(collision: &CollisionData<LittleEndian>, out: &Path) -> Result<()> {
+    let mut file = File::create(out)?;
+    file.write_fmt(format_args!(
+        "# Generated by retrotool, {} vertices, {} triangles\n# Vertices\n",
+        collision.vertices.len(),
+        collision.triangles.len()
+    ))?;
+    for vertex in &collision.vertices {
+        file.write_fmt(format_args!("v {} {} {}\n", vertex.x, vertex.y, vertex.z))?;
+    }
+    file.write_fmt(format_args!("\n# Triangles\n"))?;
+    for triangle in &collision.triangles {
         file.write_fmt(format_args!(
-            "# Generated by retrotool, {} vertices, {} triangles\n# Vertices\n",
-            verts.count, tris.count
+            "f {} {} {}\n",
+            triangle.idx1 + 1,
+            triangle.idx2 + 1,
+            triangle.idx3 + 1
         ))?;
-        for vertex in verts.vertices.iter() {
-            file.write_fmt(format_args!("v {} {} {}\n", vertex.x, vertex.y, vertex.z))?;
+    }
+    Ok(())
+}
+
+fn material_extras(material: &CCollisionMaterial) -> Result<json::extras::Extras> {
+    Ok(Some(serde_json::value::to_raw_value(&json!({
+        "orientation": material.orientation,
+        "material_type": material.material_type,
+        "world_type": material.world_type,
+        "behavior_list": material.behavior_list,
+        "filter_list": material.filter_list,
+    }))?))
+}
+
+/// Stable display colour for a material, so distinct materials are
+/// distinguishable in a viewer.
+fn material_color(material: &CCollisionMaterial) -> [f32; 4] {
+    let hash = material
+        .material_type
+        .wrapping_mul(0x9E3779B1)
+        .wrapping_add(material.world_type.wrapping_mul(0x85EBCA6B))
+        .wrapping_add(material.behavior_list.wrapping_mul(0xC2B2AE35));
+    let channel = |shift: u32| ((hash >> shift) & 0xFF) as f32 / 255.0 * 0.75 + 0.25;
+    [channel(0), channel(8), channel(16), 1.0]
+}
+
+fn accessor(
+    buffer_view: u32,
+    byte_offset: u32,
+    count: u32,
+    component_type: json::accessor::ComponentType,
+    type_: json::accessor::Type,
+    name: String,
+) -> json::Accessor {
+    json::Accessor {
+        buffer_view: Some(json::Index::new(buffer_view)),
+        byte_offset,
+        count,
+        component_type: Valid(json::accessor::GenericComponentType(component_type)),
+        extensions: None,
+        extras: Default::default(),
+        type_: Valid(type_),
+        min: None,
+        max: None,
+        name: Some(name),
+        normalized: false,
+        sparse: None,
+    }
+}
+
+fn node(name: String) -> json::Node {
+    json::Node {
+        camera: None,
+        children: None,
+        extensions: None,
+        extras: None,
+        matrix: None,
+        mesh: None,
+        name: Some(name),
+        rotation: None,
+        scale: None,
+        translation: None,
+        skin: None,
+        weights: None,
+    }
+}
+
+/// Column-major matrices mapping the unit cube [-1, 1] onto each tree node's bounds.
+fn tree_node_matrices(tree: &CollisionTree) -> Vec<[f32; 16]> {
+    match tree {
+        CollisionTree::AABox(nodes) => nodes
+            .iter()
+            .map(|node| {
+                let (min, max) = (node.bounds.min, node.bounds.max);
+                #[rustfmt::skip]
+                let m = [
+                    (max.x - min.x) / 2.0, 0.0, 0.0, 0.0,
+                    0.0, (max.y - min.y) / 2.0, 0.0, 0.0,
+                    0.0, 0.0, (max.z - min.z) / 2.0, 0.0,
+                    (max.x + min.x) / 2.0, (max.y + min.y) / 2.0, (max.z + min.z) / 2.0, 1.0,
+                ];
+                m
+            })
+            .collect(),
+        CollisionTree::OBBox(nodes) => nodes
+            .iter()
+            .map(|node| {
+                let xf = mint::ColumnMatrix4::from(node.bounds.xf);
+                let ext = node.bounds.extents;
+                #[rustfmt::skip]
+                let m = [
+                    xf.x.x * ext.x, xf.x.y * ext.x, xf.x.z * ext.x, 0.0,
+                    xf.y.x * ext.y, xf.y.y * ext.y, xf.y.z * ext.y, 0.0,
+                    xf.z.x * ext.z, xf.z.y * ext.z, xf.z.z * ext.z, 0.0,
+                    xf.w.x, xf.w.y, xf.w.z, 1.0,
+                ];
+                m
+            })
+            .collect(),
+    }
+}
+
+fn write_gltf(collision: &CollisionData<LittleEndian>, out: &Path, tree: bool) -> Result<()> {
+    // Group triangles by unique material
+    let mut material_map: HashMap<&CCollisionMaterial, usize> = HashMap::new();
+    let mut materials: Vec<&CCollisionMaterial> = vec![];
+    let mut material_indices: Vec<Vec<u32>> = vec![];
+    for triangle in &collision.triangles {
+        let material = &collision.materials[triangle.material as usize];
+        let idx = *material_map.entry(material).or_insert_with(|| {
+            materials.push(material);
+            material_indices.push(vec![]);
+            materials.len() - 1
+        });
+        material_indices[idx].extend([triangle.idx1, triangle.idx2, triangle.idx3]);
+    }
+
+    // Buffer layout: positions, triangle indices, then the unit cube for tree nodes
+    let mut buf: Vec<u8> = vec![];
+    let mut min = [f32::MAX; 3];
+    let mut max = [f32::MIN; 3];
+    for vertex in &collision.vertices {
+        for (i, v) in [vertex.x, vertex.y, vertex.z].into_iter().enumerate() {
+            min[i] = min[i].min(v);
+            max[i] = max[i].max(v);
+            buf.extend(v.to_le_bytes());
+        }
+    }
+    let position_len = buf.len() as u32;
+    for indices in &material_indices {
+        buf.extend(indices.iter().flat_map(|i| i.to_le_bytes()));
+    }
+    let index_len = buf.len() as u32 - position_len;
+    if tree {
+        for i in 0..8 {
+            for bit in [1, 2, 4] {
+                buf.extend((if i & bit != 0 { 1.0f32 } else { -1.0f32 }).to_le_bytes());
+            }
         }
-        file.write_fmt(format_args!("\n# Triangles\n"))?;
-        for triangle in tris.triangles.iter() {
-            file.write_fmt(format_args!(
-                "f {} {} {}\n",
-                triangle.idx1 + 1,
-                triangle.idx2 + 1,
-                triangle.idx3 + 1
-            ))?;
+        for i in 0u16..8 {
+            for bit in [1, 2, 4] {
+                if i & bit == 0 {
+                    buf.extend(i.to_le_bytes());
+                    buf.extend((i | bit).to_le_bytes());
+                }
+            }
+        }
+    }
+    let bin_name = out.with_extension("bin");
+    fs::write(&bin_name, &buf)?;
+    let json_buffers = vec![json::Buffer {
+        byte_length: buf.len() as u32,
+        extensions: Default::default(),
+        extras: Default::default(),
+        name: None,
+        uri: bin_name.file_name().map(|n| n.to_string_lossy().into_owned()),
+    }];
+
+    let buffer_view = |offset: u32, length: u32, target, name: &str| json::buffer::View {
+        buffer: json::Index::new(0),
+        byte_length: length,
+        byte_offset: Some(offset),
+        byte_stride: None,
+        extensions: Default::default(),
+        extras: Default::default(),
+        name: Some(name.to_string()),
+        target: Some(Valid(target)),
+    };
+    let mut json_buffer_views = vec![
+        buffer_view(0, position_len, json::buffer::Target::ArrayBuffer, "Positions"),
+        buffer_view(position_len, index_len, json::buffer::Target::ElementArrayBuffer, "Indices"),
+    ];
+
+    let mut position_accessor = accessor(
+        0,
+        0,
+        collision.vertices.len() as u32,
+        json::accessor::ComponentType::F32,
+        json::accessor::Type::Vec3,
+        "Positions".to_string(),
+    );
+    position_accessor.min = Some(json!(min));
+    position_accessor.max = Some(json!(max));
+    let mut json_accessors = vec![position_accessor];
+
+    let mut json_materials = Vec::with_capacity(materials.len());
+    let mut primitives = Vec::with_capacity(materials.len());
+    let mut index_offset = 0u32;
+    for (idx, (material, indices)) in materials.iter().zip(&material_indices).enumerate() {
+        json_materials.push(json::Material {
+            alpha_cutoff: None,
+            alpha_mode: Valid(json::material::AlphaMode::Opaque),
+            double_sided: true,
+            name: Some(format!("Collision material {idx}")),
+            pbr_metallic_roughness: json::material::PbrMetallicRoughness {
+                base_color_factor: json::material::PbrBaseColorFactor(material_color(material)),
+                base_color_texture: None,
+                metallic_factor: json::material::StrengthFactor(0.0),
+                roughness_factor: json::material::StrengthFactor(1.0),
+                metallic_roughness_texture: None,
+                extensions: None,
+                extras: None,
+            },
+            normal_texture: None,
+            occlusion_texture: None,
+            emissive_texture: None,
+            emissive_factor: Default::default(),
+            extensions: None,
+            extras: material_extras(material)?,
+        });
+        primitives.push(json::mesh::Primitive {
+            attributes: [(Valid(json::mesh::Semantic::Positions), json::Index::new(0))].into(),
+            extensions: None,
+            extras: Default::default(),
+            indices: Some(json::Index::new(json_accessors.len() as u32)),
+            material: Some(json::Index::new(idx as u32)),
+            mode: Default::default(),
+            targets: None,
+        });
+        json_accessors.push(accessor(
+            1,
+            index_offset,
+            indices.len() as u32,
+            json::accessor::ComponentType::U32,
+            json::accessor::Type::Scalar,
+            format!("Material {idx} indices"),
+        ));
+        index_offset += indices.len() as u32 * 4;
+    }
+
+    let mut json_meshes = vec![json::Mesh {
+        extensions: None,
+        extras: Default::default(),
+        name: Some("Collision".to_string()),
+        primitives,
+        weights: None,
+    }];
+    let mut json_nodes =
+        vec![json::Node { mesh: Some(json::Index::new(0)), ..node("Collision".to_string()) }];
+    let mut json_scene_nodes = vec![json::Index::new(0)];
+
+    if tree && collision.tree().is_none() {
+        log::warn!("TREE chunk missing or not parsed, skipping tree nodes");
+    }
+    if let Some(col_tree) = collision.tree().filter(|t| tree && !t.is_empty()) {
+        // Unit cube line mesh, instanced by each tree node
+        let cube_offset = position_len + index_len;
+        json_buffer_views.push(buffer_view(
+            cube_offset,
+            8 * 12,
+            json::buffer::Target::ArrayBuffer,
+            "Box positions",
+        ));
+        json_buffer_views.push(buffer_view(
+            cube_offset + 8 * 12,
+            24 * 2,
+            json::buffer::Target::ElementArrayBuffer,
+            "Box indices",
+        ));
+        let mut cube_positions = accessor(
+            2,
+            0,
+            8,
+            json::accessor::ComponentType::F32,
+            json::accessor::Type::Vec3,
+            "Box positions".to_string(),
+        );
+        cube_positions.min = Some(json!([-1.0, -1.0, -1.0]));
+        cube_positions.max = Some(json!([1.0, 1.0, 1.0]));
+        json_accessors.push(cube_positions);
+        json_accessors.push(accessor(
+            3,
+            0,
+            24,
+            json::accessor::ComponentType::U16,
+            json::accessor::Type::Scalar,
+            "Box indices".to_string(),
+        ));
+        json_meshes.push(json::Mesh {
+            extensions: None,
+            extras: Default::default(),
+            name: Some("Box".to_string()),
+            primitives: vec![json::mesh::Primitive {
+                attributes: [(
+                    Valid(json::mesh::Semantic::Positions),
+                    json::Index::new(json_accessors.len() as u32 - 2),
+                )]
+                .into(),
+                extensions: None,
+                extras: Default::default(),
+                indices: Some(json::Index::new(json_accessors.len() as u32 - 1)),
+                material: None,
+                mode: Valid(json::mesh::Mode::Lines),
+                targets: None,
+            }],
+            weights: None,
+        });
+
+        // Each tree node is an empty carrying the node data, with the box as a child so
+        // that nested nodes don't inherit its transform.
+        let root_idx = json_nodes.len();
+        json_nodes.push(node("Tree".to_string()));
+        json_scene_nodes.push(json::Index::new(root_idx as u32));
+        let matrices = tree_node_matrices(col_tree);
+        let mut children: Vec<Vec<json::Index<json::Node>>> = vec![vec![]; matrices.len() + 1];
+        let mut stack: Vec<usize> = vec![];
+        for (idx, (depth, (start, end))) in
+            col_tree.depths().into_iter().zip(col_tree.ranges()).enumerate()
+        {
+            stack.truncate(depth as usize);
+            let parent = stack.last().map(|&p| p + 1).unwrap_or(0);
+            let (unk1, unk2, unk3, unk4) = match col_tree {
+                CollisionTree::AABox(nodes) => {
+                    let n = &nodes[idx];
+                    (n.unk1, n.unk2, n.unk3, n.unk4)
+                }
+                CollisionTree::OBBox(nodes) => {
+                    let n = &nodes[idx];
+                    (n.unk1, n.unk2, n.unk3, n.unk4)
+                }
+            };
+            let node_idx = json_nodes.len();
+            json_nodes.push(json::Node {
+                extras: Some(serde_json::value::to_raw_value(&json!({
+                    "depth": depth,
+                    "start": start,
+                    "end": end,
+                    "unk": [unk1, unk2, unk3, unk4],
+                }))?),
+                ..node(format!("Node {idx}"))
+            });
+            let box_idx = json_nodes.len();
+            json_nodes.push(json::Node {
+                matrix: Some(matrices[idx]),
+                mesh: Some(json::Index::new(1)),
+                ..node(format!("Node {idx} bounds"))
+            });
+            children[idx + 1].push(json::Index::new(box_idx as u32));
+            children[parent].push(json::Index::new(node_idx as u32));
+            stack.push(idx);
+        }
+        for (idx, list) in children.into_iter().enumerate() {
+            if list.is_empty() {
+                continue;
+            }
+            // Index 0 is the tree root, others map to the node preceding their box
+            let node_idx = if idx == 0 { root_idx } else { root_idx + 1 + (idx - 1) * 2 };
+            json_nodes[node_idx].children = Some(list);
+        }
+    }
+
+    let json_root = json::Root {
+        accessors: json_accessors,
+        animations: vec![],
+        asset: Default::default(),
+        buffers: json_buffers,
+        buffer_views: json_buffer_views,
+        scene: Some(json::Index::new(0)),
+        extensions: None,
+        extras: Default::default(),
+        extensions_used: vec![],
+        extensions_required: vec![],
+        cameras: vec![],
+        images: vec![],
+        materials: json_materials,
+        meshes: json_meshes,
+        nodes: json_nodes,
+        samplers: vec![],
+        scenes: vec![json::Scene {
+            extensions: Default::default(),
+            extras: Default::default(),
+            name: Some("Scene".into()),
+            nodes: json_scene_nodes,
+        }],
+        skins: vec![],
+        textures: vec![],
+    };
+    let writer = File::create(out)?;
+    json::serialize::to_writer_pretty(writer, &json_root)?;
+    Ok(())
+}
+
+fn import(args: ImportArgs) -> Result<()> {
+    // The tree layout and the node and triangle unk fields haven't been compared against retail
+    // files, so there's no evidence the game can traverse what we build.
+    ensure!(
+        args.experimental,
+        "Collision import is experimental: the TREE layout is unverified and unknown fields are \
+         written as 0. Pass --experimental to build anyway."
+    );
+    log::warn!("Collision import is experimental, the output may not work in game");
+    let (template_kind, footer) = {
+        let data = map_file(&args.template)?;
+        let (desc, _, _) = FormDescriptor::<LittleEndian>::slice(&data)?;
+        ensure!(desc.id == K_FORM_CLSN || desc.id == K_FORM_DCLN);
+        (desc.id, Footer::<LittleEndian>::slice(&data)?)
+    };
+    let kind = match args.kind.as_deref().map(|s| s.to_ascii_uppercase()).as_deref() {
+        None => template_kind,
+        Some("CLSN") => K_FORM_CLSN,
+        Some("DCLN") => K_FORM_DCLN,
+        Some(kind) => bail!("Unknown collision kind '{kind}'"),
+    };
+    let named_materials: HashMap<String, CCollisionMaterial> = match &args.materials {
+        Some(path) => {
+            let file = File::open(path)
+                .with_context(|| format!("Failed to open materials file '{}'", path.display()))?;
+            let map: HashMap<String, Value> = serde_json::from_reader(BufReader::new(file))?;
+            map.iter()
+                .map(|(k, v)| Ok((k.clone(), material_from_json(v)?)))
+                .collect::<Result<_>>()?
+        }
+        None => HashMap::new(),
+    };
+
+    let mut mesh = MeshBuilder::default();
+    if args.input.extension().map_or(false, |ext| ext.eq_ignore_ascii_case("obj")) {
+        read_obj(&args.input, &named_materials, &mut mesh)?;
+    } else {
+        read_gltf(&args.input, &named_materials, &mut mesh)?;
+    }
+    log::info!(
+        "Building {kind} with {} vertices, {} triangles, {} materials",
+        mesh.vertices.len(),
+        mesh.triangles.len(),
+        mesh.materials.len()
+    );
+    let collision =
+        CollisionData::<LittleEndian>::new(kind, mesh.vertices, mesh.materials, mesh.triangles)?;
+    log::info!("Tree: {} nodes", collision.tree().map_or(0, |t| t.len()));
+
+    let mut file =
+        BufWriter::new(File::create(&args.output).with_context(|| {
+            format!("Failed to create output file '{}'", args.output.display())
+        })?);
+    collision.write(&mut file)?;
+    footer.write(&mut file)?;
+    file.flush()?;
+    Ok(())
+}
+
+/// Reads collision material fields from an object, as written to glTF extras by `convert`.
+fn material_from_json(value: &Value) -> Result<CCollisionMaterial> {
+    let field = |name: &str| -> Result<u32> {
+        match value.get(name) {
+            Some(v) => v
+                .as_u64()
+                .and_then(|v| u32::try_from(v).ok())
+                .ok_or_else(|| anyhow!("Invalid material field {name}: {v}")),
+            None => Ok(0),
+        }
+    };
+    Ok(CCollisionMaterial {
+        orientation: field("orientation")?,
+        material_type: field("material_type")?,
+        world_type: field("world_type")?,
+        behavior_list: field("behavior_list")?,
+        filter_list: field("filter_list")?,
+    })
+}
+
+#[derive(Default)]
+struct MeshBuilder {
+    vertices: Vec<CVector3f>,
+    vertex_map: HashMap<[u32; 3], u32>,
+    materials: Vec<CCollisionMaterial>,
+    material_map: HashMap<CCollisionMaterial, u16>,
+    triangles: Vec<CIndexedTriangle>,
+}
+
+impl MeshBuilder {
+    /// Returns the index of a vertex, welding exact duplicates.
+    fn vertex(&mut self, v: [f32; 3]) -> u32 {
+        let key = v.map(f32::to_bits);
+        if let Some(&idx) = self.vertex_map.get(&key) {
+            return idx;
+        }
+        let idx = self.vertices.len() as u32;
+        self.vertices.push(v.into());
+        self.vertex_map.insert(key, idx);
+        idx
+    }
+
+    fn material(&mut self, material: &CCollisionMaterial) -> Result<u16> {
+        if let Some(&idx) = self.material_map.get(material) {
+            return Ok(idx);
+        }
+        let idx = u16::try_from(self.materials.len()).context("Too many materials")?;
+        self.materials.push(material.clone());
+        self.material_map.insert(material.clone(), idx);
+        Ok(idx)
+    }
+
+    fn triangle(&mut self, v: [[f32; 3]; 3], material: u16) {
+        let [idx1, idx2, idx3] = v.map(|v| self.vertex(v));
+        if idx1 == idx2 || idx2 == idx3 || idx1 == idx3 {
+            // Degenerate
+            return;
+        }
+        self.triangles.push(CIndexedTriangle { idx1, idx2, idx3, material, unk: 0 });
+    }
+}
+
+fn lookup_material(
+    name: Option<&str>,
+    extras: Option<&Value>,
+    named_materials: &HashMap<String, CCollisionMaterial>,
+) -> Result<CCollisionMaterial> {
+    if let Some(material) = name.and_then(|n| named_materials.get(n)) {
+        return Ok(material.clone());
+    }
+    if let Some(extras) = extras {
+        return material_from_json(extras);
+    }
+    log::warn!("No collision material for '{}', using defaults", name.unwrap_or("<none>"));
+    Ok(CCollisionMaterial::default())
+}
+
+fn read_obj(
+    path: &Path,
+    named_materials: &HashMap<String, CCollisionMaterial>,
+    mesh: &mut MeshBuilder,
+) -> Result<()> {
+    let file = File::open(path)
+        .with_context(|| format!("Failed to open input file '{}'", path.display()))?;
+    let mut positions: Vec<[f32; 3]> = vec![];
+    // Faces before any usemtl get the default material, registered on first use
+    let mut material: Option<u16> = None;
+    for (line_num, line) in BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        let mut parts = line.split_whitespace();
+        match parts.next() {
+            Some("v") => {
+                let mut v = [0.0f32; 3];
+                for c in &mut v {
+                    *c = parts
+                        .next()
+                        .and_then(|s| s.parse().ok())
+                        .ok_or_else(|| anyhow!("Invalid vertex on line {}", line_num + 1))?;
+                }
+                positions.push(v);
+            }
+            Some("usemtl") => {
+                let name = parts.collect::<Vec<_>>().join(" ");
+                let value = lookup_material(Some(&name), None, named_materials)?;
+                material = Some(mesh.material(&value)?);
+            }
+            Some("f") => {
+                let indices = parts
+                    .map(|s| {
+                        // v, v/vt, v/vt/vn or v//vn; negative indices are relative
+                        let idx: i64 = s.split('/').next().unwrap_or_default().parse()?;
+                        let idx = if idx < 0 { positions.len() as i64 + idx } else { idx - 1 };
+                        positions
+                            .get(idx as usize)
+                            .copied()
+                            .ok_or_else(|| anyhow!("Invalid face index on line {}", line_num + 1))
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                ensure!(indices.len() >= 3, "Invalid face on line {}", line_num + 1);
+                let material = match material {
+                    Some(material) => material,
+                    None => {
+                        log::warn!("Faces without usemtl, using default collision material");
+                        *material.insert(mesh.material(&CCollisionMaterial::default())?)
+                    }
+                };
+                for i in 1..indices.len() - 1 {
+                    mesh.triangle([indices[0], indices[i], indices[i + 1]], material);
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+fn read_gltf(
+    path: &Path,
+    named_materials: &HashMap<String, CCollisionMaterial>,
+    mesh: &mut MeshBuilder,
+) -> Result<()> {
+    let gltf = GltfInput::open(path)?;
+    for (node_idx, xf) in gltf.mesh_instances()? {
+        let Some(mesh_idx) = gltf.node(node_idx)?.mesh else {
+            continue;
+        };
+        for primitive in &gltf.mesh(mesh_idx)?.primitives {
+            // Skip non-triangle primitives, such as exported tree nodes
+            let Valid(json::mesh::Mode::Triangles) = primitive.mode else {
+                continue;
+            };
+            let Some(&positions) =
+                primitive.attributes.get(&Valid(json::mesh::Semantic::Positions))
+            else {
+                continue;
+            };
+            let positions = gltf
+                .read_accessor(positions)?
+                .into_iter()
+                .map(|p| match p[..] {
+                    [x, y, z] => Ok(transform_point(&xf, [x, y, z])),
+                    _ => Err(anyhow!("Positions in mesh {} aren't 3D", mesh_idx.value())),
+                })
+                .collect::<Result<Vec<_>>>()?;
+            let indices = gltf.read_indices(primitive.indices, positions.len())?;
+            let material = match primitive.material {
+                Some(idx) => {
+                    let material = gltf.material(idx)?;
+                    let extras = match &material.extras {
+                        Some(raw) => Some(serde_json::from_str::<Value>(raw.get())?),
+                        None => None,
+                    };
+                    lookup_material(material.name.as_deref(), extras.as_ref(), named_materials)?
+                }
+                None => lookup_material(None, None, named_materials)?,
+            };
+            let material = mesh.material(&material)?;
+            for tri in indices.chunks_exact(3) {
+                let v = [tri[0], tri[1], tri[2]].map(|i| positions.get(i as usize).copied());
+                let [Some(a), Some(b), Some(c)] = v else {
+                    bail!("Invalid index in mesh {}", mesh_idx.value());
+                };
+                mesh.triangle([a, b, c], material);
+            }
         }
     }
     Ok(())
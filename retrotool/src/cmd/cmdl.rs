@@ -2,11 +2,11 @@ use std::{
     collections::HashMap,
     fs,
     fs::{DirBuilder, File},
-    io::{Cursor, Read, Write},
+    io::{BufWriter, Cursor, Read, Write},
     path::{Path, PathBuf},
 };
 
-use anyhow::{bail, ensure, Result};
+use anyhow::{bail, ensure, Context, Result};
 use argh::FromArgs;
 use binrw::{binrw, BinReaderExt, BinWriterExt, Endian};
 use gltf_json as json;
@@ -17,11 +17,14 @@ use png::SrgbRenderingIntent;
 use retrolib::{
     format::{
         cmdl::{
-            CMaterialDataInner, CMaterialTextureTokenData, EBufferType, EMaterialDataId,
-            EVertexComponent, EVertexDataFormat, ModelData,
+            CMaterialDataInner, CMaterialTextureTokenData, CRenderMesh, EBufferType,
+            EMaterialDataId, EVertexComponent, EVertexDataFormat, ModelData,
+            SIndexBufferInfoSection, SLodInfoInner, SLodInfoOuter, SMeshLoadInformation,
+            SModelHeader, SModelMetaData, SVertexBufferInfo, SVertexBufferInfoSection, K_FORM_SMDL,
         },
-        foot::locate_meta,
+        foot::{locate_meta, Footer},
         txtr::{decompress_image, slice_texture, TextureData},
+        CAABox, CVector3f,
     },
     util::file::map_file,
 };
@@ -29,6 +32,8 @@ use serde_json::json;
 use uuid::Uuid;
 use zerocopy::LittleEndian;
 
+use crate::util::gltf::{transform_point, transform_vector, GltfInput};
+
 #[derive(FromArgs, PartialEq, Debug)]
 /// process CMDL files
 #[argh(subcommand, name = "cmdl")]
@@ -41,6 +46,7 @@ pub struct Args {
 #[argh(subcommand)]
 enum SubCommand {
     Convert(ConvertArgs),
+    Import(ImportArgs),
 }
 
 #[derive(FromArgs, PartialEq, Eq, Debug)]
@@ -55,9 +61,25 @@ pub struct ConvertArgs {
     out_dir: PathBuf,
 }
 
+#[derive(FromArgs, PartialEq, Eq, Debug)]
+/// builds a CMDL/WMDL from glTF
+#[argh(subcommand, name = "import")]
+pub struct ImportArgs {
+    #[argh(positional)]
+    /// input glTF
+    input: PathBuf,
+    #[argh(positional)]
+    /// original model (materials, vertex layouts, metadata and footer are reused)
+    template: PathBuf,
+    #[argh(positional)]
+    /// output model
+    output: PathBuf,
+}
+
 pub fn run(args: Args) -> Result<()> {
     match args.command {
         SubCommand::Convert(c_args) => convert(c_args),
+        SubCommand::Import(c_args) => import(c_args),
     }
 }
 
@@ -380,72 +402,7 @@ fn convert(args: ConvertArgs) -> Result<()> {
                 };
                 let accessor_idx = json_accessors.len();
                 json_accessors.push(accessor);
-                let semantic = match attribute.component {
-                    EVertexComponent::Position => json::mesh::Semantic::Positions,
-                    EVertexComponent::Normal => json::mesh::Semantic::Normals,
-                    EVertexComponent::Tangent0 => json::mesh::Semantic::Tangents,
-                    EVertexComponent::Tangent1 => json::mesh::Semantic::Extras("TANGENT_1".into()),
-                    EVertexComponent::Tangent2 => json::mesh::Semantic::Extras("TANGENT_2".into()),
-                    EVertexComponent::TexCoord0 => json::mesh::Semantic::TexCoords(0),
-                    EVertexComponent::TexCoord1 => json::mesh::Semantic::TexCoords(1),
-                    EVertexComponent::TexCoord2 => json::mesh::Semantic::TexCoords(2),
-                    EVertexComponent::TexCoord3 => json::mesh::Semantic::TexCoords(3),
-                    // EVertexComponent::Color => json::mesh::Semantic::Colors(0),
-                    EVertexComponent::BoneIndices => json::mesh::Semantic::Joints(0),
-                    EVertexComponent::BoneWeights => json::mesh::Semantic::Weights(0),
-                    EVertexComponent::BakedLightingCoord => {
-                        json::mesh::Semantic::Extras("BAKED_LIGHTING_COORD".into())
-                    }
-                    EVertexComponent::BakedLightingTangent => {
-                        json::mesh::Semantic::Extras("BAKED_LIGHTING_TANGENT".into())
-                    }
-                    EVertexComponent::VertInstanceParams => {
-                        json::mesh::Semantic::Extras("VERT_INSTANCE_PARAMS".into())
-                    }
-                    EVertexComponent::VertInstanceColor => {
-                        json::mesh::Semantic::Extras("VERT_INSTANCE_COLOR".into())
-                    }
-                    EVertexComponent::VertTransform0 => {
-                        json::mesh::Semantic::Extras("VERT_TRANSFORM_0".into())
-                    }
-                    EVertexComponent::VertTransform1 => {
-                        json::mesh::Semantic::Extras("VERT_TRANSFORM_1".into())
-                    }
-                    EVertexComponent::VertTransform2 => {
-                        json::mesh::Semantic::Extras("VERT_TRANSFORM_2".into())
-                    }
-                    EVertexComponent::CurrentPosition => {
-                        json::mesh::Semantic::Extras("CURRENT_POSITION".into())
-                    }
-                    EVertexComponent::VertInstanceOpacityParams => {
-                        json::mesh::Semantic::Extras("VERT_INSTANCE_OPACITY_PARAMS".into())
-                    }
-                    EVertexComponent::VertInstanceColorIndexingParams => {
-                        json::mesh::Semantic::Extras("VERT_INSTANCE_COLOR_INDEXING_PARAMS".into())
-                    }
-                    EVertexComponent::VertInstanceOpacityIndexingParams => {
-                        json::mesh::Semantic::Extras("VERT_INSTANCE_OPACITY_INDEXING_PARAMS".into())
-                    }
-                    EVertexComponent::VertInstancePaintParams => {
-                        json::mesh::Semantic::Extras("VERT_INSTANCE_PAINT_PARAMS".into())
-                    }
-                    EVertexComponent::BakedLightingLookup => {
-                        json::mesh::Semantic::Extras("BAKED_LIGHTING_LOOKUP".into())
-                    }
-                    EVertexComponent::MaterialChoice0 => {
-                        json::mesh::Semantic::Extras("MATERIAL_CHOICE_0".into())
-                    }
-                    EVertexComponent::MaterialChoice1 => {
-                        json::mesh::Semantic::Extras("MATERIAL_CHOICE_1".into())
-                    }
-                    EVertexComponent::MaterialChoice2 => {
-                        json::mesh::Semantic::Extras("MATERIAL_CHOICE_2".into())
-                    }
-                    EVertexComponent::MaterialChoice3 => {
-                        json::mesh::Semantic::Extras("MATERIAL_CHOICE_3".into())
-                    }
-                    _ => continue,
-                };
+                let semantic = component_semantic(attribute.component);
                 attribute_map.insert(Valid(semantic), json::Index::new(accessor_idx as u32));
             }
         }
@@ -824,6 +781,7 @@ fn convert(args: ConvertArgs) -> Result<()> {
     let mut json_scene_nodes = Vec::with_capacity(json_meshes.len());
     let mut json_nodes = Vec::with_capacity(json_meshes.len());
     for (idx, _) in json_meshes.iter().enumerate() {
+        json_scene_nodes.push(json::Index::new(json_nodes.len() as u32));
         json_nodes.push(json::Node {
             camera: None,
             children: None,
@@ -838,7 +796,6 @@ fn convert(args: ConvertArgs) -> Result<()> {
             skin: None,
             weights: None,
         });
-        json_scene_nodes.push(json::Index::new(idx as u32));
     }
 
     let json_root = json::Root {
@@ -872,3 +829,311 @@ fn convert(args: ConvertArgs) -> Result<()> {
 
     Ok(())
 }
+
+/// glTF attribute semantic for a vertex component, used by both `convert` and `import`.
+/// Components without a standard glTF semantic use a custom (underscore-prefixed) one.
+fn component_semantic(component: EVertexComponent) -> json::mesh::Semantic {
+    use json::mesh::Semantic;
+    let extras = |name: &str| Semantic::Extras(name.into());
+    match component {
+        EVertexComponent::Position => Semantic::Positions,
+        EVertexComponent::Normal => Semantic::Normals,
+        EVertexComponent::Tangent0 => Semantic::Tangents,
+        EVertexComponent::Tangent1 => extras("TANGENT_1"),
+        EVertexComponent::Tangent2 => extras("TANGENT_2"),
+        EVertexComponent::TexCoord0 => Semantic::TexCoords(0),
+        EVertexComponent::TexCoord1 => Semantic::TexCoords(1),
+        EVertexComponent::TexCoord2 => Semantic::TexCoords(2),
+        EVertexComponent::TexCoord3 => Semantic::TexCoords(3),
+        // Not exported as COLOR_0, since the stored formats aren't all valid for it
+        EVertexComponent::Color => extras("COLOR"),
+        // Not JOINTS_0/WEIGHTS_0: the indices are into an SKHD bone palette that isn't decoded,
+        // and a glTF skin can't be built without it
+        EVertexComponent::BoneIndices => extras("BONE_INDICES"),
+        EVertexComponent::BoneWeights => extras("BONE_WEIGHTS"),
+        EVertexComponent::BakedLightingCoord => extras("BAKED_LIGHTING_COORD"),
+        EVertexComponent::BakedLightingTangent => extras("BAKED_LIGHTING_TANGENT"),
+        EVertexComponent::VertInstanceParams => extras("VERT_INSTANCE_PARAMS"),
+        EVertexComponent::VertInstanceColor => extras("VERT_INSTANCE_COLOR"),
+        EVertexComponent::VertTransform0 => extras("VERT_TRANSFORM_0"),
+        EVertexComponent::VertTransform1 => extras("VERT_TRANSFORM_1"),
+        EVertexComponent::VertTransform2 => extras("VERT_TRANSFORM_2"),
+        EVertexComponent::CurrentPosition => extras("CURRENT_POSITION"),
+        EVertexComponent::VertInstanceOpacityParams => extras("VERT_INSTANCE_OPACITY_PARAMS"),
+        EVertexComponent::VertInstanceColorIndexingParams => {
+            extras("VERT_INSTANCE_COLOR_INDEXING_PARAMS")
+        }
+        EVertexComponent::VertInstanceOpacityIndexingParams => {
+            extras("VERT_INSTANCE_OPACITY_INDEXING_PARAMS")
+        }
+        EVertexComponent::VertInstancePaintParams => extras("VERT_INSTANCE_PAINT_PARAMS"),
+        EVertexComponent::BakedLightingLookup => extras("BAKED_LIGHTING_LOOKUP"),
+        EVertexComponent::MaterialChoice0 => extras("MATERIAL_CHOICE_0"),
+        EVertexComponent::MaterialChoice1 => extras("MATERIAL_CHOICE_1"),
+        EVertexComponent::MaterialChoice2 => extras("MATERIAL_CHOICE_2"),
+        EVertexComponent::MaterialChoice3 => extras("MATERIAL_CHOICE_3"),
+    }
+}
+
+fn import(args: ImportArgs) -> Result<()> {
+    let (template, template_meta, footer) = {
+        let data = map_file(&args.template)?;
+        let meta = locate_meta::<LittleEndian>(&data)?;
+        let template = ModelData::<LittleEndian>::slice(&data, meta)?;
+        let meta: SModelMetaData = Cursor::new(meta).read_type(Endian::Little)?;
+        (template, meta, Footer::<LittleEndian>::slice(&data)?)
+    };
+    // SKHD holds per-mesh bone palettes that would no longer match the rebuilt meshes
+    ensure!(
+        template.kind != K_FORM_SMDL,
+        "SMDL templates aren't supported, since the skinning data isn't understood yet"
+    );
+    let gltf = GltfInput::open(&args.input)?;
+
+    // LOD 0 bucket for each template mesh
+    let template_buckets = template.mesh.lod_info.first().map(|lod| {
+        let mut buckets = vec![0usize; template.mesh.meshes.len()];
+        for (bucket, inner) in lod.inner.iter().enumerate() {
+            let range = inner.offset as usize..inner.offset as usize + inner.count as usize;
+            for &mesh_idx in template.mesh.shorts.get(range).unwrap_or_default() {
+                if let Some(b) = buckets.get_mut(mesh_idx as usize) {
+                    *b = bucket;
+                }
+            }
+        }
+        buckets
+    });
+    let template_unk_1 = |idx: usize| -> Result<u8> {
+        let byte =
+            template.mesh.unk_data_1.get(idx / 4).context("Template unk_data_1 too short")?;
+        Ok((byte >> ((idx % 4) * 2)) & 3)
+    };
+    let template_unk_2 = |idx: usize| -> Result<u8> {
+        let byte =
+            template.mesh.unk_data_2.get(idx / 8).context("Template unk_data_2 too short")?;
+        Ok((byte >> (idx % 8)) & 1)
+    };
+    ensure!(!template.mesh.meshes.is_empty(), "Template model has no meshes");
+    let Some(default_material) = template.mtrl.materials.first() else {
+        bail!("Template model has no materials");
+    };
+
+    let mut bounds = CAABox::default();
+    let mut vtx_infos = Vec::new();
+    let mut vtx_buffers = Vec::new();
+    let mut idx_infos = Vec::new();
+    let mut idx_buffers = Vec::new();
+    // (template mesh index, new mesh)
+    let mut meshes: Vec<(usize, CRenderMesh)> = Vec::new();
+    for (node_idx, xf) in gltf.mesh_instances()? {
+        let Some(mesh_idx) = gltf.node(node_idx)?.mesh else {
+            continue;
+        };
+        for primitive in &gltf.mesh(mesh_idx)?.primitives {
+            let Valid(json::mesh::Mode::Triangles) = primitive.mode else {
+                log::warn!("Skipping non-triangle primitive in mesh {}", mesh_idx.value());
+                continue;
+            };
+            let material_name = match primitive.material {
+                Some(idx) => gltf.material(idx)?.name.as_deref(),
+                None => None,
+            };
+            let material_idx = match material_name
+                .and_then(|name| template.mtrl.materials.iter().position(|m| m.name == name))
+            {
+                Some(idx) => idx,
+                None => {
+                    log::warn!(
+                        "Material {:?} not found in template, using {}",
+                        material_name,
+                        default_material.name
+                    );
+                    0
+                }
+            };
+            // Reuse the vertex layout and flags of a template mesh with the same material
+            let template_idx = template
+                .mesh
+                .meshes
+                .iter()
+                .position(|m| m.material_idx as usize == material_idx)
+                .unwrap_or_default();
+            let template_mesh = &template.mesh.meshes[template_idx];
+            let layout =
+                template.vbuf.info.get(template_mesh.vtx_buf_idx as usize).with_context(|| {
+                    format!("Invalid vertex buffer in template mesh {template_idx}")
+                })?;
+
+            let Some(&positions) =
+                primitive.attributes.get(&Valid(json::mesh::Semantic::Positions))
+            else {
+                bail!("Mesh {} primitive without positions", mesh_idx.value());
+            };
+            let vertex_count = gltf
+                .root
+                .accessors
+                .get(positions.value())
+                .with_context(|| format!("Invalid accessor {}", positions.value()))?
+                .count as usize;
+            let mut buffers = vec![Vec::<u8>::new(); layout.num_buffers as usize];
+            for (idx, buf) in buffers.iter_mut().enumerate() {
+                let stride = layout
+                    .components
+                    .iter()
+                    .find(|c| c.buffer_index as usize == idx)
+                    .map_or(0, |c| c.stride as usize);
+                *buf = vec![0u8; vertex_count * stride];
+            }
+            for component in &layout.components {
+                let semantic = component_semantic(component.component);
+                let values = match primitive.attributes.get(&Valid(semantic)) {
+                    Some(&accessor) => gltf.read_accessor(accessor)?,
+                    None => {
+                        log::warn!(
+                            "Mesh {} missing {:?}, filling with zeroes",
+                            mesh_idx.value(),
+                            component.component
+                        );
+                        vec![vec![]; vertex_count]
+                    }
+                };
+                ensure!(values.len() == vertex_count, "Mismatched attribute counts");
+                let buf = buffers
+                    .get_mut(component.buffer_index as usize)
+                    .context("Invalid vertex buffer index in template layout")?;
+                let mut tmp = Vec::with_capacity(16);
+                for (i, mut value) in values.into_iter().enumerate() {
+                    match component.component {
+                        EVertexComponent::Position => {
+                            let [x, y, z] = value[..] else {
+                                bail!("Positions in mesh {} aren't 3D", mesh_idx.value());
+                            };
+                            let p = transform_point(&xf, [x, y, z]);
+                            bounds.min = CVector3f::new(
+                                bounds.min.x.min(p[0]),
+                                bounds.min.y.min(p[1]),
+                                bounds.min.z.min(p[2]),
+                            );
+                            bounds.max = CVector3f::new(
+                                bounds.max.x.max(p[0]),
+                                bounds.max.y.max(p[1]),
+                                bounds.max.z.max(p[2]),
+                            );
+                            value[..3].copy_from_slice(&p);
+                        }
+                        EVertexComponent::Normal | EVertexComponent::Tangent0
+                            if value.len() >= 3 =>
+                        {
+                            let v = transform_vector(&xf, [value[0], value[1], value[2]]);
+                            let len = (v[0] * v[0] + v[1] * v[1] + v[2] * v[2]).sqrt();
+                            let len = if len > 0.0 { len } else { 1.0 };
+                            value[..3].copy_from_slice(&v.map(|c| c / len));
+                        }
+                        _ => {}
+                    }
+                    tmp.clear();
+                    component.format.encode(&value, &mut tmp)?;
+                    let offset = i * component.stride as usize + component.offset as usize;
+                    buf.get_mut(offset..offset + tmp.len())
+                        .context("Vertex component outside of its stride")?
+                        .copy_from_slice(&tmp);
+                }
+            }
+
+            let indices = gltf.read_indices(primitive.indices, vertex_count)?;
+            ensure!(indices.iter().all(|&i| (i as usize) < vertex_count), "Invalid index");
+            let (index_type, index_data) = if vertex_count <= u16::MAX as usize + 1 {
+                (EBufferType::U16, indices.iter().flat_map(|&i| (i as u16).to_le_bytes()).collect())
+            } else {
+                (EBufferType::U32, indices.iter().flat_map(|&i| i.to_le_bytes()).collect())
+            };
+
+            ensure!(vtx_infos.len() < u8::MAX as usize, "Too many primitives");
+            meshes.push((template_idx, CRenderMesh {
+                material_idx: material_idx as u16,
+                vtx_buf_idx: vtx_infos.len() as u8,
+                idx_buf_idx: idx_infos.len() as u8,
+                index_start: 0,
+                index_count: indices.len() as u32,
+                unk_c: template_mesh.unk_c,
+                unk_e: template_mesh.unk_e,
+            }));
+            vtx_infos.push(SVertexBufferInfo {
+                vertex_count: vertex_count as u32,
+                components: layout.components.clone(),
+                num_buffers: layout.num_buffers,
+            });
+            vtx_buffers.extend(buffers);
+            idx_infos.push(index_type);
+            idx_buffers.push(index_data);
+        }
+    }
+    ensure!(!meshes.is_empty(), "No triangle meshes found in {}", args.input.display());
+
+    let mut unk_data_1 = vec![0u8; (meshes.len() + 3) / 4];
+    let mut unk_data_2 = vec![0u8; (meshes.len() + 7) / 8];
+    for (idx, &(template_idx, _)) in meshes.iter().enumerate() {
+        unk_data_1[idx / 4] |= template_unk_1(template_idx)? << ((idx % 4) * 2);
+        unk_data_2[idx / 8] |= template_unk_2(template_idx)? << (idx % 8);
+    }
+    // Everything is placed in a single LOD, using the template mesh buckets. The template's
+    // LODs and LOD rules aren't carried over.
+    if template.mesh.lod_info.len() > 1 || !template.mesh.lod_rules.is_empty() {
+        log::warn!(
+            "Template has {} LODs, the output only has one and no LOD rules",
+            template.mesh.lod_info.len()
+        );
+    }
+    let mut shorts = Vec::with_capacity(meshes.len());
+    let mut lod_info = Vec::new();
+    if let Some(buckets) = &template_buckets {
+        let inner = std::array::from_fn(|bucket| {
+            let offset = shorts.len() as u32;
+            shorts.extend(
+                meshes
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, (template_idx, _))| buckets[*template_idx] == bucket)
+                    .map(|(idx, _)| idx as u16),
+            );
+            SLodInfoInner { offset, count: shorts.len() as u32 - offset }
+        });
+        lod_info.push(SLodInfoOuter { inner });
+    }
+    let mesh = SMeshLoadInformation {
+        meshes: meshes.into_iter().map(|(_, mesh)| mesh).collect(),
+        unk_data_1,
+        unk_data_2,
+        shorts,
+        lod_count: lod_info.len() as u8,
+        lod_info,
+        lod_rules: vec![],
+    };
+    log::info!(
+        "Building {} with {} meshes, {} materials",
+        template.kind,
+        mesh.meshes.len(),
+        template.mtrl.materials.len()
+    );
+
+    let head = SModelHeader { bounds, ..template.head.clone() };
+    let model = ModelData::<LittleEndian>::new(
+        template.kind,
+        head,
+        template.mtrl.clone(),
+        mesh,
+        SVertexBufferInfoSection { info: vtx_infos },
+        SIndexBufferInfoSection { info: idx_infos },
+        vtx_buffers,
+        idx_buffers,
+    );
+    let mut file =
+        BufWriter::new(File::create(&args.output).with_context(|| {
+            format!("Failed to create output file '{}'", args.output.display())
+        })?);
+    let meta = SModelMetaData { unk: template_meta.unk, ..model.write(&mut file)? };
+
+    footer.write_with_meta(&mut file, &meta)?;
+    file.flush()?;
+    Ok(())
+}
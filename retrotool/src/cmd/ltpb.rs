@@ -0,0 +1,256 @@
+use std::{
+    fs::{DirBuilder, File},
+    io::{BufReader, BufWriter, Cursor, Write},
+    path::PathBuf,
+};
+
+use anyhow::{anyhow, ensure, Context, Result};
+use argh::FromArgs;
+use binrw::{BinReaderExt, Endian};
+use retrolib::{
+    format::{
+        foot::{locate_meta, Footer},
+        ltpb::{
+            CBakedLightingUniformProbeGridIndex, LightProbeData, LightProbeExtra,
+            SLightProbeMetaData, K_FORM_LTPB,
+        },
+        rfrm::FormDescriptor,
+        txtr::{calculate_mip_sizes, STextureHeader, TextureData},
+        CVector3i,
+    },
+    util::{
+        dds::{read_dds, write_dds},
+        file::map_file,
+    },
+};
+use serde_json::{json, Value};
+use zerocopy::LittleEndian;
+
+#[derive(FromArgs, PartialEq, Debug)]
+/// process LTPB files
+#[argh(subcommand, name = "ltpb")]
+pub struct Args {
+    #[argh(subcommand)]
+    command: SubCommand,
+}
+
+#[derive(FromArgs, PartialEq, Debug)]
+#[argh(subcommand)]
+enum SubCommand {
+    Extract(ExtractArgs),
+    Import(ImportArgs),
+}
+
+#[derive(FromArgs, PartialEq, Eq, Debug)]
+/// extracts light probe textures to DDS, with a JSON sidecar
+#[argh(subcommand, name = "extract")]
+pub struct ExtractArgs {
+    #[argh(positional)]
+    /// input LTPB
+    input: PathBuf,
+    #[argh(positional)]
+    /// output directory
+    out_dir: PathBuf,
+}
+
+#[derive(FromArgs, PartialEq, Eq, Debug)]
+/// rebuilds an LTPB from extracted DDS files and JSON sidecar
+#[argh(subcommand, name = "import")]
+pub struct ImportArgs {
+    #[argh(positional)]
+    /// input directory (from ltpb extract)
+    in_dir: PathBuf,
+    #[argh(positional)]
+    /// original LTPB (header, sampler data, metadata and footer are reused)
+    template: PathBuf,
+    #[argh(positional)]
+    /// output LTPB
+    output: PathBuf,
+}
+
+pub fn run(args: Args) -> Result<()> {
+    match args.command {
+        SubCommand::Extract(c_args) => extract(c_args),
+        SubCommand::Import(c_args) => import(c_args),
+    }
+}
+
+fn grid_index_json(idx: &CBakedLightingUniformProbeGridIndex) -> Value {
+    json!({ "x": idx.x, "y": idx.y, "z": idx.z })
+}
+
+fn extract(args: ExtractArgs) -> Result<()> {
+    let data = map_file(&args.input)?;
+    let meta = locate_meta::<LittleEndian>(&data)?;
+    let ltpb = LightProbeData::<LittleEndian>::slice(&data, meta)?;
+    log::info!("Light probe bundle: {} textures", ltpb.textures.len());
+
+    DirBuilder::new().recursive(true).create(&args.out_dir)?;
+    let mut probes = Vec::with_capacity(ltpb.textures.len());
+    for (idx, texture) in ltpb.textures.iter().enumerate() {
+        let head = &texture.head;
+        let file_name = format!("probe_{idx:03}.dds");
+        let path = args.out_dir.join(&file_name);
+        log::info!(
+            "Writing {} ({} {} {}x{}x{}, {} mips)",
+            path.display(),
+            head.kind,
+            head.format,
+            head.width,
+            head.height,
+            head.layers,
+            head.mip_sizes.len()
+        );
+        let mut file = BufWriter::new(
+            File::create(&path)
+                .with_context(|| format!("Failed to create output file '{}'", path.display()))?,
+        );
+        write_dds(&mut file, head, texture.data.clone())?;
+        file.flush()?;
+
+        let extra = &ltpb.extra[idx];
+        // The extra vector is only assumed to be a grid index, so bad values aren't fatal
+        let grid_index = match ltpb.grid_index(idx) {
+            Ok(grid_index) => grid_index_json(&grid_index),
+            Err(e) => {
+                log::warn!("{e}");
+                Value::Null
+            }
+        };
+        probes.push(json!({
+            "file": file_name,
+            "kind": head.kind.to_string(),
+            "format": head.format.to_string(),
+            "width": head.width,
+            "height": head.height,
+            "layers": head.layers,
+            "mips": head.mip_sizes.len(),
+            "grid_index": grid_index,
+            "extra": {
+                "vec": [extra.vec.x, extra.vec.y, extra.vec.z],
+                "unk": extra.unk,
+            },
+        }));
+    }
+
+    let head = &ltpb.head;
+    let sidecar = json!({
+        "header": {
+            "unk1": head.unk1,
+            "unk2": head.unk2,
+            "unk_vec": [head.unk_vec.x, head.unk_vec.y, head.unk_vec.z],
+            "grid_idx1": grid_index_json(&head.grid_idx1),
+            "grid_idx2": grid_index_json(&head.grid_idx2),
+        },
+        "probes": probes,
+    });
+    let path = args.out_dir.join("probes.json");
+    let file = File::create(&path)
+        .with_context(|| format!("Failed to create output file '{}'", path.display()))?;
+    serde_json::to_writer_pretty(BufWriter::new(file), &sidecar)?;
+    Ok(())
+}
+
+fn import(args: ImportArgs) -> Result<()> {
+    let (template, template_meta, footer) = {
+        let data = map_file(&args.template)?;
+        let meta = locate_meta::<LittleEndian>(&data)?;
+        let ltpb = LightProbeData::<LittleEndian>::slice(&data, meta)?;
+        let ltpb_meta: SLightProbeMetaData = Cursor::new(meta).read_type(Endian::Little)?;
+        let (ltpb_desc, _, _) = FormDescriptor::<LittleEndian>::slice(&data)?;
+        ensure!(ltpb_desc.id == K_FORM_LTPB);
+        (ltpb, ltpb_meta, Footer::<LittleEndian>::slice(&data)?)
+    };
+    ensure!(!template.textures.is_empty(), "Template LTPB has no probes");
+
+    let path = args.in_dir.join("probes.json");
+    let file = File::open(&path)
+        .with_context(|| format!("Failed to open input file '{}'", path.display()))?;
+    let sidecar: Value = serde_json::from_reader(BufReader::new(file))?;
+    let probes = sidecar["probes"]
+        .as_array()
+        .ok_or_else(|| anyhow!("'{}' has no probes array", path.display()))?;
+
+    let mut textures = Vec::with_capacity(probes.len());
+    let mut extra = Vec::with_capacity(probes.len());
+    for (idx, probe) in probes.iter().enumerate() {
+        let file_name =
+            probe["file"].as_str().ok_or_else(|| anyhow!("Probe {idx} has no file name"))?;
+        let path = args.in_dir.join(file_name);
+        let mut file = File::open(&path)
+            .with_context(|| format!("Failed to open input file '{}'", path.display()))?;
+        let dds = read_dds(&mut file)
+            .with_context(|| format!("Failed to read DDS '{}'", path.display()))?;
+
+        // Sampler data and tiling come from the matching template probe
+        let template_head = &template.textures[idx.min(template.textures.len() - 1)].head;
+        let head = STextureHeader {
+            kind: dds.kind,
+            format: dds.format,
+            width: dds.width,
+            height: dds.height,
+            layers: dds.layers,
+            tile_mode: template_head.tile_mode,
+            swizzle: template_head.swizzle,
+            mip_sizes: calculate_mip_sizes(
+                dds.kind,
+                dds.format,
+                dds.width,
+                dds.height,
+                dds.layers,
+                dds.mip_count,
+            ),
+            sampler_data: template_head.sampler_data.clone(),
+        };
+        log::info!(
+            "Probe {idx}: {} {} {}x{}x{}, {} mips",
+            head.kind,
+            head.format,
+            head.width,
+            head.height,
+            head.layers,
+            head.mip_sizes.len()
+        );
+        textures.push(TextureData::<LittleEndian>::new(head, dds.data));
+        extra.push(probe_extra(probe, template.extra.get(idx))?);
+    }
+
+    let ltpb = LightProbeData::<LittleEndian>::new(
+        template.head.clone(),
+        textures,
+        extra,
+        template.texture_meta.clone(),
+    );
+    let mut file =
+        BufWriter::new(File::create(&args.output).with_context(|| {
+            format!("Failed to create output file '{}'", args.output.display())
+        })?);
+    let meta = SLightProbeMetaData {
+        unk1: template_meta.unk1,
+        unk2: template_meta.unk2,
+        ..ltpb.write(&mut file)?
+    };
+
+    footer.write_with_meta(&mut file, &meta)?;
+    file.flush()?;
+    Ok(())
+}
+
+/// Reads a probe's extra fields from the sidecar, falling back to the template probe.
+fn probe_extra(probe: &Value, template: Option<&LightProbeExtra>) -> Result<LightProbeExtra> {
+    let mut out = template.cloned().unwrap_or_default();
+    let extra = &probe["extra"];
+    if let Some(vec) = extra["vec"].as_array() {
+        let v = |i: usize| -> Result<i32> {
+            vec.get(i)
+                .and_then(Value::as_i64)
+                .map(|v| v as i32)
+                .ok_or_else(|| anyhow!("Invalid probe extra vector"))
+        };
+        out.vec = CVector3i { x: v(0)?, y: v(1)?, z: v(2)? };
+    }
+    if let Some(unk) = extra["unk"].as_u64() {
+        out.unk = unk as u32;
+    }
+    Ok(out)
+}
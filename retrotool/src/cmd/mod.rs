@@ -1,5 +1,9 @@
 pub mod clsn;
 pub mod cmdl;
 pub mod fmv0;
+pub mod ltpb;
+pub mod mtrl;
 pub mod pak;
+pub mod room;
+pub mod templates;
 pub mod txtr;
@@ -0,0 +1,63 @@
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use argh::FromArgs;
+use retrolib::{
+    format::{
+        cmdl::ModelData,
+        foot::{locate_asset_id, locate_meta},
+        mtrl::MaterialData,
+    },
+    util::file::map_file,
+};
+use zerocopy::LittleEndian;
+
+#[derive(FromArgs, PartialEq, Debug)]
+/// process MTRL files
+#[argh(subcommand, name = "mtrl")]
+pub struct Args {
+    #[argh(subcommand)]
+    command: SubCommand,
+}
+
+#[derive(FromArgs, PartialEq, Debug)]
+#[argh(subcommand)]
+enum SubCommand {
+    Info(InfoArgs),
+}
+
+#[derive(FromArgs, PartialEq, Eq, Debug)]
+/// prints the size of an MTRL file and the model materials using it
+#[argh(subcommand, name = "info")]
+pub struct InfoArgs {
+    #[argh(positional)]
+    /// input MTRL
+    input: PathBuf,
+    #[argh(option, short = 'm')]
+    /// model (CMDL/SMDL/WMDL) to list materials using this shader from
+    model: Vec<PathBuf>,
+}
+
+pub fn run(args: Args) -> Result<()> {
+    match args.command {
+        SubCommand::Info(c_args) => info(c_args),
+    }
+}
+
+fn info(args: InfoArgs) -> Result<()> {
+    let data = map_file(&args.input)?;
+    let id = locate_asset_id::<LittleEndian>(&data)?;
+    let meta = locate_meta::<LittleEndian>(&data)?;
+    let mtrl = MaterialData::<LittleEndian>::slice(&data, meta)?;
+    log::info!("Shader {id}: {} bytes inflated (not decoded)", mtrl.decompressed.len());
+    for path in &args.model {
+        let data = map_file(path)?;
+        let meta = locate_meta::<LittleEndian>(&data)?;
+        let model = ModelData::<LittleEndian>::slice(&data, meta)
+            .with_context(|| format!("Failed to read model '{}'", path.display()))?;
+        for (idx, mat) in MaterialData::<LittleEndian>::users(id, &model.mtrl.materials) {
+            log::info!("{}: material {idx} '{}' uses this shader", path.display(), mat.name);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,267 @@
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, Cursor, Write},
+    path::PathBuf,
+};
+
+use anyhow::{ensure, Context, Result};
+use argh::FromArgs;
+use retrolib::{
+    format::{rfrm::FormDescriptor, room::RoomData},
+    util::{
+        file::map_file,
+        templates::{load_game_templates, DEFAULT_GAME},
+        world::{RoomEdgeKind, WorldGraph},
+    },
+};
+use serde_json::json;
+use zerocopy::LittleEndian;
+
+#[derive(FromArgs, PartialEq, Debug)]
+/// process ROOM files
+#[argh(subcommand, name = "room")]
+pub struct Args {
+    #[argh(subcommand)]
+    command: SubCommand,
+}
+
+#[derive(FromArgs, PartialEq, Debug)]
+#[argh(subcommand)]
+enum SubCommand {
+    Graph(GraphArgs),
+    Export(ExportArgs),
+    Import(ImportArgs),
+    Verify(VerifyArgs),
+}
+
+#[derive(FromArgs, PartialEq, Eq, Debug)]
+/// writes the room connectivity graph of a set of PAKs
+#[argh(subcommand, name = "graph")]
+pub struct GraphArgs {
+    #[argh(positional)]
+    /// input PAK files
+    inputs: Vec<PathBuf>,
+    #[argh(option, short = 'd')]
+    /// output GraphViz DOT file
+    dot: Option<PathBuf>,
+    #[argh(option, short = 'j')]
+    /// output JSON file
+    json: Option<PathBuf>,
+}
+
+#[derive(FromArgs, PartialEq, Eq, Debug)]
+/// exports ROOM layers and script properties to JSON
+#[argh(subcommand, name = "export")]
+pub struct ExportArgs {
+    #[argh(positional)]
+    /// input ROOM file
+    input: PathBuf,
+    #[argh(positional)]
+    /// output JSON file
+    output: PathBuf,
+    #[argh(option, default = "DEFAULT_GAME.to_string()")]
+    /// game whose bundled script templates are used (default: mp1r)
+    game: String,
+    #[argh(option)]
+    /// script templates directory, overrides --game
+    templates: Option<PathBuf>,
+}
+
+#[derive(FromArgs, PartialEq, Eq, Debug)]
+/// imports ROOM layers and script properties from JSON
+#[argh(subcommand, name = "import")]
+pub struct ImportArgs {
+    #[argh(positional)]
+    /// input JSON file
+    input: PathBuf,
+    #[argh(option, short = 't')]
+    /// template ROOM file (instances, headers and lighting)
+    template: PathBuf,
+    #[argh(positional)]
+    /// output ROOM file
+    output: PathBuf,
+    #[argh(option, default = "DEFAULT_GAME.to_string()")]
+    /// game whose bundled script templates are used (default: mp1r)
+    game: String,
+    #[argh(option)]
+    /// script templates directory, overrides --game
+    templates: Option<PathBuf>,
+}
+
+#[derive(FromArgs, PartialEq, Eq, Debug)]
+/// checks that ROOM files are rewritten byte-identically
+#[argh(subcommand, name = "verify")]
+pub struct VerifyArgs {
+    #[argh(positional)]
+    /// input ROOM files
+    inputs: Vec<PathBuf>,
+    #[argh(option, default = "DEFAULT_GAME.to_string()")]
+    /// game whose bundled script templates are used (default: mp1r)
+    game: String,
+    #[argh(option)]
+    /// script templates directory, overrides --game
+    templates: Option<PathBuf>,
+}
+
+pub fn run(args: Args) -> Result<()> {
+    match args.command {
+        SubCommand::Graph(c_args) => graph(c_args),
+        SubCommand::Export(c_args) => export(c_args),
+        SubCommand::Import(c_args) => import(c_args),
+        SubCommand::Verify(c_args) => verify(c_args),
+    }
+}
+
+fn export(args: ExportArgs) -> Result<()> {
+    let db = load_game_templates(&args.game, args.templates.as_deref())?;
+    let data = map_file(&args.input)?;
+    let room = RoomData::<LittleEndian>::slice(&data, Some(&*db))
+        .with_context(|| format!("Failed to read room '{}'", args.input.display()))?;
+    let out = room.export_script()?;
+    let file = File::create(&args.output)
+        .with_context(|| format!("Failed to create output file '{}'", args.output.display()))?;
+    let mut writer = BufWriter::new(file);
+    serde_json::to_writer_pretty(&mut writer, &out)?;
+    writer.flush()?;
+    Ok(())
+}
+
+fn import(args: ImportArgs) -> Result<()> {
+    let db = load_game_templates(&args.game, args.templates.as_deref())?;
+    let data = map_file(&args.template)?;
+    let mut room = RoomData::<LittleEndian>::slice(&data, Some(&*db))
+        .with_context(|| format!("Failed to read room '{}'", args.template.display()))?;
+    let file = File::open(&args.input)
+        .with_context(|| format!("Failed to open input file '{}'", args.input.display()))?;
+    let value: serde_json::Value = serde_json::from_reader(BufReader::new(file))?;
+    room.import_script(&value, &db)
+        .with_context(|| format!("Failed to import '{}'", args.input.display()))?;
+    let mut file =
+        BufWriter::new(File::create(&args.output).with_context(|| {
+            format!("Failed to create output file '{}'", args.output.display())
+        })?);
+    room.write(&mut file)?;
+    file.flush()?;
+    Ok(())
+}
+
+fn verify(args: VerifyArgs) -> Result<()> {
+    let db = load_game_templates(&args.game, args.templates.as_deref())?;
+    let mut failed = 0;
+    for path in &args.inputs {
+        let data = map_file(path)?;
+        let room = RoomData::<LittleEndian>::slice(&data, Some(&*db))
+            .with_context(|| format!("Failed to read room '{}'", path.display()))?;
+        // Only the ROOM form is rewritten; anything after it (FOOT) is not compared.
+        let (_, _, remain) = FormDescriptor::<LittleEndian>::slice(&data)?;
+        let expected = &data[..data.len() - remain.len()];
+        let mut out = Cursor::new(Vec::new());
+        room.write(&mut out)?;
+        let out = out.into_inner();
+        if out == expected {
+            log::info!("{}: OK", path.display());
+            continue;
+        }
+        failed += 1;
+        match out.iter().zip(expected).position(|(a, b)| a != b) {
+            Some(offset) => log::error!("{}: mismatch at {offset:#X}", path.display()),
+            None => log::error!(
+                "{}: size mismatch ({:#X} written, {:#X} expected)",
+                path.display(),
+                out.len(),
+                expected.len()
+            ),
+        }
+    }
+    ensure!(failed == 0, "{failed} of {} rooms did not round-trip", args.inputs.len());
+    Ok(())
+}
+
+fn graph(args: GraphArgs) -> Result<()> {
+    let mut graph = WorldGraph::default();
+    for path in &args.inputs {
+        log::info!("Reading {}", path.display());
+        let data = map_file(path)?;
+        graph
+            .add_package::<LittleEndian>(&data)
+            .with_context(|| format!("Failed to read package '{}'", path.display()))?;
+    }
+    let unresolved = graph.edges.iter().filter(|e| !graph.is_resolved(e)).count();
+    let undecoded = graph.rooms.values().filter(|r| r.docks_undecoded).count();
+    log::info!(
+        "{} rooms ({} failed to read, {} with undecoded docks), {} connections ({} unresolved)",
+        graph.rooms.len(),
+        graph.failed.len(),
+        undecoded,
+        graph.edges.len(),
+        unresolved
+    );
+    let check = graph.dock_check();
+    if check.is_symmetric() {
+        log::info!("All {} resolved dock connections are reciprocated", check.checked);
+    } else {
+        log::warn!(
+            "Only {} of {} resolved dock connections are reciprocated; \
+             the DOCK layout may be wrong, don't rely on one-sided dock edges",
+            check.reciprocated,
+            check.checked
+        );
+    }
+
+    if let Some(path) = &args.dot {
+        let mut file = BufWriter::new(
+            File::create(path)
+                .with_context(|| format!("Failed to create output file '{}'", path.display()))?,
+        );
+        graph.write_dot(&mut file)?;
+        file.flush()?;
+    }
+    if let Some(path) = &args.json {
+        let rooms = graph
+            .rooms
+            .values()
+            .map(|room| {
+                json!({
+                    "id": room.id.to_string(),
+                    "name": room.name,
+                    "parent": room.parent_id.map(|id| id.to_string()),
+                    "docks": room.dock_ids.iter().map(|id| id.to_string()).collect::<Vec<_>>(),
+                    "docks_decoded": !room.docks_undecoded,
+                })
+            })
+            .collect::<Vec<_>>();
+        let edges = graph
+            .edges
+            .iter()
+            .map(|edge| {
+                json!({
+                    "kind": match edge.kind {
+                        RoomEdgeKind::Dock => "dock",
+                        RoomEdgeKind::Parent => "parent",
+                    },
+                    "from_room": edge.from_room.to_string(),
+                    "from_dock": edge.from_dock.map(|id| id.to_string()),
+                    "to_room": edge.to_room.to_string(),
+                    "to_dock": edge.to_dock.map(|id| id.to_string()),
+                    "resolved": graph.is_resolved(edge),
+                    "reciprocated": graph.is_reciprocated(edge),
+                })
+            })
+            .collect::<Vec<_>>();
+        let file = File::create(path)
+            .with_context(|| format!("Failed to create output file '{}'", path.display()))?;
+        let failed = graph
+            .failed
+            .iter()
+            .map(|(id, error)| json!({ "id": id.to_string(), "error": error }))
+            .collect::<Vec<_>>();
+        let out = json!({
+            "rooms": rooms,
+            "edges": edges,
+            "failed": failed,
+            "dock_check": { "checked": check.checked, "reciprocated": check.reciprocated },
+        });
+        serde_json::to_writer_pretty(BufWriter::new(file), &out)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,213 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs::File,
+    io::BufWriter,
+    path::PathBuf,
+};
+
+use anyhow::{Context, Result};
+use argh::FromArgs;
+use retrolib::{
+    format::{
+        pack::Package,
+        room::{validate_object, ObjectValidation, RoomData, K_FORM_ROOM},
+    },
+    util::{
+        file::map_file,
+        templates::{load_game_templates, HexU32, DEFAULT_GAME},
+    },
+};
+use serde_json::json;
+use zerocopy::LittleEndian;
+
+#[derive(FromArgs, PartialEq, Debug)]
+/// process script object templates
+#[argh(subcommand, name = "templates")]
+pub struct Args {
+    #[argh(subcommand)]
+    command: SubCommand,
+}
+
+#[derive(FromArgs, PartialEq, Debug)]
+#[argh(subcommand)]
+enum SubCommand {
+    Validate(ValidateArgs),
+}
+
+#[derive(FromArgs, PartialEq, Eq, Debug)]
+/// parses every ROOM in a set of PAKs and reports template coverage
+#[argh(subcommand, name = "validate")]
+pub struct ValidateArgs {
+    #[argh(positional)]
+    /// input PAK files
+    inputs: Vec<PathBuf>,
+    #[argh(option, short = 'j')]
+    /// output JSON report
+    json: Option<PathBuf>,
+    #[argh(option, default = "DEFAULT_GAME.to_string()")]
+    /// game whose bundled script templates are used (default: mp1r)
+    game: String,
+    #[argh(option)]
+    /// script templates directory, overrides --game
+    templates: Option<PathBuf>,
+}
+
+pub fn run(args: Args) -> Result<()> {
+    match args.command {
+        SubCommand::Validate(c_args) => validate(c_args),
+    }
+}
+
+#[derive(Default)]
+struct TypeStats {
+    name: Option<String>,
+    count: usize,
+    /// Parsed without leftover bytes
+    parsed: usize,
+    missing: usize,
+    failed: usize,
+    unconsumed: usize,
+    errors: BTreeMap<String, usize>,
+    unknown_properties: BTreeMap<String, usize>,
+}
+
+fn validate(args: ValidateArgs) -> Result<()> {
+    let db = load_game_templates(&args.game, args.templates.as_deref())?;
+    let mut stats = BTreeMap::<HexU32, TypeStats>::new();
+    let mut seen = HashSet::new();
+    let mut rooms = 0usize;
+    let mut room_errors = 0usize;
+    for path in &args.inputs {
+        log::info!("Reading {}", path.display());
+        let data = map_file(path)?;
+        let entries = Package::<LittleEndian>::read_sparse(&data)
+            .with_context(|| format!("Failed to read package '{}'", path.display()))?;
+        for entry in entries {
+            if entry.kind != K_FORM_ROOM || !seen.insert(entry.id) {
+                continue;
+            }
+            let room_data = Package::<LittleEndian>::read_asset(&data, entry.id)?;
+            // Objects are only parsed by validate_object below
+            let room = match RoomData::<LittleEndian>::slice(&room_data, None) {
+                Ok(room) => room,
+                Err(e) => {
+                    log::warn!("Failed to read room {}: {:?}", entry.id, e);
+                    room_errors += 1;
+                    continue;
+                }
+            };
+            rooms += 1;
+
+            let string_pool = room.string_pool.as_ref();
+            let allocations = room
+                .instance_data
+                .iter()
+                .flat_map(|i| &i.connections)
+                .flat_map(|c| [&c.event_criteria, &c.action_payload])
+                .filter(|a| a.type_id != 0)
+                .map(|a| (a.type_id, a.data.as_slice()));
+            let objects = room
+                .component_properties
+                .iter()
+                .map(|p| (p.component_type, p.data.as_slice()))
+                .chain(allocations);
+            for (id, data) in objects {
+                let stats = stats.entry(HexU32(id)).or_insert_with(|| TypeStats {
+                    name: db.find_object(id).0.cloned(),
+                    ..Default::default()
+                });
+                stats.count += 1;
+                match validate_object(&db, string_pool, id, data) {
+                    ObjectValidation::MissingTemplate => stats.missing += 1,
+                    ObjectValidation::Failed(e) => {
+                        stats.failed += 1;
+                        *stats.errors.entry(e).or_default() += 1;
+                    }
+                    ObjectValidation::Parsed { unconsumed, unknown_properties } => {
+                        if unconsumed > 0 {
+                            stats.unconsumed += 1;
+                        } else {
+                            stats.parsed += 1;
+                        }
+                        for path in unknown_properties {
+                            *stats.unknown_properties.entry(path).or_default() += 1;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    // Most common types first, as they matter most for coverage
+    let mut sorted = stats.iter().collect::<Vec<_>>();
+    sorted.sort_by(|(_, a), (_, b)| b.count.cmp(&a.count));
+    let total = stats.values().map(|s| s.count).sum::<usize>();
+    let parsed = stats.values().map(|s| s.parsed).sum::<usize>();
+    log::info!(
+        "{} rooms ({} failed to read), {} object types, {} objects",
+        rooms,
+        room_errors,
+        stats.len(),
+        total
+    );
+    log::info!(
+        "Fully parsed: {}/{} objects ({:.1}%)",
+        parsed,
+        total,
+        if total > 0 { parsed as f64 * 100.0 / total as f64 } else { 100.0 }
+    );
+    let mut covered = 0usize;
+    for (id, stats) in &sorted {
+        if stats.parsed == stats.count && stats.unknown_properties.is_empty() {
+            covered += 1;
+            continue;
+        }
+        log::info!(
+            "{} {}: {} objects, {} parsed, {} missing template, {} failed, {} unconsumed",
+            id,
+            stats.name.as_deref().unwrap_or("(unnamed)"),
+            stats.count,
+            stats.parsed,
+            stats.missing,
+            stats.failed,
+            stats.unconsumed
+        );
+        for (error, count) in &stats.errors {
+            log::info!("  Error ({count}x): {error}");
+        }
+        for (path, count) in &stats.unknown_properties {
+            log::info!("  Unknown property ({count}x): {path}");
+        }
+    }
+    log::info!("{} object types fully covered", covered);
+
+    if let Some(path) = &args.json {
+        let types = sorted
+            .iter()
+            .map(|(id, stats)| {
+                json!({
+                    "id": id,
+                    "name": stats.name,
+                    "count": stats.count,
+                    "parsed": stats.parsed,
+                    "missing_template": stats.missing,
+                    "failed": stats.failed,
+                    "unconsumed": stats.unconsumed,
+                    "errors": stats.errors,
+                    "unknown_properties": stats.unknown_properties,
+                })
+            })
+            .collect::<Vec<_>>();
+        let out = json!({
+            "rooms": rooms,
+            "room_errors": room_errors,
+            "objects": total,
+            "parsed": parsed,
+            "types": types,
+        });
+        let file = File::create(path)
+            .with_context(|| format!("Failed to create output file '{}'", path.display()))?;
+        serde_json::to_writer_pretty(BufWriter::new(file), &out)?;
+    }
+    Ok(())
+}
@@ -1,14 +1,29 @@
 use std::{
+    cmp::max,
     fs::File,
-    io::{BufWriter, Write},
+    io::{BufWriter, Cursor, Write},
     path::PathBuf,
 };
 
-use anyhow::{bail, Context, Result};
+use anyhow::{bail, ensure, Context, Result};
 use argh::FromArgs;
+use binrw::{BinReaderExt, Endian};
+use image::imageops::FilterType;
 use retrolib::{
-    format::{foot::locate_meta, txtr::TextureData},
-    util::{astc::write_astc, dds::write_dds, file::map_file},
+    format::{
+        chunk::ChunkDescriptor,
+        foot::{locate_meta, Footer},
+        rfrm::FormDescriptor,
+        txtr::{
+            calculate_mip_sizes, encode_image, ETextureFormat, ETextureType, STextureHeader,
+            STextureMetaData, TextureData, K_CHUNK_HEAD, K_FORM_TXTR,
+        },
+    },
+    util::{
+        astc::write_astc,
+        dds::{read_dds, write_dds},
+        file::map_file,
+    },
 };
 use zerocopy::LittleEndian;
 
@@ -24,6 +39,7 @@ pub struct Args {
 #[argh(subcommand)]
 enum SubCommand {
     Convert(ConvertArgs),
+    Import(ImportArgs),
 }
 
 #[derive(FromArgs, PartialEq, Eq, Debug)]
@@ -38,10 +54,32 @@ pub struct ConvertArgs {
     astc: bool,
 }
 
+#[derive(FromArgs, PartialEq, Eq, Debug)]
+/// builds a TXTR file from DDS/PNG
+#[argh(subcommand, name = "import")]
+pub struct ImportArgs {
+    #[argh(positional)]
+    /// input DDS or PNG
+    input: PathBuf,
+    #[argh(positional)]
+    /// original TXTR (sampler data, metadata and footer are reused)
+    template: PathBuf,
+    #[argh(positional)]
+    /// output TXTR
+    output: PathBuf,
+    #[argh(option, short = 'f')]
+    /// texture format for PNG input (default: template format)
+    format: Option<ETextureFormat>,
+    #[argh(option, short = 'm')]
+    /// mip count for PNG input (default: full chain)
+    mips: Option<u32>,
+}
+
 #[allow(unused)]
 pub fn run(args: Args) -> Result<()> {
     match args.command {
         SubCommand::Convert(c_args) => convert(c_args),
+        SubCommand::Import(c_args) => import(c_args),
     }
 }
 
@@ -78,3 +116,80 @@ fn convert(args: ConvertArgs) -> Result<()> {
 
     Ok(())
 }
+
+fn import(args: ImportArgs) -> Result<()> {
+    let (template_head, template_meta, footer) = {
+        let data = map_file(&args.template)?;
+        let meta: STextureMetaData =
+            Cursor::new(locate_meta::<LittleEndian>(&data)?).read_type(Endian::Little)?;
+        let (txtr_desc, txtr_data, _) = FormDescriptor::<LittleEndian>::slice(&data)?;
+        ensure!(txtr_desc.id == K_FORM_TXTR);
+        let (head_desc, head_data, _) = ChunkDescriptor::<LittleEndian>::slice(txtr_data)?;
+        ensure!(head_desc.id == K_CHUNK_HEAD);
+        let head: STextureHeader = Cursor::new(head_data).read_type(Endian::Little)?;
+        (head, meta, Footer::<LittleEndian>::slice(&data)?)
+    };
+
+    let is_dds = args.input.extension().map_or(false, |ext| ext.eq_ignore_ascii_case("dds"));
+    let (kind, format, width, height, layers, mip_count, data) = if is_dds {
+        ensure!(args.mips.is_none(), "--mips only applies to PNG input, DDS mips are used as-is");
+        let mut file = File::open(&args.input)
+            .with_context(|| format!("Failed to open input file '{}'", args.input.display()))?;
+        let dds = read_dds(&mut file)?;
+        if let Some(format) = args.format {
+            ensure!(format == dds.format, "DDS format {} doesn't match {}", dds.format, format);
+        }
+        (dds.kind, dds.format, dds.width, dds.height, dds.layers, dds.mip_count, dds.data)
+    } else {
+        let image = image::open(&args.input)
+            .with_context(|| format!("Failed to open input file '{}'", args.input.display()))?;
+        let format = args.format.unwrap_or(template_head.format);
+        let (width, height) = (image.width(), image.height());
+        let mip_count = args.mips.unwrap_or_else(|| 32 - max(width, height).leading_zeros());
+        let mut data = encode_image(format, &image)
+            .with_context(|| format!("PNG input requires an uncompressed format, got {format}"))?;
+        for mip in 1..mip_count {
+            let mip_image = image.resize_exact(
+                max(width >> mip, 1),
+                max(height >> mip, 1),
+                FilterType::Triangle,
+            );
+            data.extend(encode_image(format, &mip_image)?);
+        }
+        (ETextureType::D2, format, width, height, 1, mip_count, data)
+    };
+
+    let head = STextureHeader {
+        kind,
+        format,
+        width,
+        height,
+        layers,
+        tile_mode: template_head.tile_mode,
+        swizzle: template_head.swizzle,
+        mip_sizes: calculate_mip_sizes(kind, format, width, height, layers, mip_count),
+        sampler_data: template_head.sampler_data,
+    };
+    log::info!("Texture info:");
+    log::info!("  Type: {}", head.kind);
+    log::info!("  Format: {}", head.format);
+    log::info!("  Size: {}x{}x{}", head.width, head.height, head.layers);
+    log::info!("  Mip count: {}", head.mip_sizes.len());
+
+    let mut file =
+        BufWriter::new(File::create(&args.output).with_context(|| {
+            format!("Failed to create output file '{}'", args.output.display())
+        })?);
+    let meta = TextureData::<LittleEndian>::new(head, data).write(&mut file)?;
+    let meta = STextureMetaData {
+        unk1: template_meta.unk1,
+        unk2: template_meta.unk2,
+        alloc_category: template_meta.alloc_category,
+        align: template_meta.align,
+        ..meta
+    };
+
+    footer.write_with_meta(&mut file, &meta)?;
+    file.flush()?;
+    Ok(())
+}
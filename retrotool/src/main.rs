@@ -1,5 +1,6 @@
 mod argh_version;
 mod cmd;
+mod util;
 
 use argh::FromArgs;
 
@@ -16,7 +17,11 @@ pub enum SubCommand {
     Clsn(cmd::clsn::Args),
     Cmdl(cmd::cmdl::Args),
     Fmv0(cmd::fmv0::Args),
+    Ltpb(cmd::ltpb::Args),
+    Mtrl(cmd::mtrl::Args),
     Pak(cmd::pak::Args),
+    Room(cmd::room::Args),
+    Templates(cmd::templates::Args),
     Txtr(cmd::txtr::Args),
 }
 
@@ -32,7 +37,11 @@ fn main() {
         SubCommand::Clsn(args) => cmd::clsn::run(args),
         SubCommand::Cmdl(args) => cmd::cmdl::run(args),
         SubCommand::Fmv0(args) => cmd::fmv0::run(args),
+        SubCommand::Ltpb(args) => cmd::ltpb::run(args),
+        SubCommand::Mtrl(args) => cmd::mtrl::run(args),
         SubCommand::Pak(args) => cmd::pak::run(args),
+        SubCommand::Room(args) => cmd::room::run(args),
+        SubCommand::Templates(args) => cmd::templates::run(args),
         SubCommand::Txtr(args) => cmd::txtr::run(args),
     };
     if let Err(e) = result {
@@ -0,0 +1,241 @@
+use std::{fs, fs::File, io::BufReader, path::Path};
+
+use anyhow::{anyhow, bail, ensure, Context, Result};
+use gltf_json as json;
+use json::{accessor::ComponentType, validation::Checked::Valid};
+
+pub type Mat4 = [f32; 16];
+
+pub const MAT4_IDENTITY: Mat4 =
+    [1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0];
+
+/// Column-major 4x4 multiply.
+pub fn mat4_mul(a: &Mat4, b: &Mat4) -> Mat4 {
+    std::array::from_fn(|i| {
+        let (col, row) = (i / 4, i % 4);
+        (0..4).map(|k| a[k * 4 + row] * b[col * 4 + k]).sum()
+    })
+}
+
+pub fn node_matrix(node: &json::Node) -> Mat4 {
+    if let Some(matrix) = node.matrix {
+        return matrix;
+    }
+    let [tx, ty, tz] = node.translation.unwrap_or([0.0; 3]);
+    let [x, y, z, w] = node.rotation.map(|r| r.0).unwrap_or([0.0, 0.0, 0.0, 1.0]);
+    let [sx, sy, sz] = node.scale.unwrap_or([1.0; 3]);
+    let (xx, yy, zz) = (x * x, y * y, z * z);
+    let (xy, xz, yz, xw, yw, zw) = (x * y, x * z, y * z, x * w, y * w, z * w);
+    #[rustfmt::skip]
+    let m = [
+        (1.0 - 2.0 * (yy + zz)) * sx, 2.0 * (xy + zw) * sx, 2.0 * (xz - yw) * sx, 0.0,
+        2.0 * (xy - zw) * sy, (1.0 - 2.0 * (xx + zz)) * sy, 2.0 * (yz + xw) * sy, 0.0,
+        2.0 * (xz + yw) * sz, 2.0 * (yz - xw) * sz, (1.0 - 2.0 * (xx + yy)) * sz, 0.0,
+        tx, ty, tz, 1.0,
+    ];
+    m
+}
+
+pub fn transform_point(m: &Mat4, p: [f32; 3]) -> [f32; 3] {
+    std::array::from_fn(|i| m[i] * p[0] + m[4 + i] * p[1] + m[8 + i] * p[2] + m[12 + i])
+}
+
+/// Transforms a direction, ignoring translation. Not renormalized.
+pub fn transform_vector(m: &Mat4, v: [f32; 3]) -> [f32; 3] {
+    std::array::from_fn(|i| m[i] * v[0] + m[4 + i] * v[1] + m[8 + i] * v[2])
+}
+
+/// A glTF document with its external buffers loaded.
+pub struct GltfInput {
+    pub root: json::Root,
+    pub buffers: Vec<Vec<u8>>,
+}
+
+impl GltfInput {
+    pub fn open(path: &Path) -> Result<Self> {
+        let file = File::open(path)
+            .with_context(|| format!("Failed to open input file '{}'", path.display()))?;
+        let root: json::Root = serde_json::from_reader(BufReader::new(file))?;
+        let dir = path.parent().unwrap_or_else(|| Path::new("."));
+        let buffers = root
+            .buffers
+            .iter()
+            .map(|buffer| match &buffer.uri {
+                Some(uri) if !uri.starts_with("data:") => fs::read(dir.join(uri))
+                    .with_context(|| format!("Failed to read buffer '{uri}'")),
+                _ => bail!("Only external glTF buffers are supported"),
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self { root, buffers })
+    }
+
+    pub fn node(&self, idx: json::Index<json::Node>) -> Result<&json::Node> {
+        self.root.nodes.get(idx.value()).ok_or_else(|| anyhow!("Invalid node {}", idx.value()))
+    }
+
+    pub fn mesh(&self, idx: json::Index<json::Mesh>) -> Result<&json::Mesh> {
+        self.root.meshes.get(idx.value()).ok_or_else(|| anyhow!("Invalid mesh {}", idx.value()))
+    }
+
+    pub fn material(&self, idx: json::Index<json::Material>) -> Result<&json::Material> {
+        self.root
+            .materials
+            .get(idx.value())
+            .ok_or_else(|| anyhow!("Invalid material {}", idx.value()))
+    }
+
+    /// Resolves an accessor to its component type, component count, element stride and data.
+    fn accessor_data(
+        &self,
+        idx: json::Index<json::Accessor>,
+    ) -> Result<(&json::Accessor, ComponentType, usize, usize, &[u8])> {
+        let accessor = self
+            .root
+            .accessors
+            .get(idx.value())
+            .ok_or_else(|| anyhow!("Invalid accessor {}", idx.value()))?;
+        let view_idx =
+            accessor.buffer_view.ok_or_else(|| anyhow!("Sparse accessors are not supported"))?;
+        let view = self
+            .root
+            .buffer_views
+            .get(view_idx.value())
+            .ok_or_else(|| anyhow!("Invalid buffer view {}", view_idx.value()))?;
+        let Valid(json::accessor::GenericComponentType(component_type)) = accessor.component_type
+        else {
+            bail!("Invalid accessor component type");
+        };
+        let Valid(type_) = &accessor.type_ else {
+            bail!("Invalid accessor type");
+        };
+        let size = component_size(component_type);
+        let count = type_.multiplicity();
+        let stride = view.byte_stride.map_or(size * count, |s| s as usize);
+        let buffer = self
+            .buffers
+            .get(view.buffer.value())
+            .ok_or_else(|| anyhow!("Invalid buffer {}", view.buffer.value()))?;
+        let offset = view.byte_offset.unwrap_or_default() as usize + accessor.byte_offset as usize;
+        let data = buffer
+            .get(offset..)
+            .ok_or_else(|| anyhow!("Accessor {} out of bounds", idx.value()))?;
+        Ok((accessor, component_type, count, stride, data))
+    }
+
+    /// Reads an accessor as a list of components, converted to f32.
+    /// Normalized integer accessors are mapped to [0, 1] or [-1, 1].
+    pub fn read_accessor(&self, idx: json::Index<json::Accessor>) -> Result<Vec<Vec<f32>>> {
+        let (accessor, component_type, count, stride, data) = self.accessor_data(idx)?;
+        let size = component_size(component_type);
+        let normalized = accessor.normalized;
+        (0..accessor.count as usize)
+            .map(|i| {
+                (0..count)
+                    .map(|c| {
+                        let offset = i * stride + c * size;
+                        let b = data
+                            .get(offset..offset + size)
+                            .ok_or_else(|| anyhow!("Accessor out of bounds"))?;
+                        Ok(match component_type {
+                            ComponentType::I8 if normalized => {
+                                (b[0] as i8 as f32 / 127.0).max(-1.0)
+                            }
+                            ComponentType::I8 => b[0] as i8 as f32,
+                            ComponentType::U8 if normalized => b[0] as f32 / 255.0,
+                            ComponentType::U8 => b[0] as f32,
+                            ComponentType::I16 => {
+                                let v = i16::from_le_bytes([b[0], b[1]]) as f32;
+                                if normalized {
+                                    (v / 32767.0).max(-1.0)
+                                } else {
+                                    v
+                                }
+                            }
+                            ComponentType::U16 => {
+                                let v = u16::from_le_bytes([b[0], b[1]]) as f32;
+                                if normalized {
+                                    v / 65535.0
+                                } else {
+                                    v
+                                }
+                            }
+                            ComponentType::U32 => {
+                                u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f32
+                            }
+                            ComponentType::F32 => f32::from_le_bytes([b[0], b[1], b[2], b[3]]),
+                        })
+                    })
+                    .collect::<Result<Vec<f32>>>()
+            })
+            .collect()
+    }
+
+    /// Reads an index accessor. Without one, primitives are drawn in vertex order.
+    pub fn read_indices(
+        &self,
+        indices: Option<json::Index<json::Accessor>>,
+        vertex_count: usize,
+    ) -> Result<Vec<u32>> {
+        let Some(indices) = indices else {
+            return Ok((0..vertex_count as u32).collect());
+        };
+        let (accessor, component_type, count, stride, data) = self.accessor_data(indices)?;
+        ensure!(count == 1, "Index accessor must be scalar");
+        let size = component_size(component_type);
+        (0..accessor.count as usize)
+            .map(|i| {
+                let offset = i * stride;
+                let b = data
+                    .get(offset..offset + size)
+                    .ok_or_else(|| anyhow!("Index accessor out of bounds"))?;
+                Ok(match component_type {
+                    ComponentType::U8 => b[0] as u32,
+                    ComponentType::U16 => u16::from_le_bytes([b[0], b[1]]) as u32,
+                    ComponentType::U32 => u32::from_le_bytes([b[0], b[1], b[2], b[3]]),
+                    _ => bail!("Invalid index component type {:?}", component_type),
+                })
+            })
+            .collect()
+    }
+
+    /// Collects every mesh instance in the default scene, with its world transform.
+    pub fn mesh_instances(&self) -> Result<Vec<(json::Index<json::Node>, Mat4)>> {
+        let root = &self.root;
+        let scene = root.scene.or_else(|| (!root.scenes.is_empty()).then(|| json::Index::new(0)));
+        let scene_nodes = match scene {
+            Some(scene) => root
+                .scenes
+                .get(scene.value())
+                .ok_or_else(|| anyhow!("Invalid scene {}", scene.value()))?
+                .nodes
+                .clone(),
+            None => (0..root.nodes.len() as u32).map(json::Index::new).collect(),
+        };
+        let mut out = Vec::new();
+        let mut stack: Vec<(json::Index<json::Node>, Mat4)> =
+            scene_nodes.into_iter().rev().map(|n| (n, MAT4_IDENTITY)).collect();
+        let mut visited = 0;
+        while let Some((node_idx, parent_xf)) = stack.pop() {
+            let node = self.node(node_idx)?;
+            // A node hierarchy can't visit more nodes than there are, unless it has a cycle
+            visited += 1;
+            ensure!(visited <= root.nodes.len(), "Node hierarchy contains a cycle");
+            let xf = mat4_mul(&parent_xf, &node_matrix(node));
+            for &child in node.children.iter().flatten().rev() {
+                stack.push((child, xf));
+            }
+            if node.mesh.is_some() {
+                out.push((node_idx, xf));
+            }
+        }
+        Ok(out)
+    }
+}
+
+fn component_size(component_type: ComponentType) -> usize {
+    match component_type {
+        ComponentType::I8 | ComponentType::U8 => 1,
+        ComponentType::I16 | ComponentType::U16 => 2,
+        ComponentType::U32 | ComponentType::F32 => 4,
+    }
+}